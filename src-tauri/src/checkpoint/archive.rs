@@ -0,0 +1,654 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File};
+use std::path::{Component, Path, PathBuf};
+use tempfile::TempDir;
+use zstd::stream::{copy_decode, Decoder, Encoder};
+
+use super::{
+    chunks,
+    storage::{self, CheckpointStorage, FileRef},
+    Checkpoint, CheckpointPaths, SessionTimeline, TimelineNode,
+};
+
+/// Version of the archive layout written by `export_session`
+const ARCHIVE_FORMAT_VERSION: u32 = 2;
+
+/// Oldest archive layout `import_session` still reads, with a refs directory
+/// per checkpoint instead of a manifest
+const LEGACY_ARCHIVE_FORMAT_VERSION: u32 = 1;
+
+/// Describes the contents of a timeline archive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ArchiveManifest {
+    format_version: u32,
+    project_id: String,
+    session_id: String,
+    exported_at: DateTime<Utc>,
+    checkpoint_ids: Vec<String>,
+    blob_hashes: Vec<String>,
+}
+
+/// Summary of an exported or imported timeline archive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveSummary {
+    /// Session the timeline belongs to after the operation
+    pub session_id: String,
+    /// Project the timeline belongs to after the operation
+    pub project_id: String,
+    /// Number of checkpoints in the archive
+    pub checkpoint_count: usize,
+    /// Number of pool objects in the archive
+    pub blob_count: usize,
+    /// Location of the archive file
+    pub archive_path: PathBuf,
+}
+
+impl CheckpointStorage {
+    /// Bundle a session's timeline, checkpoints and referenced content into a tar+zstd archive
+    ///
+    /// The archive holds `manifest.json`, `timeline.json`,
+    /// `checkpoints/<id>/{metadata.json,messages.jsonl}`, `manifests/<id>.json`,
+    /// the still-compressed pool objects under `objects/<hash>` and the chunks
+    /// of large objects under `chunks/<hash>`.
+    pub fn export_session(
+        &self,
+        project_id: &str,
+        session_id: &str,
+        archive_path: &Path,
+    ) -> Result<ArchiveSummary> {
+        let paths = CheckpointPaths::new(&self.claude_dir, project_id, session_id);
+        let timeline = self.load_timeline(&paths.timeline_file)?;
+
+        let checkpoint_ids = timeline.checkpoint_ids();
+
+        let mut blob_hashes = BTreeSet::new();
+        for checkpoint_id in &checkpoint_ids {
+            for file_ref in Self::read_checkpoint_refs(&paths, checkpoint_id)? {
+                if !file_ref.is_deleted {
+                    blob_hashes.insert(file_ref.hash);
+                }
+            }
+        }
+
+        let manifest = ArchiveManifest {
+            format_version: ARCHIVE_FORMAT_VERSION,
+            project_id: project_id.to_string(),
+            session_id: session_id.to_string(),
+            exported_at: Utc::now(),
+            checkpoint_ids: checkpoint_ids.clone(),
+            blob_hashes: blob_hashes.iter().cloned().collect(),
+        };
+
+        let parent = archive_path
+            .parent()
+            .filter(|p| !p.as_os_str().is_empty())
+            .unwrap_or_else(|| Path::new("."));
+        let temp_file =
+            tempfile::NamedTempFile::new_in(parent).context("Failed to create archive file")?;
+
+        let encoder =
+            Encoder::new(temp_file.reopen()?, 3).context("Failed to start compression")?;
+        let mut builder = tar::Builder::new(encoder);
+
+        append_bytes(
+            &mut builder,
+            "manifest.json",
+            &serde_json::to_vec_pretty(&manifest)?,
+        )?;
+        append_bytes(
+            &mut builder,
+            "timeline.json",
+            &serde_json::to_vec_pretty(&timeline)?,
+        )?;
+
+        for checkpoint_id in &checkpoint_ids {
+            builder
+                .append_path_with_name(
+                    paths.checkpoint_metadata_file(checkpoint_id),
+                    format!("checkpoints/{}/metadata.json", checkpoint_id),
+                )
+                .with_context(|| format!("Failed to archive checkpoint {}", checkpoint_id))?;
+            builder
+                .append_path_with_name(
+                    paths.checkpoint_messages_file(checkpoint_id),
+                    format!("checkpoints/{}/messages.jsonl", checkpoint_id),
+                )
+                .with_context(|| format!("Failed to archive messages of {}", checkpoint_id))?;
+
+            // Checkpoints from before manifests are exported with one as well
+            append_bytes(
+                &mut builder,
+                &format!("manifests/{}.json", checkpoint_id),
+                &serde_json::to_vec(&Self::read_checkpoint_refs(&paths, checkpoint_id)?)?,
+            )?;
+        }
+
+        let mut chunk_hashes = BTreeSet::new();
+        for hash in &blob_hashes {
+            builder
+                .append_path_with_name(paths.content_object_path(hash), format!("objects/{}", hash))
+                .with_context(|| format!("Failed to archive content {}", hash))?;
+            chunk_hashes.extend(
+                self.content_chunks(&paths, hash)?
+                    .into_iter()
+                    .map(|c| c.hash),
+            );
+        }
+        for hash in &chunk_hashes {
+            builder
+                .append_path_with_name(paths.chunk_path(hash), format!("chunks/{}", hash))
+                .with_context(|| format!("Failed to archive chunk {}", hash))?;
+        }
+
+        builder
+            .into_inner()
+            .context("Failed to write archive")?
+            .finish()
+            .context("Failed to finish archive compression")?
+            .sync_all()
+            .context("Failed to sync archive")?;
+        temp_file
+            .persist(archive_path)
+            .map_err(|e| e.error)
+            .context("Failed to move archive into place")?;
+
+        log::info!(
+            "Exported {} checkpoints of session {} to {}",
+            checkpoint_ids.len(),
+            session_id,
+            archive_path.display()
+        );
+
+        Ok(ArchiveSummary {
+            session_id: session_id.to_string(),
+            project_id: project_id.to_string(),
+            checkpoint_count: checkpoint_ids.len(),
+            blob_count: blob_hashes.len(),
+            archive_path: archive_path.to_path_buf(),
+        })
+    }
+
+    /// Import a timeline archive into a project
+    ///
+    /// The session keeps its original ID unless `session_id` is given. Every
+    /// pool object is re-hashed before anything is written. The session is
+    /// built in a staging directory and renamed into place once complete, so
+    /// a failed import never shows up as a session or leaves references
+    /// counted in the pool.
+    pub fn import_session(
+        &self,
+        archive_path: &Path,
+        project_id: &str,
+        session_id: Option<&str>,
+    ) -> Result<ArchiveSummary> {
+        let timelines_dir = CheckpointPaths::timelines_dir(&self.claude_dir, project_id);
+        fs::create_dir_all(&timelines_dir).context("Failed to create timelines directory")?;
+
+        // Unpack next to the pool so objects can be renamed into place
+        let staging = tempfile::Builder::new()
+            .prefix(".import")
+            .tempdir_in(&timelines_dir)
+            .context("Failed to create staging directory")?;
+        unpack_archive(archive_path, &staging)?;
+        let staged = staging.path();
+
+        let manifest: ArchiveManifest = serde_json::from_slice(
+            &fs::read(staged.join("manifest.json")).context("Archive has no manifest")?,
+        )
+        .context("Failed to parse archive manifest")?;
+        if !(LEGACY_ARCHIVE_FORMAT_VERSION..=ARCHIVE_FORMAT_VERSION)
+            .contains(&manifest.format_version)
+        {
+            anyhow::bail!(
+                "Unsupported archive format version {}",
+                manifest.format_version
+            );
+        }
+
+        let session_id = session_id.unwrap_or(&manifest.session_id).to_string();
+        if !is_plain_name(&session_id) {
+            anyhow::bail!("Invalid session ID: {}", session_id);
+        }
+        let paths = CheckpointPaths::new(&self.claude_dir, project_id, &session_id);
+        if paths.timeline_file.exists()
+            && self
+                .load_timeline(&paths.timeline_file)?
+                .root_node
+                .is_some()
+        {
+            anyhow::bail!(
+                "Session {} already has checkpoints in this project",
+                session_id
+            );
+        }
+
+        let mut timeline: SessionTimeline = serde_json::from_slice(
+            &fs::read(staged.join("timeline.json")).context("Archive has no timeline")?,
+        )
+        .context("Failed to parse archived timeline")?;
+        timeline.session_id = session_id.clone();
+        if let Some(root) = &mut timeline.root_node {
+            rewrite_node_ids(root, project_id, &session_id);
+        }
+
+        let checkpoint_ids = timeline.checkpoint_ids();
+        if let Some(id) = checkpoint_ids.iter().find(|id| !is_plain_name(id)) {
+            anyhow::bail!("Archive contains an invalid checkpoint ID: {}", id);
+        }
+
+        // Check every reference and object before touching the project
+        let mut blob_hashes = BTreeSet::new();
+        let mut checkpoint_refs = BTreeMap::new();
+        for checkpoint_id in &checkpoint_ids {
+            let file_refs = read_archived_refs(staged, manifest.format_version, checkpoint_id)?;
+            for file_ref in &file_refs {
+                if !is_relative_path(&file_ref.path) {
+                    anyhow::bail!(
+                        "Archive references a file outside the project: {}",
+                        file_ref.path.display()
+                    );
+                }
+                if !file_ref.is_deleted {
+                    if !is_plain_name(&file_ref.hash) {
+                        anyhow::bail!("Archive contains an invalid content hash");
+                    }
+                    blob_hashes.insert(file_ref.hash.clone());
+                }
+            }
+            checkpoint_refs.insert(checkpoint_id.clone(), file_refs);
+        }
+        let mut chunk_hashes = BTreeSet::new();
+        for hash in &blob_hashes {
+            let mut object = File::open(staged.join("objects").join(hash))
+                .with_context(|| format!("Archive is missing content {}", hash))?;
+            // Hash while decompressing so large content is never held in memory
+            let mut hasher = Sha256::new();
+            match chunks::read_chunk_list(&mut object)? {
+                Some(entries) => {
+                    for chunk in entries {
+                        let chunk_file = File::open(staged.join("chunks").join(&chunk.hash))
+                            .with_context(|| format!("Archive is missing chunk {}", chunk.hash))?;
+                        copy_decode(chunk_file, &mut hasher)
+                            .with_context(|| format!("Archived chunk {} is corrupt", chunk.hash))?;
+                        chunk_hashes.insert(chunk.hash);
+                    }
+                }
+                None => copy_decode(object, &mut hasher)
+                    .with_context(|| format!("Archived content {} is corrupt", hash))?,
+            }
+            if format!("{:x}", hasher.finalize()) != *hash {
+                anyhow::bail!("Archived content {} does not match its hash", hash);
+            }
+        }
+
+        // Build the session next to its final location
+        let building = tempfile::Builder::new()
+            .prefix(".import")
+            .tempdir_in(&timelines_dir)
+            .context("Failed to create staging directory")?;
+        let built_dir = building.path().join("session");
+        let built = CheckpointPaths::with_base_dir(&built_dir, &timelines_dir);
+        fs::create_dir_all(&built.checkpoints_dir)
+            .context("Failed to create checkpoints directory")?;
+        fs::create_dir_all(&built.files_dir).context("Failed to create files directory")?;
+
+        for checkpoint_id in &checkpoint_ids {
+            let staged_dir = staged.join("checkpoints").join(checkpoint_id);
+            let checkpoint: Checkpoint = serde_json::from_slice(
+                &fs::read(staged_dir.join("metadata.json"))
+                    .with_context(|| format!("Archive is missing checkpoint {}", checkpoint_id))?,
+            )
+            .context("Failed to parse archived checkpoint metadata")?;
+            let checkpoint = Checkpoint {
+                session_id: session_id.clone(),
+                project_id: project_id.to_string(),
+                ..checkpoint
+            };
+
+            fs::create_dir_all(built.checkpoint_dir(checkpoint_id))
+                .context("Failed to create checkpoint directory")?;
+            self.write_data(
+                &built.checkpoint_metadata_file(checkpoint_id),
+                serde_json::to_string_pretty(&checkpoint)?.as_bytes(),
+            )
+            .context("Failed to write checkpoint metadata")?;
+            fs::rename(
+                staged_dir.join("messages.jsonl"),
+                built.checkpoint_messages_file(checkpoint_id),
+            )
+            .with_context(|| format!("Archive is missing messages of {}", checkpoint_id))?;
+
+            self.write_checkpoint_manifest(&built, checkpoint_id, &checkpoint_refs[checkpoint_id])?;
+        }
+        self.save_timeline(&built.timeline_file, &timeline)?;
+
+        {
+            let _pool_guard = storage::lock_pool();
+            fs::create_dir_all(paths.content_pool_dir.join("objects"))
+                .context("Failed to create content pool directory")?;
+            if !chunk_hashes.is_empty() {
+                fs::create_dir_all(paths.chunks_dir())
+                    .context("Failed to create chunks directory")?;
+            }
+            for hash in &chunk_hashes {
+                let target = paths.chunk_path(hash);
+                if !target.exists() {
+                    fs::rename(staged.join("chunks").join(hash), &target)
+                        .context("Failed to import chunk into pool")?;
+                }
+            }
+            for hash in &blob_hashes {
+                let target = paths.content_object_path(hash);
+                if !target.exists() {
+                    fs::rename(staged.join("objects").join(hash), &target)
+                        .context("Failed to import content into pool")?;
+                }
+            }
+
+            // Renaming the finished session into place makes it visible. A
+            // session without checkpoints may already be there; it is replaced.
+            let session_dir = timelines_dir.join(&session_id);
+            let replaced_dir = building.path().join("replaced");
+            if session_dir.exists() {
+                fs::rename(&session_dir, &replaced_dir)
+                    .context("Failed to move the existing empty session aside")?;
+            }
+            if let Err(e) = fs::rename(&built_dir, &session_dir) {
+                if replaced_dir.exists() {
+                    if let Err(restore_err) = fs::rename(&replaced_dir, &session_dir) {
+                        log::warn!("Failed to restore session {}: {}", session_id, restore_err);
+                    }
+                }
+                return Err(e).context("Failed to move the imported session into place");
+            }
+
+            if let Err(e) = self.rebuild_refcounts(project_id, &paths) {
+                // Leave no session behind whose content the pool doesn't count
+                if let Err(remove_err) = fs::remove_dir_all(&session_dir) {
+                    log::warn!(
+                        "Failed to remove partly imported session {}: {}",
+                        session_id,
+                        remove_err
+                    );
+                }
+                if let Err(rebuild_err) = self.rebuild_refcounts(project_id, &paths) {
+                    log::warn!("Failed to rebuild pool reference counts: {}", rebuild_err);
+                }
+                return Err(e);
+            }
+        }
+
+        log::info!(
+            "Imported {} checkpoints from {} as session {}",
+            checkpoint_ids.len(),
+            archive_path.display(),
+            session_id
+        );
+
+        Ok(ArchiveSummary {
+            session_id,
+            project_id: project_id.to_string(),
+            checkpoint_count: checkpoint_ids.len(),
+            blob_count: blob_hashes.len(),
+            archive_path: archive_path.to_path_buf(),
+        })
+    }
+}
+
+fn append_bytes<W: std::io::Write>(
+    builder: &mut tar::Builder<W>,
+    name: &str,
+    bytes: &[u8],
+) -> Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(bytes.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(Utc::now().timestamp().max(0) as u64);
+    header.set_cksum();
+    builder
+        .append_data(&mut header, name, bytes)
+        .with_context(|| format!("Failed to write {} to archive", name))
+}
+
+/// Read the file references an archive holds for a checkpoint
+fn read_archived_refs(
+    staged: &Path,
+    format_version: u32,
+    checkpoint_id: &str,
+) -> Result<Vec<FileRef>> {
+    if format_version == LEGACY_ARCHIVE_FORMAT_VERSION {
+        return CheckpointStorage::read_file_refs(&staged.join("refs").join(checkpoint_id));
+    }
+    let manifest_file = staged
+        .join("manifests")
+        .join(format!("{}.json", checkpoint_id));
+    serde_json::from_slice(
+        &fs::read(&manifest_file)
+            .with_context(|| format!("Archive has no manifest for {}", checkpoint_id))?,
+    )
+    .with_context(|| format!("Failed to parse archived manifest of {}", checkpoint_id))
+}
+
+/// Extract the regular files of an archive, rejecting paths outside the archive root
+fn unpack_archive(archive_path: &Path, staging: &TempDir) -> Result<()> {
+    let file = File::open(archive_path)
+        .with_context(|| format!("Failed to open archive {}", archive_path.display()))?;
+    let decoder = Decoder::new(file).context("Failed to start decompression")?;
+    let mut archive = tar::Archive::new(decoder);
+
+    for entry in archive.entries().context("Failed to read archive")? {
+        let mut entry = entry.context("Failed to read archive entry")?;
+        let entry_type = entry.header().entry_type();
+        if !entry_type.is_file() && !entry_type.is_dir() {
+            continue;
+        }
+
+        let path = entry.path()?.into_owned();
+        if !is_relative_path(&path) {
+            anyhow::bail!("Archive entry has an unsafe path: {}", path.display());
+        }
+
+        entry
+            .unpack_in(staging.path())
+            .with_context(|| format!("Failed to extract {}", path.display()))?;
+    }
+
+    Ok(())
+}
+
+/// Whether a path stays inside the directory it is joined to
+fn is_relative_path(path: &Path) -> bool {
+    path.components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+}
+
+/// Whether a name can be used as a single path component
+fn is_plain_name(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+    matches!(components.next(), Some(Component::Normal(_))) && components.next().is_none()
+}
+
+/// Point every checkpoint in the tree at the importing project and session
+fn rewrite_node_ids(node: &mut TimelineNode, project_id: &str, session_id: &str) {
+    node.checkpoint.project_id = project_id.to_string();
+    node.checkpoint.session_id = session_id.to_string();
+    for child in &mut node.children {
+        rewrite_node_ids(child, project_id, session_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checkpoint::manager::CheckpointManager;
+    use std::io::Read;
+
+    /// A session with two checkpoints, exported to `out.tar.zst`
+    async fn exported_session(temp_dir: &TempDir) -> (PathBuf, Vec<String>) {
+        let claude_dir = temp_dir.path().join("claude");
+        let project_path = temp_dir.path().join("project");
+        fs::create_dir_all(&project_path).unwrap();
+        let manager = CheckpointManager::new(
+            "proj".to_string(),
+            "sess".to_string(),
+            project_path.clone(),
+            claude_dir.clone(),
+        )
+        .await
+        .unwrap();
+
+        let mut ids = Vec::new();
+        fs::write(project_path.join("a.txt"), b"one").unwrap();
+        ids.push(
+            manager
+                .create_checkpoint(None, None)
+                .await
+                .unwrap()
+                .checkpoint
+                .id,
+        );
+        fs::write(project_path.join("b.bin"), [0u8, 159, 146, 150]).unwrap();
+        ids.push(
+            manager
+                .create_checkpoint(None, None)
+                .await
+                .unwrap()
+                .checkpoint
+                .id,
+        );
+
+        let archive_path = temp_dir.path().join("out.tar.zst");
+        let summary = CheckpointStorage::new(claude_dir)
+            .export_session("proj", "sess", &archive_path)
+            .unwrap();
+        assert_eq!(summary.checkpoint_count, 2);
+        assert_eq!(summary.blob_count, 2);
+        (archive_path, ids)
+    }
+
+    /// Copy an archive, replacing the data of entries `rewrite` returns new data for
+    fn rewrite_archive(source: &Path, target: &Path, rewrite: impl Fn(&str) -> Option<Vec<u8>>) {
+        let mut archive = tar::Archive::new(Decoder::new(File::open(source).unwrap()).unwrap());
+        let mut builder =
+            tar::Builder::new(Encoder::new(File::create(target).unwrap(), 3).unwrap());
+        for entry in archive.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let path = entry.path().unwrap().to_string_lossy().to_string();
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data).unwrap();
+            let data = rewrite(&path).unwrap_or(data);
+            let mut header = entry.header().clone();
+            header.set_size(data.len() as u64);
+            header.set_cksum();
+            builder.append_data(&mut header, &path, &data[..]).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_export_import_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let (archive_path, ids) = exported_session(&temp_dir).await;
+
+        let other_dir = temp_dir.path().join("other");
+        let storage = CheckpointStorage::new(other_dir.clone());
+        let summary = storage
+            .import_session(&archive_path, "proj2", Some("copy"))
+            .unwrap();
+        assert_eq!(summary.session_id, "copy");
+        assert_eq!(summary.checkpoint_count, 2);
+
+        let paths = CheckpointPaths::new(&other_dir, "proj2", "copy");
+        let timeline = storage.load_timeline(&paths.timeline_file).unwrap();
+        assert_eq!(timeline.checkpoint_ids(), ids);
+        let tree = storage
+            .load_checkpoint_tree("proj2", "copy", &timeline, &ids[1])
+            .unwrap();
+        assert_eq!(tree[Path::new("a.txt")].content, b"one");
+        assert_eq!(tree[Path::new("b.bin")].content, [0u8, 159, 146, 150]);
+        let (checkpoint, _, _) = storage.load_checkpoint("proj2", "copy", &ids[0]).unwrap();
+        assert_eq!(
+            (
+                checkpoint.project_id.as_str(),
+                checkpoint.session_id.as_str()
+            ),
+            ("proj2", "copy")
+        );
+        assert!(storage
+            .verify_session("proj2", "copy", false)
+            .unwrap()
+            .issues
+            .is_empty());
+
+        // Importing over a session with checkpoints fails
+        assert!(storage
+            .import_session(&archive_path, "proj2", Some("copy"))
+            .is_err());
+
+        // A staging directory left by an import is not a session
+        let staging = CheckpointPaths::timelines_dir(&other_dir, "proj2").join(".import-left");
+        fs::create_dir_all(&staging).unwrap();
+        fs::write(staging.join("timeline.json"), b"{}").unwrap();
+        assert_eq!(storage.list_session_ids("proj2").unwrap(), vec!["copy"]);
+    }
+
+    #[tokio::test]
+    async fn test_import_rejects_unsafe_paths() {
+        let temp_dir = TempDir::new().unwrap();
+        let archive_path = temp_dir.path().join("evil.tar.zst");
+        let mut builder =
+            tar::Builder::new(Encoder::new(File::create(&archive_path).unwrap(), 3).unwrap());
+        append_bytes(&mut builder, "manifest.json", b"{}").unwrap();
+        // The tar crate refuses to write `..`, so set the raw name
+        let mut header = tar::Header::new_old();
+        header.as_old_mut().name[..13].copy_from_slice(b"../escaped.sh");
+        header.set_size(2);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append(&header, &b"hi"[..]).unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let claude_dir = temp_dir.path().join("claude");
+        let storage = CheckpointStorage::new(claude_dir.clone());
+        let err = storage
+            .import_session(&archive_path, "proj", None)
+            .unwrap_err();
+        assert!(format!("{:#}", err).contains("unsafe path"), "{:#}", err);
+
+        let timelines_dir = CheckpointPaths::timelines_dir(&claude_dir, "proj");
+        assert!(!timelines_dir.parent().unwrap().join("escaped.sh").exists());
+        assert_eq!(fs::read_dir(&timelines_dir).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_import_rejects_content_not_matching_its_hash() {
+        let temp_dir = TempDir::new().unwrap();
+        let (archive_path, _) = exported_session(&temp_dir).await;
+        let tampered_path = temp_dir.path().join("tampered.tar.zst");
+        rewrite_archive(&archive_path, &tampered_path, |path| {
+            path.starts_with("objects/")
+                .then(|| zstd::stream::encode_all(&b"tampered"[..], 3).unwrap())
+        });
+
+        let claude_dir = temp_dir.path().join("other");
+        let storage = CheckpointStorage::new(claude_dir.clone());
+        let err = storage
+            .import_session(&tampered_path, "proj", None)
+            .unwrap_err();
+        assert!(
+            format!("{:#}", err).contains("does not match its hash"),
+            "{:#}",
+            err
+        );
+
+        let paths = CheckpointPaths::new(&claude_dir, "proj", "sess");
+        assert!(!paths.timeline_file.exists());
+        assert!(storage.list_session_ids("proj").unwrap().is_empty());
+        assert!(!paths.content_pool_dir.join("objects").exists());
+    }
+}
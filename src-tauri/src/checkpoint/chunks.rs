@@ -0,0 +1,281 @@
+use anyhow::{Context, Result};
+use std::io::{Read, Seek, SeekFrom};
+
+/// Smallest chunk cut, except for the end of the content
+pub const MIN_CHUNK_SIZE: usize = 16 * 1024;
+/// Size the cut points are normalized around
+pub const AVG_CHUNK_SIZE: usize = 64 * 1024;
+/// Largest chunk; a cut is forced when no boundary is found before it
+pub const MAX_CHUNK_SIZE: usize = 256 * 1024;
+
+/// First line of a pool object that lists chunks instead of holding content
+///
+/// zstd frames start with a fixed magic number, so a chunk list can never be
+/// mistaken for compressed content.
+const CHUNK_LIST_HEADER: &[u8] = b"chunks v1\n";
+
+/// Stricter mask used before the average size, making early cuts unlikely
+const MASK_S: u64 = top_bits(18);
+/// Looser mask used after the average size, making late cuts likely
+const MASK_L: u64 = top_bits(14);
+
+/// Random values the rolling gear hash adds for each byte
+const GEAR: [u64; 256] = gear_table();
+
+/// Content-defined chunks of a stream, in order
+///
+/// Boundaries are found with FastCDC's normalized gear hash, so an edit only
+/// changes the chunks around it and the rest keep their hashes. At most
+/// `MAX_CHUNK_SIZE` bytes of the stream are buffered.
+pub struct ReaderChunks<R> {
+    reader: R,
+    buffer: Vec<u8>,
+    start: usize,
+    at_end: bool,
+}
+
+impl<R: Read> ReaderChunks<R> {
+    /// Read the next chunk, or `None` once the stream is exhausted
+    pub fn next_chunk(&mut self) -> std::io::Result<Option<&[u8]>> {
+        // A cut point only depends on the next MAX_CHUNK_SIZE bytes
+        if !self.at_end && self.buffer.len() - self.start < MAX_CHUNK_SIZE {
+            self.buffer.drain(..self.start);
+            self.start = 0;
+            let wanted = MAX_CHUNK_SIZE - self.buffer.len();
+            let read = (&mut self.reader)
+                .take(wanted as u64)
+                .read_to_end(&mut self.buffer)?;
+            self.at_end = read < wanted;
+        }
+
+        let data = &self.buffer[self.start..];
+        if data.is_empty() {
+            return Ok(None);
+        }
+        let len = cut_point(data);
+        self.start += len;
+        Ok(Some(&self.buffer[self.start - len..self.start]))
+    }
+}
+
+/// Split a stream into content-defined chunks
+pub fn reader_chunks<R: Read>(reader: R) -> ReaderChunks<R> {
+    ReaderChunks {
+        reader,
+        buffer: Vec::with_capacity(MAX_CHUNK_SIZE),
+        start: 0,
+        at_end: false,
+    }
+}
+
+/// Length of the first chunk of `data`
+fn cut_point(data: &[u8]) -> usize {
+    if data.len() <= MIN_CHUNK_SIZE {
+        return data.len();
+    }
+    let end = data.len().min(MAX_CHUNK_SIZE);
+    let normal = end.min(AVG_CHUNK_SIZE);
+
+    let mut hash = 0u64;
+    for (i, &byte) in data.iter().enumerate().take(end).skip(MIN_CHUNK_SIZE) {
+        hash = (hash << 1).wrapping_add(GEAR[byte as usize]);
+        let mask = if i < normal { MASK_S } else { MASK_L };
+        if hash & mask == 0 {
+            return i + 1;
+        }
+    }
+    end
+}
+
+/// One chunk of a chunked pool object
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkEntry {
+    /// SHA-256 hash of the uncompressed chunk
+    pub hash: String,
+    /// Uncompressed size in bytes
+    pub size: u64,
+}
+
+/// Read the chunk list of a pool object, or `None` if it holds compressed
+/// content
+///
+/// Only the header of other objects is read, and the object is left at its
+/// start for them.
+pub fn read_chunk_list<R: Read + Seek>(object: &mut R) -> Result<Option<Vec<ChunkEntry>>> {
+    let mut header = Vec::with_capacity(CHUNK_LIST_HEADER.len());
+    object
+        .take(CHUNK_LIST_HEADER.len() as u64)
+        .read_to_end(&mut header)
+        .context("Failed to read pool object")?;
+    if header != CHUNK_LIST_HEADER {
+        object
+            .seek(SeekFrom::Start(0))
+            .context("Failed to read pool object")?;
+        return Ok(None);
+    }
+
+    let mut list = header;
+    object
+        .read_to_end(&mut list)
+        .context("Failed to read chunk list")?;
+    decode_chunk_list(&list).map(Some)
+}
+
+/// Serialize a chunk list as a pool object
+pub fn encode_chunk_list(entries: &[ChunkEntry]) -> Vec<u8> {
+    let mut out = CHUNK_LIST_HEADER.to_vec();
+    for entry in entries {
+        out.extend_from_slice(format!("{} {}\n", entry.hash, entry.size).as_bytes());
+    }
+    out
+}
+
+/// Parse a pool object written by `encode_chunk_list`
+pub fn decode_chunk_list(object: &[u8]) -> Result<Vec<ChunkEntry>> {
+    let body = object
+        .strip_prefix(CHUNK_LIST_HEADER)
+        .context("Not a chunk list")?;
+    let body = std::str::from_utf8(body).context("Chunk list is not valid UTF-8")?;
+
+    body.lines()
+        .map(|line| {
+            let (hash, size) = line
+                .split_once(' ')
+                .with_context(|| format!("Invalid chunk list entry: {}", line))?;
+            if hash.is_empty() || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                anyhow::bail!("Invalid chunk hash: {}", hash);
+            }
+            Ok(ChunkEntry {
+                hash: hash.to_string(),
+                size: size
+                    .parse()
+                    .with_context(|| format!("Invalid chunk size: {}", size))?,
+            })
+        })
+        .collect()
+}
+
+/// Mask with the `bits` highest bits set
+///
+/// The gear hash shifts left, so its high bits depend on the most bytes.
+const fn top_bits(bits: u32) -> u64 {
+    !0u64 << (64 - bits)
+}
+
+/// Fixed pseudo-random gear table, generated with splitmix64
+const fn gear_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut state = 0x9e37_79b9_7f4a_7c15u64;
+    let mut i = 0;
+    while i < 256 {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// All chunks of a stream
+    fn chunks(reader: impl Read) -> Vec<Vec<u8>> {
+        let mut reader = reader_chunks(reader);
+        let mut parts = Vec::new();
+        while let Some(chunk) = reader.next_chunk().unwrap() {
+            parts.push(chunk.to_vec());
+        }
+        parts
+    }
+
+    /// Reader returning at most `step` bytes per read
+    struct Trickle<'a> {
+        data: &'a [u8],
+        step: usize,
+    }
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let len = buf.len().min(self.step).min(self.data.len());
+            buf[..len].copy_from_slice(&self.data[..len]);
+            self.data = &self.data[len..];
+            Ok(len)
+        }
+    }
+
+    /// Deterministic incompressible test data
+    fn noise(len: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_chunk_boundaries() {
+        let data = noise(4 * 1024 * 1024, 1);
+        let parts = chunks(&data[..]);
+
+        assert_eq!(parts.concat(), data);
+        assert!(parts.len() > 16);
+        for part in &parts[..parts.len() - 1] {
+            assert!(part.len() >= MIN_CHUNK_SIZE && part.len() <= MAX_CHUNK_SIZE);
+        }
+        assert!(chunks(&[][..]).is_empty());
+        assert_eq!(chunks(&b"small"[..]), vec![b"small".to_vec()]);
+
+        // An insertion near the start leaves later chunks unchanged
+        let mut edited = data.clone();
+        edited.splice(1000..1000, b"inserted".iter().copied());
+        let edited_parts = chunks(&edited[..]);
+        let shared = edited_parts.iter().filter(|p| parts.contains(p)).count();
+        assert!(shared >= parts.len() - 2);
+    }
+
+    #[test]
+    fn test_chunks_do_not_depend_on_read_sizes() {
+        let data = noise(3 * 1024 * 1024 + 123, 2);
+        let expected = chunks(&data[..]);
+
+        for step in [1000, 4096, MAX_CHUNK_SIZE + 1] {
+            assert_eq!(chunks(Trickle { data: &data, step }), expected);
+        }
+    }
+
+    #[test]
+    fn test_chunk_list_round_trip() {
+        let entries = vec![
+            ChunkEntry {
+                hash: "ab12".to_string(),
+                size: 10,
+            },
+            ChunkEntry {
+                hash: "cd34".to_string(),
+                size: 20,
+            },
+        ];
+        let object = encode_chunk_list(&entries);
+
+        assert_eq!(
+            read_chunk_list(&mut Cursor::new(&object)).unwrap(),
+            Some(entries.clone())
+        );
+
+        // Compressed content is left where it started
+        let mut compressed = Cursor::new(vec![0x28, 0xb5, 0x2f, 0xfd, 0x00]);
+        assert_eq!(read_chunk_list(&mut compressed).unwrap(), None);
+        assert_eq!(compressed.position(), 0);
+        assert_eq!(decode_chunk_list(&object).unwrap(), entries);
+        assert!(decode_chunk_list(b"chunks v1\n../x 1\n").is_err());
+    }
+}
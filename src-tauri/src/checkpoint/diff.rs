@@ -0,0 +1,326 @@
+use anyhow::Result;
+use similar::{capture_diff_slices, group_diff_ops, Algorithm, DiffOp, DiffTag};
+use std::borrow::Cow;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use super::{storage::CheckpointStorage, CheckpointPaths, ContentSource, FileDiff, FileSnapshot};
+
+/// Largest file whose content is loaded to diff or merge it
+pub const MAX_DIFF_FILE_SIZE: u64 = 8 * 1024 * 1024;
+
+/// Options for line-level diffs between checkpoint files
+#[derive(Debug, Clone)]
+pub struct DiffOptions {
+    /// Number of unchanged lines shown around each hunk
+    pub context_lines: usize,
+    /// Treat lines that differ only in whitespace as equal
+    pub ignore_whitespace: bool,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            context_lines: 3,
+            ignore_whitespace: false,
+        }
+    }
+}
+
+/// Content of a snapshot prepared for a line-based diff or merge
+#[derive(Debug, PartialEq, Eq)]
+pub enum SnapshotText<'a> {
+    Text(Cow<'a, str>),
+    /// The content is not valid UTF-8
+    Binary,
+    /// The file is over `MAX_DIFF_FILE_SIZE`, so its content was not read
+    TooLarge,
+}
+
+impl<'a> SnapshotText<'a> {
+    /// Read the text of a snapshot, including content left in the pool or
+    /// on disk
+    pub fn load(
+        storage: &CheckpointStorage,
+        paths: &CheckpointPaths,
+        snapshot: &'a FileSnapshot,
+    ) -> Result<Self> {
+        if snapshot.size > MAX_DIFF_FILE_SIZE {
+            return Ok(Self::TooLarge);
+        }
+        let text = match &snapshot.source {
+            ContentSource::Inline => std::str::from_utf8(&snapshot.content)
+                .ok()
+                .map(Cow::Borrowed),
+            ContentSource::Pool => {
+                String::from_utf8(storage.read_content_object(paths, &snapshot.hash)?)
+                    .ok()
+                    .map(Cow::Owned)
+            }
+            ContentSource::File(path) => String::from_utf8(fs::read(path)?).ok().map(Cow::Owned),
+        };
+        Ok(text.map_or(Self::Binary, Self::Text))
+    }
+}
+
+/// Diff two snapshots of the same file
+///
+/// Text files produce a unified diff. If either side is not valid UTF-8, or
+/// is too large to load, the result only records that the content differs.
+pub fn diff_snapshots(
+    storage: &CheckpointStorage,
+    paths: &CheckpointPaths,
+    from: &FileSnapshot,
+    to: &FileSnapshot,
+    options: &DiffOptions,
+) -> Result<FileDiff> {
+    let from_text = SnapshotText::load(storage, paths, from)?;
+    let to_text = SnapshotText::load(storage, paths, to)?;
+    let path = to.file_path.display();
+    let note = match (&from_text, &to_text) {
+        (SnapshotText::Text(old), SnapshotText::Text(new)) => {
+            return Ok(diff_text(&to.file_path, old, new, options));
+        }
+        (SnapshotText::Binary, _) | (_, SnapshotText::Binary) => {
+            format!("Binary files a/{0} and b/{0} differ\n", path)
+        }
+        _ => format!(
+            "Files a/{0} and b/{0} differ but are too large to diff\n",
+            path
+        ),
+    };
+
+    Ok(FileDiff {
+        path: to.file_path.clone(),
+        additions: 0,
+        deletions: 0,
+        diff_content: Some(note),
+    })
+}
+
+/// Compute a unified diff between two versions of a text file
+pub fn diff_text(path: &Path, old: &str, new: &str, options: &DiffOptions) -> FileDiff {
+    let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+
+    let ops = if options.ignore_whitespace {
+        let old_keys: Vec<String> = old_lines.iter().map(|l| strip_whitespace(l)).collect();
+        let new_keys: Vec<String> = new_lines.iter().map(|l| strip_whitespace(l)).collect();
+        capture_diff_slices(Algorithm::Myers, &old_keys, &new_keys)
+    } else {
+        capture_diff_slices(Algorithm::Myers, &old_lines, &new_lines)
+    };
+
+    let mut additions = 0;
+    let mut deletions = 0;
+    for op in &ops {
+        let (tag, old_range, new_range) = op.as_tag_tuple();
+        match tag {
+            DiffTag::Equal => {}
+            DiffTag::Delete => deletions += old_range.len(),
+            DiffTag::Insert => additions += new_range.len(),
+            DiffTag::Replace => {
+                deletions += old_range.len();
+                additions += new_range.len();
+            }
+        }
+    }
+
+    let groups = group_diff_ops(ops, options.context_lines);
+    let diff_content = if groups.is_empty() {
+        None
+    } else {
+        let mut out = String::new();
+        let _ = writeln!(out, "--- a/{}", path.display());
+        let _ = writeln!(out, "+++ b/{}", path.display());
+        for group in &groups {
+            write_hunk(&mut out, group, &old_lines, &new_lines);
+        }
+        Some(out)
+    };
+
+    FileDiff {
+        path: path.to_path_buf(),
+        additions,
+        deletions,
+        diff_content,
+    }
+}
+
+/// Write a single `@@` hunk for a group of diff operations
+fn write_hunk(out: &mut String, group: &[DiffOp], old_lines: &[&str], new_lines: &[&str]) {
+    let (Some(first), Some(last)) = (group.first(), group.last()) else {
+        return;
+    };
+    let old_start = first.old_range().start;
+    let new_start = first.new_range().start;
+    let old_len = last.old_range().end - old_start;
+    let new_len = last.new_range().end - new_start;
+
+    let _ = writeln!(
+        out,
+        "@@ -{} +{} @@",
+        hunk_range(old_start, old_len),
+        hunk_range(new_start, new_len)
+    );
+
+    for op in group {
+        let (tag, old_range, new_range) = op.as_tag_tuple();
+        match tag {
+            DiffTag::Equal => {
+                // Equal lines may differ in whitespace; show the new version
+                for line in &new_lines[new_range] {
+                    push_line(out, ' ', line);
+                }
+            }
+            DiffTag::Delete => {
+                for line in &old_lines[old_range] {
+                    push_line(out, '-', line);
+                }
+            }
+            DiffTag::Insert => {
+                for line in &new_lines[new_range] {
+                    push_line(out, '+', line);
+                }
+            }
+            DiffTag::Replace => {
+                for line in &old_lines[old_range] {
+                    push_line(out, '-', line);
+                }
+                for line in &new_lines[new_range] {
+                    push_line(out, '+', line);
+                }
+            }
+        }
+    }
+}
+
+/// Format a hunk range as `start,len` using 1-based line numbers
+fn hunk_range(start: usize, len: usize) -> String {
+    match len {
+        // An empty range refers to the line before the insertion point
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, len),
+    }
+}
+
+fn push_line(out: &mut String, prefix: char, line: &str) {
+    out.push(prefix);
+    out.push_str(line);
+    if !line.ends_with('\n') {
+        out.push_str("\n\\ No newline at end of file\n");
+    }
+}
+
+fn strip_whitespace(line: &str) -> String {
+    line.chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checkpoint::manager::CheckpointManager;
+    use crate::checkpoint::storage::CHUNKED_OBJECT_THRESHOLD;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_unified_diff_counts_changed_lines() {
+        let old = "a\nb\nc\nd\n";
+        let new = "a\nB\nc\nd\ne\n";
+        let diff = diff_text(Path::new("src/lib.rs"), old, new, &DiffOptions::default());
+
+        assert_eq!(diff.additions, 2);
+        assert_eq!(diff.deletions, 1);
+        assert_eq!(
+            diff.diff_content.unwrap(),
+            "--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -1,4 +1,5 @@\n a\n-b\n+B\n c\n d\n+e\n"
+        );
+    }
+
+    #[test]
+    fn test_ignore_whitespace_and_context() {
+        let old = "fn main() {\n    run();\n}\n";
+        let new = "fn main() {\n\trun();\n}\n";
+        let options = DiffOptions {
+            context_lines: 0,
+            ignore_whitespace: true,
+        };
+        let diff = diff_text(Path::new("main.rs"), old, new, &options);
+        assert_eq!((diff.additions, diff.deletions), (0, 0));
+        assert!(diff.diff_content.is_none());
+
+        let options = DiffOptions {
+            context_lines: 0,
+            ignore_whitespace: false,
+        };
+        let diff = diff_text(Path::new("main.rs"), old, new, &options);
+        assert_eq!(
+            diff.diff_content.unwrap(),
+            "--- a/main.rs\n+++ b/main.rs\n@@ -2 +2 @@\n-    run();\n+\trun();\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_large_text_files_are_diffed_from_the_pool() {
+        let temp_dir = TempDir::new().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        let project_path = temp_dir.path().join("project");
+        fs::create_dir_all(&project_path).unwrap();
+        let manager = CheckpointManager::new(
+            "proj".to_string(),
+            "sess".to_string(),
+            project_path.clone(),
+            claude_dir.clone(),
+        )
+        .await
+        .unwrap();
+
+        let lines: Vec<String> = (0..CHUNKED_OBJECT_THRESHOLD / 16 + 100)
+            .map(|i| format!("line {:010}\n", i))
+            .collect();
+        fs::write(project_path.join("big.txt"), lines.concat()).unwrap();
+        let first = manager.create_checkpoint(None, None).await.unwrap();
+        let mut edited = lines.clone();
+        edited[1] = "changed\n".to_string();
+        fs::write(project_path.join("big.txt"), edited.concat()).unwrap();
+        let second = manager.create_checkpoint(None, None).await.unwrap();
+
+        let storage = CheckpointStorage::new(claude_dir.clone());
+        let paths = CheckpointPaths::new(&claude_dir, "proj", "sess");
+        let timeline = storage.load_timeline(&paths.timeline_file).unwrap();
+        let load = |id: &str| {
+            storage
+                .load_checkpoint_tree("proj", "sess", &timeline, id)
+                .unwrap()
+                .remove(Path::new("big.txt"))
+                .unwrap()
+        };
+        let from = load(&first.checkpoint.id);
+        let to = load(&second.checkpoint.id);
+        assert_eq!(to.source, ContentSource::Pool);
+
+        let options = DiffOptions {
+            context_lines: 0,
+            ..Default::default()
+        };
+        let diff = diff_snapshots(&storage, &paths, &from, &to, &options).unwrap();
+        assert_eq!((diff.additions, diff.deletions), (1, 1));
+        assert_eq!(
+            diff.diff_content.unwrap(),
+            "--- a/big.txt\n+++ b/big.txt\n@@ -2 +2 @@\n-line 0000000001\n+changed\n"
+        );
+
+        // Files over the limit are reported without reading them
+        let huge = FileSnapshot {
+            size: MAX_DIFF_FILE_SIZE + 1,
+            ..to.clone()
+        };
+        let diff = diff_snapshots(&storage, &paths, &from, &huge, &options).unwrap();
+        assert_eq!(
+            diff.diff_content.unwrap(),
+            "Files a/big.txt and b/big.txt differ but are too large to diff\n"
+        );
+    }
+}
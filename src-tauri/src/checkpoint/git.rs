@@ -0,0 +1,507 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+use super::Checkpoint;
+
+/// Namespace of the refs checkpoints are exported to
+const REF_NAMESPACE: &str = "refs/claude";
+
+/// Identity used for commits when git has none configured
+const FALLBACK_NAME: &str = "Claudia";
+const FALLBACK_EMAIL: &str = "claudia@localhost";
+
+/// Longest commit subject taken from a prompt
+const MAX_SUBJECT_CHARS: usize = 72;
+
+/// A checkpoint written to git as a commit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GitExport {
+    /// Exported checkpoint
+    pub checkpoint_id: String,
+    /// Commit holding the checkpoint's files
+    pub commit: String,
+    /// Ref pointing at the commit
+    pub ref_name: String,
+}
+
+/// A file in a git tree
+#[derive(Debug, Clone)]
+pub struct GitFile {
+    /// Path relative to the project root
+    pub path: PathBuf,
+    /// Object ID of the blob
+    pub blob: String,
+    /// Whether the file has the executable bit
+    pub executable: bool,
+    /// Whether the entry is a symlink, whose blob holds the target
+    pub symlink: bool,
+}
+
+/// Entries of a commit
+#[derive(Debug, Default)]
+pub struct CommitFiles {
+    /// Regular files and symlinks with their size in bytes
+    pub files: Vec<(GitFile, u64)>,
+    /// Submodules, which cannot be read
+    pub skipped: Vec<PathBuf>,
+}
+
+/// The git repository containing a project
+///
+/// Only plumbing commands are used, with a private index, so the user's
+/// index, working tree and branches are never touched.
+pub struct GitRepo {
+    project_path: PathBuf,
+    /// Location of the project inside the repository, with a trailing `/`
+    prefix: String,
+}
+
+impl GitRepo {
+    /// Open the repository that contains `project_path`
+    pub fn open(project_path: &Path) -> Result<Self> {
+        let mut repo = Self {
+            project_path: project_path.to_path_buf(),
+            prefix: String::new(),
+        };
+        let prefix = repo
+            .run(&["rev-parse", "--show-prefix"], None)
+            .with_context(|| {
+                format!("{} is not inside a git repository", project_path.display())
+            })?;
+        repo.prefix = String::from_utf8_lossy(&prefix).trim_end().to_string();
+        Ok(repo)
+    }
+
+    /// Ref a checkpoint is exported to
+    pub fn checkpoint_ref(session_id: &str, checkpoint_id: &str) -> String {
+        format!("{}/{}/{}", REF_NAMESPACE, session_id, checkpoint_id)
+    }
+
+    /// Resolve a revision to a commit ID, or `None` if it does not exist
+    pub fn resolve_commit(&self, rev: &str) -> Result<Option<String>> {
+        let spec = format!("{}^{{commit}}", rev);
+        match self.run(&["rev-parse", "--verify", "--quiet", &spec], None) {
+            Ok(out) => Ok(Some(String::from_utf8_lossy(&out).trim().to_string())),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Store files as blobs with a single `hash-object`, returning their
+    /// object IDs in order
+    ///
+    /// Content is stored as is, without applying any attributes or line
+    /// ending conversion.
+    pub fn write_blobs(&self, files: &[PathBuf]) -> Result<Vec<String>> {
+        if files.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut input = Vec::new();
+        for file in files {
+            let path = file
+                .to_str()
+                .with_context(|| format!("Path is not valid UTF-8: {}", file.display()))?;
+            input.extend_from_slice(path.as_bytes());
+            input.push(b'\n');
+        }
+
+        let out = self.run(
+            &["hash-object", "-w", "--no-filters", "--stdin-paths"],
+            Some(&input),
+        )?;
+        let blobs: Vec<String> = String::from_utf8_lossy(&out)
+            .lines()
+            .map(str::to_string)
+            .collect();
+        if blobs.len() != files.len() {
+            anyhow::bail!(
+                "git hash-object returned {} objects for {} files",
+                blobs.len(),
+                files.len()
+            );
+        }
+        Ok(blobs)
+    }
+
+    /// Write a tree holding `files` under the project's location in the repository
+    pub fn write_tree(&self, files: &[GitFile]) -> Result<String> {
+        let index_dir = tempfile::tempdir().context("Failed to create temporary index")?;
+        let index_file = index_dir.path().join("index");
+        let env = [("GIT_INDEX_FILE", index_file.as_os_str())];
+
+        self.run_with_env(&["read-tree", "--empty"], None, &env)?;
+
+        let mut index_info = Vec::new();
+        for file in files {
+            let mode = if file.symlink {
+                "120000"
+            } else if file.executable {
+                "100755"
+            } else {
+                "100644"
+            };
+            index_info.extend_from_slice(
+                format!(
+                    "{} {}\t{}{}\0",
+                    mode,
+                    file.blob,
+                    self.prefix,
+                    git_path(&file.path)?
+                )
+                .as_bytes(),
+            );
+        }
+        self.run_with_env(
+            &["update-index", "-z", "--index-info"],
+            Some(&index_info),
+            &env,
+        )?;
+
+        let out = self.run_with_env(&["write-tree"], None, &env)?;
+        Ok(String::from_utf8_lossy(&out).trim().to_string())
+    }
+
+    /// Create a commit dated at `timestamp`
+    pub fn commit_tree(
+        &self,
+        tree: &str,
+        parents: &[String],
+        message: &str,
+        timestamp: DateTime<Utc>,
+    ) -> Result<String> {
+        let mut args = vec!["commit-tree".to_string(), tree.to_string()];
+        for parent in parents {
+            args.push("-p".to_string());
+            args.push(parent.clone());
+        }
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+        let date = format!("@{} +0000", timestamp.timestamp());
+        let mut env: Vec<(&str, &std::ffi::OsStr)> = vec![
+            ("GIT_AUTHOR_DATE", date.as_ref()),
+            ("GIT_COMMITTER_DATE", date.as_ref()),
+        ];
+        if self.run(&["var", "GIT_COMMITTER_IDENT"], None).is_err() {
+            env.extend([
+                ("GIT_AUTHOR_NAME", FALLBACK_NAME.as_ref()),
+                ("GIT_AUTHOR_EMAIL", FALLBACK_EMAIL.as_ref()),
+                ("GIT_COMMITTER_NAME", FALLBACK_NAME.as_ref()),
+                ("GIT_COMMITTER_EMAIL", FALLBACK_EMAIL.as_ref()),
+            ]);
+        }
+
+        let out = self.run_with_env(&args, Some(message.as_bytes()), &env)?;
+        Ok(String::from_utf8_lossy(&out).trim().to_string())
+    }
+
+    /// Point a ref at a commit
+    pub fn update_ref(&self, ref_name: &str, commit: &str) -> Result<()> {
+        self.run(&["update-ref", ref_name, commit], None)?;
+        Ok(())
+    }
+
+    /// Full message of a commit
+    pub fn commit_message(&self, commit: &str) -> Result<String> {
+        let out = self.run(&["log", "-1", "--format=%B", commit], None)?;
+        Ok(String::from_utf8_lossy(&out).trim_end().to_string())
+    }
+
+    /// Files of a commit that lie inside the project, with their sizes
+    ///
+    /// Nothing is read but the tree, so callers can leave out files before
+    /// reading them with a `BlobReader`. Submodules are returned by path.
+    pub fn list_commit_files(&self, commit: &str) -> Result<CommitFiles> {
+        let listing = self.run(&["ls-tree", "-r", "-l", "-z", commit], None)?;
+
+        let mut files = Vec::new();
+        let mut skipped = Vec::new();
+        for entry in listing.split(|b| *b == 0).filter(|e| !e.is_empty()) {
+            let entry = String::from_utf8_lossy(entry);
+            let (info, path) = entry
+                .split_once('\t')
+                .with_context(|| format!("Unexpected ls-tree output: {}", entry))?;
+            // The size is padded with spaces, and `-` for submodules
+            let mut fields = info.split_whitespace();
+            let (Some(mode), Some(kind), Some(blob), Some(size)) =
+                (fields.next(), fields.next(), fields.next(), fields.next())
+            else {
+                anyhow::bail!("Unexpected ls-tree output: {}", entry);
+            };
+
+            let path = PathBuf::from(path);
+            if kind != "blob" {
+                skipped.push(path);
+                continue;
+            }
+            let size = size
+                .parse()
+                .with_context(|| format!("Unexpected ls-tree output: {}", entry))?;
+            files.push((
+                GitFile {
+                    path,
+                    blob: blob.to_string(),
+                    executable: mode == "100755",
+                    symlink: mode == "120000",
+                },
+                size,
+            ));
+        }
+
+        Ok(CommitFiles { files, skipped })
+    }
+
+    /// Start a `cat-file --batch` to read blobs through, one at a time
+    pub fn blob_reader(&self) -> Result<BlobReader> {
+        let mut child = Command::new("git")
+            .arg("-C")
+            .arg(&self.project_path)
+            .args(["cat-file", "--batch"])
+            .env("GIT_TERMINAL_PROMPT", "0")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .context("Failed to run git")?;
+        let stdin = child
+            .stdin
+            .take()
+            .context("Failed to open git cat-file input")?;
+        let stdout = child
+            .stdout
+            .take()
+            .context("Failed to open git cat-file output")?;
+
+        Ok(BlobReader {
+            child,
+            stdin,
+            stdout: BufReader::new(stdout),
+        })
+    }
+
+    fn run(&self, args: &[&str], stdin: Option<&[u8]>) -> Result<Vec<u8>> {
+        self.run_with_env(args, stdin, &[])
+    }
+
+    fn run_with_env(
+        &self,
+        args: &[&str],
+        stdin: Option<&[u8]>,
+        env: &[(&str, &std::ffi::OsStr)],
+    ) -> Result<Vec<u8>> {
+        let mut child = Command::new("git")
+            .arg("-C")
+            .arg(&self.project_path)
+            .args(args)
+            .envs(env.iter().copied())
+            .env("GIT_TERMINAL_PROMPT", "0")
+            .stdin(if stdin.is_some() {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("Failed to run git")?;
+
+        // Feed stdin from a thread so large outputs cannot fill the pipe and block
+        let writer = match (stdin, child.stdin.take()) {
+            (Some(input), Some(mut pipe)) => {
+                let input = input.to_vec();
+                Some(std::thread::spawn(move || pipe.write_all(&input)))
+            }
+            _ => None,
+        };
+
+        let mut stdout = Vec::new();
+        if let Some(mut pipe) = child.stdout.take() {
+            pipe.read_to_end(&mut stdout)?;
+        }
+        let output = child.wait_with_output()?;
+        if let Some(writer) = writer {
+            let _ = writer.join();
+        }
+
+        if !output.status.success() {
+            anyhow::bail!(
+                "git {} failed: {}",
+                args.first().copied().unwrap_or_default(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(stdout)
+    }
+}
+
+/// Reads blobs from a running `git cat-file --batch`
+///
+/// Each blob is requested and read in full before the next, so only one is
+/// held in memory at a time.
+pub struct BlobReader {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl BlobReader {
+    /// Content of a blob
+    pub fn read(&mut self, blob: &str) -> Result<Vec<u8>> {
+        writeln!(self.stdin, "{}", blob)
+            .and_then(|_| self.stdin.flush())
+            .context("Failed to request blob from git cat-file")?;
+
+        let mut header = String::new();
+        self.stdout
+            .read_line(&mut header)
+            .context("Failed to read git cat-file output")?;
+        let header = header.trim_end();
+        if header.is_empty() {
+            anyhow::bail!("Truncated cat-file output");
+        }
+        if header.ends_with(" missing") {
+            anyhow::bail!("Blob {} not found", blob);
+        }
+        let size: usize = header
+            .rsplit(' ')
+            .next()
+            .and_then(|s| s.parse().ok())
+            .with_context(|| format!("Unexpected cat-file header: {}", header))?;
+
+        // The content is followed by a newline
+        let mut content = vec![0; size + 1];
+        self.stdout
+            .read_exact(&mut content)
+            .context("Truncated cat-file output")?;
+        content.truncate(size);
+        Ok(content)
+    }
+}
+
+impl Drop for BlobReader {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Commit message for a checkpoint
+///
+/// The subject is the first line of the prompt, falling back to the
+/// description. The full prompt follows, then trailers identifying the
+/// checkpoint, session and model.
+pub fn commit_message(checkpoint: &Checkpoint) -> String {
+    let prompt = checkpoint.metadata.user_prompt.trim();
+    let first_line = prompt
+        .lines()
+        .next()
+        .filter(|l| !l.trim().is_empty())
+        .or(checkpoint.description.as_deref())
+        .unwrap_or("Checkpoint")
+        .trim();
+
+    let mut subject: String = first_line.chars().take(MAX_SUBJECT_CHARS).collect();
+    if first_line.chars().count() > MAX_SUBJECT_CHARS {
+        subject.push_str("...");
+    }
+
+    let mut message = subject;
+    if !prompt.is_empty() && prompt != first_line {
+        message.push_str("\n\n");
+        message.push_str(prompt);
+    }
+    message.push_str(&format!(
+        "\n\nCheckpoint: {}\nSession: {}",
+        checkpoint.id, checkpoint.session_id
+    ));
+    if !checkpoint.metadata.model_used.is_empty() {
+        message.push_str(&format!("\nModel: {}", checkpoint.metadata.model_used));
+    }
+    message.push('\n');
+    message
+}
+
+/// A relative path with `/` separators, as git stores it
+fn git_path(path: &Path) -> Result<String> {
+    let mut parts = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => parts.push(
+                part.to_str()
+                    .with_context(|| format!("Path is not valid UTF-8: {}", path.display()))?,
+            ),
+            _ => anyhow::bail!("Unexpected path in checkpoint: {}", path.display()),
+        }
+    }
+    Ok(parts.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checkpoint::CheckpointMetadata;
+
+    fn checkpoint(prompt: &str, description: Option<&str>) -> Checkpoint {
+        Checkpoint {
+            id: "c1".to_string(),
+            session_id: "s1".to_string(),
+            project_id: "p1".to_string(),
+            message_index: 0,
+            timestamp: Utc::now(),
+            description: description.map(str::to_string),
+            parent_checkpoint_id: None,
+            merge_parent_checkpoint_id: None,
+            tags: Vec::new(),
+            full_tree: true,
+            metadata: CheckpointMetadata {
+                total_tokens: 0,
+                model_used: "opus".to_string(),
+                user_prompt: prompt.to_string(),
+                file_changes: 0,
+                snapshot_size: 0,
+                skipped_files: Vec::new(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_commit_message_from_prompt_and_model() {
+        assert_eq!(
+            commit_message(&checkpoint("Fix the parser\nIt drops commas", None)),
+            "Fix the parser\n\nFix the parser\nIt drops commas\n\nCheckpoint: c1\nSession: s1\nModel: opus\n"
+        );
+        assert_eq!(
+            commit_message(&checkpoint("", Some("Before refactor"))),
+            "Before refactor\n\nCheckpoint: c1\nSession: s1\nModel: opus\n"
+        );
+    }
+
+    #[test]
+    fn test_blob_reader_reads_blobs_one_at_a_time() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let repo = GitRepo {
+            project_path: temp_dir.path().to_path_buf(),
+            prefix: String::new(),
+        };
+        repo.run(&["init", "--quiet"], None).unwrap();
+        let write_blob = |content: &[u8]| {
+            let out = repo
+                .run(&["hash-object", "-w", "--stdin"], Some(content))
+                .unwrap();
+            String::from_utf8(out).unwrap().trim().to_string()
+        };
+        let text = write_blob(b"line one\nline two\n");
+        let binary = write_blob(&[0, 159, 146, 150, b'\n']);
+        let empty = write_blob(b"");
+
+        let mut blobs = repo.blob_reader().unwrap();
+        assert_eq!(blobs.read(&text).unwrap(), b"line one\nline two\n");
+        assert_eq!(blobs.read(&empty).unwrap(), b"");
+        assert_eq!(blobs.read(&binary).unwrap(), [0, 159, 146, 150, b'\n']);
+        assert!(blobs.read(&"0".repeat(40)).is_err());
+        assert_eq!(blobs.read(&text).unwrap(), b"line one\nline two\n");
+    }
+}
@@ -1,18 +1,37 @@
 use anyhow::{Context, Result};
 use chrono::{DateTime, TimeZone, Utc};
 use log;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs;
-use std::path::PathBuf;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
 
 use super::{
-    storage::{self, CheckpointStorage},
-    Checkpoint, CheckpointMetadata, CheckpointPaths, CheckpointResult, CheckpointStrategy,
-    FileSnapshot, FileState, FileTracker, SessionTimeline,
+    git::{self, GitExport, GitFile, GitRepo},
+    manifest::{
+        entry_hash, entry_metadata, read_entry_content, ManifestEntry, WorkingTreeManifest,
+    },
+    merge::{self, MergeResult},
+    retention::RetentionReport,
+    rules::CompiledSmartRules,
+    scheduler::{self, AutoCheckpointScheduler},
+    storage::{self, CheckpointStorage, FileRef, FsyncPolicy},
+    transcript,
+    verify::VerifyReport,
+    walker::{self, ProjectWalker},
+    watcher::LiveTracking,
+    AutoCheckpointSchedule, Checkpoint, CheckpointMetadata, CheckpointPaths, CheckpointResult,
+    CheckpointStrategy, CheckpointTag, ContentSource, EntryKind, FileSnapshot, FileState,
+    FileTracker, PermissionChange, RestorePlan, RetentionPolicy, SessionTimeline, SkipReason,
+    SkippedFile, SmartRules, TrackerState,
 };
 
+/// Longest time to wait for the project watcher before falling back to a full scan
+const WATCHER_FLUSH_TIMEOUT: Duration = Duration::from_secs(2);
+
 /// Manages checkpoint operations for a session
 pub struct CheckpointManager {
     project_id: String,
@@ -22,6 +41,10 @@ pub struct CheckpointManager {
     pub storage: Arc<CheckpointStorage>,
     timeline: Arc<RwLock<SessionTimeline>>,
     current_messages: Arc<RwLock<Vec<String>>>, // JSONL messages
+    bash_manifests: Arc<RwLock<HashMap<String, WorkingTreeManifest>>>, // keyed by tool_use id
+    last_manifest: Arc<RwLock<Option<WorkingTreeManifest>>>,
+    live: Arc<LiveTracking>,
+    scheduler: Arc<RwLock<AutoCheckpointScheduler>>,
 }
 
 impl CheckpointManager {
@@ -44,9 +67,38 @@ impl CheckpointManager {
         } else {
             SessionTimeline::new(session_id.clone())
         };
+        storage.set_fsync_policy(timeline.fsync_policy);
+
+        // Resume the tracker and messages saved by a previous run
+        let tracker_state = if paths.tracker_file.exists() {
+            storage
+                .load_tracker_state(&paths.tracker_file)
+                .map_err(|e| log::warn!("Ignoring saved file tracker: {}", e))
+                .ok()
+        } else {
+            None
+        };
+        let message_cursor = match &tracker_state {
+            Some(state) => state.message_cursor,
+            // Sessions saved before the cursor existed resume at their current checkpoint
+            None => timeline
+                .current_checkpoint_id
+                .as_deref()
+                .and_then(|id| timeline.find_checkpoint(id))
+                .map_or(0, |node| node.checkpoint.message_index + 1),
+        };
+        let current_messages = load_session_messages(
+            &storage,
+            &project_id,
+            &session_id,
+            &timeline,
+            message_cursor,
+        );
 
         let file_tracker = FileTracker {
-            tracked_files: HashMap::new(),
+            tracked_files: tracker_state
+                .map(|state| state.tracked_files)
+                .unwrap_or_default(),
         };
 
         Ok(Self {
@@ -56,11 +108,18 @@ impl CheckpointManager {
             file_tracker: Arc::new(RwLock::new(file_tracker)),
             storage,
             timeline: Arc::new(RwLock::new(timeline)),
-            current_messages: Arc::new(RwLock::new(Vec::new())),
+            current_messages: Arc::new(RwLock::new(current_messages)),
+            bash_manifests: Arc::new(RwLock::new(HashMap::new())),
+            last_manifest: Arc::new(RwLock::new(None)),
+            live: Arc::new(LiveTracking::default()),
+            scheduler: Arc::new(RwLock::new(AutoCheckpointScheduler::default())),
         })
     }
 
     /// Track a new message in the session
+    ///
+    /// Messages are usually replayed from the session file after the fact,
+    /// so Bash commands are not diffed here; see `track_live_message`.
     pub async fn track_message(&self, jsonl_message: String) -> Result<()> {
         let mut messages = self.current_messages.write().await;
         messages.push(jsonl_message.clone());
@@ -85,63 +144,103 @@ impl CheckpointManager {
         Ok(())
     }
 
-    /// Track file operations from tool usage
-    async fn track_tool_operation(&self, tool: &str, input: &serde_json::Value) -> Result<()> {
-        match tool.to_lowercase().as_str() {
-            "edit" | "write" | "multiedit" => {
-                if let Some(file_path) = input.get("file_path").and_then(|p| p.as_str()) {
-                    self.track_file_modification(file_path).await?;
+    /// Observe a message as it streams in, before it reaches the session file
+    ///
+    /// A Bash tool use captures the working tree before the command runs,
+    /// and its tool result tracks the files the command changed. The
+    /// message itself is not recorded; that happens when it is replayed.
+    pub async fn track_live_message(&self, jsonl_message: &str) -> Result<()> {
+        let Ok(msg) = serde_json::from_str::<serde_json::Value>(jsonl_message) else {
+            return Ok(());
+        };
+        let Some(content_array) = msg
+            .get("message")
+            .and_then(|m| m.get("content"))
+            .and_then(|c| c.as_array())
+        else {
+            return Ok(());
+        };
+
+        for item in content_array {
+            match item.get("type").and_then(|t| t.as_str()) {
+                Some("tool_use") => {
+                    let is_bash = item
+                        .get("name")
+                        .and_then(|n| n.as_str())
+                        .is_some_and(|name| name.eq_ignore_ascii_case("bash"));
+                    if let (true, Some(tool_use_id)) =
+                        (is_bash, item.get("id").and_then(|i| i.as_str()))
+                    {
+                        // Compared with the tree after the command once its result arrives
+                        let manifest = self.capture_manifest().await?;
+                        self.bash_manifests
+                            .write()
+                            .await
+                            .insert(tool_use_id.to_string(), manifest);
+                    }
                 }
-            }
-            "bash" => {
-                // Try to detect file modifications from bash commands
-                if let Some(command) = input.get("command").and_then(|c| c.as_str()) {
-                    self.track_bash_side_effects(command).await?;
+                Some("tool_result") => {
+                    if let Some(tool_use_id) = item.get("tool_use_id").and_then(|i| i.as_str()) {
+                        self.track_bash_side_effects(tool_use_id).await?;
+                    }
                 }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Track file operations from tool usage
+    async fn track_tool_operation(&self, tool: &str, input: &serde_json::Value) -> Result<()> {
+        if matches!(tool.to_lowercase().as_str(), "edit" | "write" | "multiedit") {
+            if let Some(file_path) = input.get("file_path").and_then(|p| p.as_str()) {
+                self.track_file_modification(file_path).await?;
             }
-            _ => {}
         }
         Ok(())
     }
 
     /// Track a file modification
     pub async fn track_file_modification(&self, file_path: &str) -> Result<()> {
+        let max_file_size = self.timeline.read().await.max_file_size;
         let mut tracker = self.file_tracker.write().await;
         let full_path = self.project_path.join(file_path);
 
-        // Read current file state
-        let (hash, exists, _size, modified) = if full_path.exists() {
-            let content = fs::read_to_string(&full_path).unwrap_or_default();
-            let metadata = fs::metadata(&full_path)?;
-            let modified = metadata
-                .modified()
-                .ok()
-                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-                .map(|d| {
-                    Utc.timestamp_opt(d.as_secs() as i64, d.subsec_nanos())
-                        .unwrap()
-                })
-                .unwrap_or_else(Utc::now);
-
-            (
-                storage::CheckpointStorage::calculate_file_hash(&content),
-                true,
-                metadata.len(),
-                modified,
-            )
-        } else {
-            (String::new(), false, 0, Utc::now())
-        };
+        // Read current file state, without following symlinks
+        let (hash, exists, kind, modified) =
+            if let Some((kind, metadata)) = entry_metadata(&full_path) {
+                // Files over the size limit are never read
+                let hash = if kind == EntryKind::File && metadata.len() > max_file_size {
+                    String::new()
+                } else {
+                    entry_hash(&full_path, kind).unwrap_or_default()
+                };
+                let modified = metadata
+                    .modified()
+                    .ok()
+                    .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                    .map(|d| {
+                        Utc.timestamp_opt(d.as_secs() as i64, d.subsec_nanos())
+                            .unwrap()
+                    })
+                    .unwrap_or_else(Utc::now);
+
+                (hash, true, kind, modified)
+            } else {
+                (String::new(), false, EntryKind::File, Utc::now())
+            };
 
         // Check if file has actually changed
         let is_modified =
             if let Some(existing_state) = tracker.tracked_files.get(&PathBuf::from(file_path)) {
                 // File is modified if:
                 // 1. Hash has changed
-                // 2. Existence state has changed
+                // 2. Existence state or kind has changed
                 // 3. It was already marked as modified
                 existing_state.last_hash != hash
                     || existing_state.exists != exists
+                    || existing_state.kind != kind
                     || existing_state.is_modified
             } else {
                 // New file is always considered modified
@@ -155,40 +254,125 @@ impl CheckpointManager {
                 is_modified,
                 last_modified: modified,
                 exists,
+                kind,
             },
         );
 
         Ok(())
     }
 
-    /// Track potential file changes from bash commands
-    async fn track_bash_side_effects(&self, command: &str) -> Result<()> {
-        // Common file-modifying commands
-        let file_commands = [
-            "echo", "cat", "cp", "mv", "rm", "touch", "sed", "awk", "npm", "yarn", "pnpm", "bun",
-            "cargo", "make", "gcc", "g++",
-        ];
+    /// Track the files a finished Bash command created, modified or deleted
+    ///
+    /// Does nothing unless a manifest was recorded when the command started.
+    async fn track_bash_side_effects(&self, tool_use_id: &str) -> Result<()> {
+        let Some(before) = self.bash_manifests.write().await.remove(tool_use_id) else {
+            return Ok(());
+        };
 
-        // Simple heuristic: if command contains file-modifying operations
-        for cmd in &file_commands {
-            if command.contains(cmd) {
-                // Mark all tracked files as potentially modified
-                let mut tracker = self.file_tracker.write().await;
-                for (_, state) in tracker.tracked_files.iter_mut() {
-                    state.is_modified = true;
-                }
-                break;
-            }
+        let after = self.capture_manifest().await?;
+        let changes = before.changes_since(&after);
+        if changes.is_empty() {
+            return Ok(());
+        }
+
+        log::debug!(
+            "Bash command {} created {}, modified {} and deleted {} files",
+            tool_use_id,
+            changes.created.len(),
+            changes.modified.len(),
+            changes.deleted.len()
+        );
+        for path in changes.paths() {
+            self.track_file_modification(&path.to_string_lossy())
+                .await?;
         }
 
         Ok(())
     }
 
-    /// Create a checkpoint
+    /// Capture the working tree, reusing hashes of files unchanged since the last capture
+    ///
+    /// The first capture reuses the hashes recorded for the current
+    /// checkpoint instead, so it only reads files changed since.
+    async fn capture_manifest(&self) -> Result<WorkingTreeManifest> {
+        let mut last_manifest = self.last_manifest.write().await;
+        let previous = match last_manifest.take() {
+            Some(manifest) => manifest,
+            None => self.checkpoint_manifest().await,
+        };
+        let max_file_size = self.timeline.read().await.max_file_size;
+        let manifest =
+            WorkingTreeManifest::capture(&self.project_path, Some(&previous), max_file_size)?;
+        *last_manifest = Some(manifest.clone());
+        Ok(manifest)
+    }
+
+    /// Manifest of the files whose tracked hash matches the current checkpoint
+    ///
+    /// The tracker holds each file's hash and mtime when it was last read,
+    /// and the checkpoint the size of that content.
+    async fn checkpoint_manifest(&self) -> WorkingTreeManifest {
+        let Some(checkpoint_id) = self.timeline.read().await.current_checkpoint_id.clone() else {
+            return WorkingTreeManifest::default();
+        };
+        let paths =
+            CheckpointPaths::new(&self.storage.claude_dir, &self.project_id, &self.session_id);
+        let file_refs = match CheckpointStorage::read_checkpoint_refs(&paths, &checkpoint_id) {
+            Ok(file_refs) => file_refs,
+            Err(e) => {
+                log::warn!(
+                    "Failed to read checkpoint {} manifest: {}",
+                    checkpoint_id,
+                    e
+                );
+                return WorkingTreeManifest::default();
+            }
+        };
+
+        let tracker = self.file_tracker.read().await;
+        let entries = file_refs
+            .into_iter()
+            .filter(|file_ref| !file_ref.is_deleted && file_ref.kind == EntryKind::File)
+            .filter_map(|file_ref| {
+                let state = tracker.tracked_files.get(&file_ref.path)?;
+                (state.exists && state.kind == EntryKind::File && state.last_hash == file_ref.hash)
+                    .then(|| {
+                        let entry = ManifestEntry {
+                            kind: EntryKind::File,
+                            modified: Some(state.last_modified.into()),
+                            size: file_ref.size,
+                            hash: file_ref.hash,
+                        };
+                        (file_ref.path, entry)
+                    })
+            })
+            .collect();
+        WorkingTreeManifest::from_entries(entries)
+    }
+
+    /// Create a checkpoint and prune old ones according to the retention policy
     pub async fn create_checkpoint(
         &self,
         description: Option<String>,
         parent_checkpoint_id: Option<String>,
+    ) -> Result<CheckpointResult> {
+        let result = self
+            .save_new_checkpoint(description, parent_checkpoint_id, None)
+            .await?;
+
+        if let Err(e) = self.apply_retention_policy().await {
+            log::warn!("Failed to apply checkpoint retention policy: {}", e);
+        }
+
+        Ok(result)
+    }
+
+    /// Snapshot the current state as a new checkpoint
+    async fn save_new_checkpoint(
+        &self,
+        description: Option<String>,
+        parent_checkpoint_id: Option<String>,
+        merge_parent_checkpoint_id: Option<String>,
     ) -> Result<CheckpointResult> {
         let messages = self.current_messages.read().await;
         let message_index = messages.len().saturating_sub(1);
@@ -197,48 +381,17 @@ impl CheckpointManager {
         let (user_prompt, model_used, total_tokens) =
             self.extract_checkpoint_metadata(&messages).await?;
 
-        // Ensure every file in the project is tracked so new checkpoints include all files
-        // Recursively walk the project directory and track each file
-        fn collect_files(
-            dir: &std::path::Path,
-            base: &std::path::Path,
-            files: &mut Vec<std::path::PathBuf>,
-        ) -> Result<(), std::io::Error> {
-            for entry in std::fs::read_dir(dir)? {
-                let entry = entry?;
-                let path = entry.path();
-                if path.is_dir() {
-                    // Skip hidden directories like .git
-                    if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-                        if name.starts_with('.') {
-                            continue;
-                        }
-                    }
-                    collect_files(&path, base, files)?;
-                } else if path.is_file() {
-                    // Compute relative path from project root
-                    if let Ok(rel) = path.strip_prefix(base) {
-                        files.push(rel.to_path_buf());
-                    }
-                }
-            }
-            Ok(())
-        }
-        let mut all_files = Vec::new();
-        let project_dir = &self.project_path;
-        let _ = collect_files(project_dir.as_path(), project_dir.as_path(), &mut all_files);
-        for rel in all_files {
-            if let Some(p) = rel.to_str() {
-                // Track each file for snapshot
-                let _ = self.track_file_modification(p).await;
-            }
-        }
+        // Ensure every non-ignored file in the project is tracked so new checkpoints include all files
+        self.refresh_file_tracker().await;
 
         // Generate checkpoint ID early so snapshots reference it
         let checkpoint_id = storage::CheckpointStorage::generate_checkpoint_id();
 
         // Create file snapshots
-        let file_snapshots = self.create_file_snapshots(&checkpoint_id).await?;
+        let (file_snapshots, unchanged_files, skipped_files) =
+            self.create_file_snapshots(&checkpoint_id).await?;
+        let skipped_warnings: Vec<String> =
+            skipped_files.iter().map(skipped_file_warning).collect();
 
         // Generate checkpoint struct
         let checkpoint = Checkpoint {
@@ -248,36 +401,46 @@ impl CheckpointManager {
             message_index,
             timestamp: Utc::now(),
             description,
+            tags: Vec::new(),
+            full_tree: true,
             parent_checkpoint_id: {
                 if let Some(parent_id) = parent_checkpoint_id {
                     Some(parent_id)
                 } else {
-                    // Perform an asynchronous read to avoid blocking within the runtime
+                    // Perform an asynchronous read to avoid blocking within the runtime.
+                    // Without a current checkpoint, new checkpoints hang off the root.
                     let timeline = self.timeline.read().await;
-                    timeline.current_checkpoint_id.clone()
+                    timeline.current_checkpoint_id.clone().or_else(|| {
+                        timeline
+                            .root_node
+                            .as_ref()
+                            .map(|root| root.checkpoint.id.clone())
+                    })
                 }
             },
+            merge_parent_checkpoint_id,
             metadata: CheckpointMetadata {
                 total_tokens,
                 model_used,
                 user_prompt,
                 file_changes: file_snapshots.len(),
-                snapshot_size: storage::CheckpointStorage::estimate_checkpoint_size(
-                    &messages.join("\n"),
-                    &file_snapshots,
-                ),
+                // Measured by storage when the checkpoint is written
+                snapshot_size: 0,
+                skipped_files,
             },
         };
 
         // Save checkpoint
         let messages_content = messages.join("\n");
-        let result = self.storage.save_checkpoint(
+        let mut result = self.storage.save_checkpoint(
             &self.project_id,
             &self.session_id,
             &checkpoint,
             file_snapshots,
+            unchanged_files,
             &messages_content,
         )?;
+        result.warnings.extend(skipped_warnings);
 
         // Reload timeline from disk so in-memory timeline has updated nodes and total_checkpoints
         let claude_dir = self.storage.claude_dir.clone();
@@ -297,10 +460,171 @@ impl CheckpointManager {
         for (_, state) in tracker.tracked_files.iter_mut() {
             state.is_modified = false;
         }
-
+        self.save_tracker_state(&tracker, messages.len());
         Ok(result)
     }
 
+    /// Save the tracker and message cursor so a restarted app resumes from them
+    fn save_tracker_state(&self, tracker: &FileTracker, message_cursor: usize) {
+        let paths =
+            CheckpointPaths::new(&self.storage.claude_dir, &self.project_id, &self.session_id);
+        let state = TrackerState {
+            tracked_files: tracker.tracked_files.clone(),
+            message_cursor,
+        };
+        if let Err(e) = self.storage.save_tracker_state(&paths.tracker_file, &state) {
+            log::warn!("Failed to save file tracker state: {}", e);
+        }
+    }
+
+    /// Number of session messages tracked so far
+    pub async fn message_count(&self) -> usize {
+        self.current_messages.read().await.len()
+    }
+
+    /// Track every non-ignored file, symlink and empty directory in the project
+    async fn track_project_files(&self) {
+        let all_entries = ProjectWalker::new(&self.project_path)
+            .collect_entries()
+            .unwrap_or_default();
+        for (rel, _) in all_entries {
+            if let Some(p) = rel.to_str() {
+                // Track each file for snapshot
+                let _ = self.track_file_modification(p).await;
+            }
+        }
+    }
+
+    /// Bring the file tracker up to date before reading it
+    ///
+    /// With a live watcher only its queued events are waited for; otherwise
+    /// the whole project is walked.
+    async fn refresh_file_tracker(&self) {
+        if self.live.is_active() {
+            let deadline = Instant::now() + WATCHER_FLUSH_TIMEOUT;
+            while self.live.has_pending_events() && Instant::now() < deadline {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            if !self.live.has_pending_events() {
+                return;
+            }
+            log::warn!(
+                "File watcher is behind, scanning {}",
+                self.project_path.display()
+            );
+        }
+
+        self.track_project_files().await;
+    }
+
+    /// Re-track every project file, including tracked files that were deleted
+    ///
+    /// Enables live tracking once the tracker matches the disk.
+    pub async fn sync_file_tracker(&self) {
+        self.live.set_active(false);
+        self.track_project_files().await;
+
+        let missing: Vec<PathBuf> = {
+            let tracker = self.file_tracker.read().await;
+            tracker
+                .tracked_files
+                .iter()
+                .filter(|(path, state)| {
+                    state.exists && entry_metadata(&self.project_path.join(path)).is_none()
+                })
+                .map(|(path, _)| path.clone())
+                .collect()
+        };
+        for rel in missing {
+            let _ = self.track_file_modification(&rel.to_string_lossy()).await;
+        }
+
+        self.live.set_active(true);
+    }
+
+    /// Track files reported as changed by the project watcher
+    ///
+    /// A directory event may stand for everything in it, for example when a
+    /// tree is moved into the project, so its entries are walked and tracked.
+    pub async fn track_watched_paths(&self, paths: Vec<PathBuf>) {
+        for rel in paths {
+            let full_path = self.project_path.join(&rel);
+            let metadata = entry_metadata(&full_path);
+
+            let targets = if metadata
+                .as_ref()
+                .is_some_and(|(kind, _)| *kind == EntryKind::Directory)
+                && !walker::is_empty_dir(&full_path)
+            {
+                // Only empty directories are recorded, so track what is inside
+                match ProjectWalker::new(&self.project_path).collect_entries_under(&rel) {
+                    Ok(entries) => entries.into_iter().map(|(path, _)| path).collect(),
+                    Err(e) => {
+                        log::warn!("Failed to walk {}: {}", rel.display(), e);
+                        Vec::new()
+                    }
+                }
+            } else if metadata.is_some() {
+                vec![rel]
+            } else {
+                // A removed path may be a directory holding tracked files
+                let tracker = self.file_tracker.read().await;
+                tracker
+                    .tracked_files
+                    .keys()
+                    .filter(|path| path.starts_with(&rel))
+                    .cloned()
+                    .collect()
+            };
+
+            for target in targets {
+                if let Err(e) = self
+                    .track_file_modification(&target.to_string_lossy())
+                    .await
+                {
+                    log::warn!("Failed to track {}: {}", target.display(), e);
+                }
+            }
+        }
+    }
+
+    /// Project directory this manager checkpoints
+    pub fn project_path(&self) -> &Path {
+        &self.project_path
+    }
+
+    /// Live tracking state to share with a project watcher
+    pub fn live_tracking(&self) -> Arc<LiveTracking> {
+        Arc::clone(&self.live)
+    }
+
+    /// Check whether the working tree or conversation moved past the current checkpoint
+    async fn has_unsaved_changes(&self) -> bool {
+        self.refresh_file_tracker().await;
+
+        let tracker = self.file_tracker.read().await;
+        if tracker
+            .tracked_files
+            .values()
+            .any(|state| state.is_modified)
+        {
+            return true;
+        }
+
+        let timeline = self.timeline.read().await;
+        let current = timeline
+            .current_checkpoint_id
+            .as_deref()
+            .and_then(|id| timeline.find_checkpoint(id));
+        match current {
+            Some(node) => {
+                let message_count = self.current_messages.read().await.len();
+                message_count.saturating_sub(1) != node.checkpoint.message_index
+            }
+            None => true,
+        }
+    }
+
     /// Extract metadata from messages for checkpoint
     async fn extract_checkpoint_metadata(
         &self,
@@ -398,41 +722,104 @@ impl CheckpointManager {
     }
 
     /// Create file snapshots for all tracked modified files
-    async fn create_file_snapshots(&self, checkpoint_id: &str) -> Result<Vec<FileSnapshot>> {
+    ///
+    /// Unchanged files whose content is already in the pool are returned as
+    /// references to it instead, so the checkpoint still lists the whole tree.
+    /// Files over the session's size limit are returned as skipped.
+    async fn create_file_snapshots(
+        &self,
+        checkpoint_id: &str,
+    ) -> Result<(Vec<FileSnapshot>, Vec<FileRef>, Vec<SkippedFile>)> {
+        let max_file_size = self.timeline.read().await.max_file_size;
         let tracker = self.file_tracker.read().await;
+        let paths =
+            CheckpointPaths::new(&self.storage.claude_dir, &self.project_id, &self.session_id);
         let mut snapshots = Vec::new();
+        let mut unchanged = Vec::new();
+        let mut skipped = Vec::new();
 
         for (rel_path, state) in &tracker.tracked_files {
-            // Skip files that haven't been modified
-            if !state.is_modified {
+            let full_path = self.project_path.join(rel_path);
+
+            let current = entry_metadata(&full_path);
+            // Directories are only recorded while empty
+            if current
+                .as_ref()
+                .is_some_and(|(kind, _)| *kind == EntryKind::Directory)
+                && !walker::is_empty_dir(&full_path)
+            {
                 continue;
             }
+            if let Some((EntryKind::File, metadata)) = &current {
+                if metadata.len() > max_file_size {
+                    skipped.push(SkippedFile {
+                        path: rel_path.clone(),
+                        size: metadata.len(),
+                        reason: SkipReason::TooLarge,
+                    });
+                    continue;
+                }
+            }
 
-            let full_path = self.project_path.join(rel_path);
+            if !state.is_modified {
+                // Deleted files are left out of the tree
+                let Some((kind, metadata)) = current
+                    .as_ref()
+                    .filter(|(kind, _)| state.exists && *kind == state.kind)
+                else {
+                    continue;
+                };
+                if paths.content_object_path(&state.last_hash).exists() {
+                    unchanged.push(FileRef {
+                        path: rel_path.clone(),
+                        hash: state.last_hash.clone(),
+                        is_deleted: false,
+                        permissions: entry_permissions(*kind, metadata),
+                        size: match kind {
+                            EntryKind::Directory => 0,
+                            _ => metadata.len(),
+                        },
+                        kind: *kind,
+                    });
+                    continue;
+                }
+                // Never stored, so snapshot it like a modified file
+            }
 
-            let (content, exists, permissions, size, current_hash) = if full_path.exists() {
-                let content = fs::read_to_string(&full_path).unwrap_or_default();
-                let current_hash = storage::CheckpointStorage::calculate_file_hash(&content);
+            // Large files are hashed as a stream and storage streams them
+            // into the pool, so they are never held in memory
+            let streamed = current.as_ref().is_some_and(|(kind, metadata)| {
+                *kind == EntryKind::File
+                    && metadata.len() > storage::CHUNKED_OBJECT_THRESHOLD as u64
+            });
 
-                // Don't skip based on hash - if is_modified is true, we should snapshot it
-                // The hash check in track_file_modification already determined if it changed
+            let (content, exists, permissions, size, current_hash, kind) =
+                if let Some((kind, metadata)) = current {
+                    let permissions = entry_permissions(kind, &metadata);
+                    if streamed {
+                        let current_hash = entry_hash(&full_path, kind).unwrap_or_default();
+                        (
+                            Vec::new(),
+                            true,
+                            permissions,
+                            metadata.len(),
+                            current_hash,
+                            kind,
+                        )
+                    } else {
+                        let content = read_entry_content(&full_path, kind).unwrap_or_default();
+                        let current_hash =
+                            storage::CheckpointStorage::calculate_file_hash(&content);
 
-                let metadata = fs::metadata(&full_path)?;
-                let permissions = {
-                    #[cfg(unix)]
-                    {
-                        use std::os::unix::fs::PermissionsExt;
-                        Some(metadata.permissions().mode())
-                    }
-                    #[cfg(not(unix))]
-                    {
-                        None
+                        // Don't skip based on hash - if is_modified is true, we should snapshot it
+                        // The hash check in track_file_modification already determined if it changed
+
+                        let size = content.len() as u64;
+                        (content, true, permissions, size, current_hash, kind)
                     }
+                } else {
+                    (Vec::new(), false, None, 0, String::new(), EntryKind::File)
                 };
-                (content, true, permissions, metadata.len(), current_hash)
-            } else {
-                (String::new(), false, None, 0, String::new())
-            };
 
             snapshots.push(FileSnapshot {
                 checkpoint_id: checkpoint_id.to_string(),
@@ -442,126 +829,38 @@ impl CheckpointManager {
                 is_deleted: !exists,
                 permissions,
                 size,
+                kind,
+                source: if streamed {
+                    ContentSource::File(full_path)
+                } else {
+                    ContentSource::Inline
+                },
             });
         }
 
-        Ok(snapshots)
+        skipped.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok((snapshots, unchanged, skipped))
     }
 
     /// Restore a checkpoint
     pub async fn restore_checkpoint(&self, checkpoint_id: &str) -> Result<CheckpointResult> {
         // Load checkpoint data
-        let (checkpoint, file_snapshots, messages) =
+        let (checkpoint, _, messages) =
             self.storage
                 .load_checkpoint(&self.project_id, &self.session_id, checkpoint_id)?;
+        let file_snapshots = self.load_tree_snapshots(checkpoint_id).await?;
+
+        // Save the current state first so the restore can be undone from the timeline
+        let safety_checkpoint_id = self
+            .save_safety_checkpoint(format!(
+                "Pre-restore snapshot (before restoring {})",
+                short_id(checkpoint_id)
+            ))
+            .await
+            .context("Failed to create pre-restore checkpoint")?;
 
-        // First, collect all files currently in the project to handle deletions
-        fn collect_all_project_files(
-            dir: &std::path::Path,
-            base: &std::path::Path,
-            files: &mut Vec<std::path::PathBuf>,
-        ) -> Result<(), std::io::Error> {
-            for entry in std::fs::read_dir(dir)? {
-                let entry = entry?;
-                let path = entry.path();
-                if path.is_dir() {
-                    // Skip hidden directories like .git
-                    if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-                        if name.starts_with('.') {
-                            continue;
-                        }
-                    }
-                    collect_all_project_files(&path, base, files)?;
-                } else if path.is_file() {
-                    // Compute relative path from project root
-                    if let Ok(rel) = path.strip_prefix(base) {
-                        files.push(rel.to_path_buf());
-                    }
-                }
-            }
-            Ok(())
-        }
-
-        let mut current_files = Vec::new();
-        let _ =
-            collect_all_project_files(&self.project_path, &self.project_path, &mut current_files);
-
-        // Create a set of files that should exist after restore
-        let mut checkpoint_files = std::collections::HashSet::new();
-        for snapshot in &file_snapshots {
-            if !snapshot.is_deleted {
-                checkpoint_files.insert(snapshot.file_path.clone());
-            }
-        }
-
-        // Delete files that exist now but shouldn't exist in the checkpoint
-        let mut warnings = Vec::new();
-        let mut files_processed = 0;
-
-        for current_file in current_files {
-            if !checkpoint_files.contains(&current_file) {
-                // This file exists now but not in the checkpoint, so delete it
-                let full_path = self.project_path.join(&current_file);
-                match fs::remove_file(&full_path) {
-                    Ok(_) => {
-                        files_processed += 1;
-                        log::info!("Deleted file not in checkpoint: {:?}", current_file);
-                    }
-                    Err(e) => {
-                        warnings.push(format!(
-                            "Failed to delete {}: {}",
-                            current_file.display(),
-                            e
-                        ));
-                    }
-                }
-            }
-        }
-
-        // Clean up empty directories
-        fn remove_empty_dirs(
-            dir: &std::path::Path,
-            base: &std::path::Path,
-        ) -> Result<bool, std::io::Error> {
-            if dir == base {
-                return Ok(false); // Don't remove the base directory
-            }
-
-            let mut is_empty = true;
-            for entry in fs::read_dir(dir)? {
-                let entry = entry?;
-                let path = entry.path();
-                if path.is_dir() {
-                    if !remove_empty_dirs(&path, base)? {
-                        is_empty = false;
-                    }
-                } else {
-                    is_empty = false;
-                }
-            }
-
-            if is_empty {
-                fs::remove_dir(dir)?;
-                Ok(true)
-            } else {
-                Ok(false)
-            }
-        }
-
-        // Clean up any empty directories left after file deletion
-        let _ = remove_empty_dirs(&self.project_path, &self.project_path);
-
-        // Restore files from checkpoint
-        for snapshot in &file_snapshots {
-            match self.restore_file_snapshot(snapshot).await {
-                Ok(_) => files_processed += 1,
-                Err(e) => warnings.push(format!(
-                    "Failed to restore {}: {}",
-                    snapshot.file_path.display(),
-                    e
-                )),
-            }
-        }
+        let (files_processed, warnings) =
+            self.write_snapshots(checkpoint_id, &file_snapshots).await;
 
         // Update current messages
         let mut current_messages = self.current_messages.write().await;
@@ -570,9 +869,14 @@ impl CheckpointManager {
             current_messages.push(line.to_string());
         }
 
-        // Update timeline
+        // Update timeline, on disk too so the session reopens at this checkpoint
         let mut timeline = self.timeline.write().await;
         timeline.current_checkpoint_id = Some(checkpoint_id.to_string());
+        let paths =
+            CheckpointPaths::new(&self.storage.claude_dir, &self.project_id, &self.session_id);
+        self.storage
+            .save_timeline(&paths.timeline_file, &timeline)
+            .context("Failed to save timeline")?;
 
         // Update file tracker
         let mut tracker = self.file_tracker.write().await;
@@ -586,97 +890,1066 @@ impl CheckpointManager {
                         is_modified: false,
                         last_modified: Utc::now(),
                         exists: true,
+                        kind: snapshot.kind,
                     },
                 );
             }
         }
+        self.save_tracker_state(&tracker, current_messages.len());
 
         Ok(CheckpointResult {
             checkpoint: checkpoint.clone(),
             files_processed,
             warnings,
+            safety_checkpoint_id,
         })
     }
 
-    /// Restore a single file from snapshot
-    async fn restore_file_snapshot(&self, snapshot: &FileSnapshot) -> Result<()> {
-        let full_path = self.project_path.join(&snapshot.file_path);
-
-        if snapshot.is_deleted {
-            // Delete the file if it exists
-            if full_path.exists() {
-                fs::remove_file(&full_path).context("Failed to delete file")?;
-            }
-        } else {
-            // Create parent directories if needed
-            if let Some(parent) = full_path.parent() {
-                fs::create_dir_all(parent).context("Failed to create parent directories")?;
-            }
-
-            // Write file content
-            fs::write(&full_path, &snapshot.content).context("Failed to write file")?;
-
-            // Restore permissions if available
-            #[cfg(unix)]
-            if let Some(mode) = snapshot.permissions {
-                use std::os::unix::fs::PermissionsExt;
-                let permissions = std::fs::Permissions::from_mode(mode);
-                fs::set_permissions(&full_path, permissions)
-                    .context("Failed to set file permissions")?;
-            }
+    /// Checkpoint the working tree before it is overwritten, if it has unsaved changes
+    ///
+    /// Retention is skipped so it cannot prune the checkpoint about to be
+    /// written to disk.
+    async fn save_safety_checkpoint(&self, description: String) -> Result<Option<String>> {
+        if !self.has_unsaved_changes().await {
+            return Ok(None);
         }
-
-        Ok(())
+        let result = self
+            .save_new_checkpoint(Some(description), None, None)
+            .await?;
+        Ok(Some(result.checkpoint.id))
     }
 
-    /// Get the current timeline
-    pub async fn get_timeline(&self) -> SessionTimeline {
-        self.timeline.read().await.clone()
-    }
+    /// Make the project on disk match a set of snapshots
+    ///
+    /// Returns the number of files changed and a warning for each file that
+    /// could not be.
+    async fn write_snapshots(
+        &self,
+        checkpoint_id: &str,
+        file_snapshots: &[FileSnapshot],
+    ) -> (usize, Vec<String>) {
+        // Work out exactly what has to change, then apply that plan
+        let max_file_size = self.timeline.read().await.max_file_size;
+        let plan = self.build_restore_plan(checkpoint_id, file_snapshots, max_file_size);
+        let snapshots_by_path: HashMap<&Path, &FileSnapshot> = file_snapshots
+            .iter()
+            .map(|s| (s.file_path.as_path(), s))
+            .collect();
 
-    /// List all checkpoints
-    pub async fn list_checkpoints(&self) -> Vec<Checkpoint> {
-        let timeline = self.timeline.read().await;
-        let mut checkpoints = Vec::new();
+        let kept_dirs = directory_paths(file_snapshots.iter());
 
-        if let Some(root) = &timeline.root_node {
-            Self::collect_checkpoints_from_node(root, &mut checkpoints);
+        let mut warnings = Vec::new();
+        let mut files_processed = 0;
+
+        // Delete files that exist now but shouldn't exist in the checkpoint
+        for rel_path in &plan.files_to_delete {
+            match remove_entry(&self.project_path.join(rel_path)) {
+                Ok(_) => {
+                    files_processed += 1;
+                    log::info!("Deleted file not in checkpoint: {:?}", rel_path);
+                    // Clean up any empty directories left after file deletion
+                    self.remove_empty_parent_dirs(rel_path, &kept_dirs);
+                }
+                Err(e) => {
+                    warnings.push(format!("Failed to delete {}: {}", rel_path.display(), e));
+                }
+            }
         }
 
-        checkpoints
-    }
+        // Write files that are missing or differ from the checkpoint
+        let mut written = HashSet::new();
+        for rel_path in plan.files_to_create.iter().chain(&plan.files_to_overwrite) {
+            let Some(snapshot) = snapshots_by_path.get(rel_path.as_path()) else {
+                continue;
+            };
+            match self.restore_file_snapshot(snapshot).await {
+                Ok(_) => {
+                    files_processed += 1;
+                    written.insert(rel_path);
+                }
+                Err(e) => warnings.push(format!("Failed to restore {}: {}", rel_path.display(), e)),
+            }
+        }
 
-    /// Recursively collect checkpoints from timeline tree
-    fn collect_checkpoints_from_node(
-        node: &super::TimelineNode,
-        checkpoints: &mut Vec<Checkpoint>,
-    ) {
-        checkpoints.push(node.checkpoint.clone());
-        for child in &node.children {
-            Self::collect_checkpoints_from_node(child, checkpoints);
+        // Fix permissions on files whose content already matched
+        for change in &plan.permission_changes {
+            if written.contains(&change.path) {
+                continue;
+            }
+            match set_file_permissions(&self.project_path.join(&change.path), change.to) {
+                Ok(_) => files_processed += 1,
+                Err(e) => warnings.push(format!(
+                    "Failed to set permissions on {}: {}",
+                    change.path.display(),
+                    e
+                )),
+            }
         }
+
+        (files_processed, warnings)
     }
 
-    /// Fork from a checkpoint
-    pub async fn fork_from_checkpoint(
+    /// Merge the `theirs` checkpoint into `ours` and record the result
+    ///
+    /// Files are merged against the branches' common ancestor. Text files
+    /// changed on both sides are merged line by line, with conflict markers
+    /// where the edits overlap. The merged tree is written to disk and saved
+    /// as a child of `ours` that also records `theirs` as a parent. The
+    /// conversation continues from `ours`.
+    pub async fn merge_checkpoints(
         &self,
-        checkpoint_id: &str,
+        ours_id: &str,
+        theirs_id: &str,
         description: Option<String>,
-    ) -> Result<CheckpointResult> {
-        // Load the checkpoint to fork from
-        let (_base_checkpoint, _, _) =
+    ) -> Result<MergeResult> {
+        if ours_id == theirs_id {
+            anyhow::bail!("Cannot merge a checkpoint with itself");
+        }
+
+        let timeline = self.timeline.read().await.clone();
+        for id in [ours_id, theirs_id] {
+            if timeline.find_checkpoint(id).is_none() {
+                anyhow::bail!("Checkpoint {} not found", id);
+            }
+        }
+        let base_id = timeline.merge_base(ours_id, theirs_id);
+        if base_id.as_deref() == Some(theirs_id) {
+            anyhow::bail!(
+                "Checkpoint {} is already part of {}",
+                short_id(theirs_id),
+                short_id(ours_id)
+            );
+        }
+
+        let load_tree = |id: &str| {
+            self.storage
+                .load_checkpoint_tree(&self.project_id, &self.session_id, &timeline, id)
+        };
+        let base_tree = match &base_id {
+            Some(id) => load_tree(id)?,
+            None => BTreeMap::new(),
+        };
+        let ours_tree = load_tree(ours_id)?;
+        let theirs_tree = load_tree(theirs_id)?;
+        let paths =
+            CheckpointPaths::new(&self.storage.claude_dir, &self.project_id, &self.session_id);
+        let merged = merge::merge_trees(
+            &self.storage,
+            &paths,
+            &base_tree,
+            &ours_tree,
+            &theirs_tree,
+            (short_id(ours_id), short_id(theirs_id)),
+        )?;
+
+        let (_, _, messages) =
+            self.storage
+                .load_checkpoint(&self.project_id, &self.session_id, ours_id)?;
+
+        let safety_checkpoint_id = self
+            .save_safety_checkpoint(format!(
+                "Pre-merge snapshot (before merging {} into {})",
+                short_id(theirs_id),
+                short_id(ours_id)
+            ))
+            .await
+            .context("Failed to create pre-merge checkpoint")?;
+
+        let merged_snapshots: Vec<FileSnapshot> = merged.files.values().cloned().collect();
+        let (files_processed, warnings) = self.write_snapshots(ours_id, &merged_snapshots).await;
+
+        {
+            let mut current_messages = self.current_messages.write().await;
+            *current_messages = messages.lines().map(str::to_string).collect();
+        }
+
+        // Snapshot the whole merged tree, and record files only `ours` had as deleted
+        {
+            let mut tracker = self.file_tracker.write().await;
+            tracker.tracked_files.clear();
+            for path in merged.files.keys().chain(ours_tree.keys()) {
+                tracker.tracked_files.insert(
+                    path.clone(),
+                    FileState {
+                        last_hash: String::new(),
+                        is_modified: true,
+                        last_modified: Utc::now(),
+                        exists: true,
+                        kind: merged.files.get(path).map(|s| s.kind).unwrap_or_default(),
+                    },
+                );
+            }
+        }
+
+        let description = description.unwrap_or_else(|| match merged.conflicts.len() {
+            0 => format!("Merge {} into {}", short_id(theirs_id), short_id(ours_id)),
+            n => format!(
+                "Merge {} into {} ({} conflicts)",
+                short_id(theirs_id),
+                short_id(ours_id),
+                n
+            ),
+        });
+        let result = self
+            .save_new_checkpoint(
+                Some(description),
+                Some(ours_id.to_string()),
+                Some(theirs_id.to_string()),
+            )
+            .await?;
+
+        if let Err(e) = self.apply_retention_policy().await {
+            log::warn!("Failed to apply checkpoint retention policy: {}", e);
+        }
+
+        Ok(MergeResult {
+            checkpoint: result.checkpoint,
+            base_checkpoint_id: base_id,
+            conflicts: merged.conflicts,
+            files_processed,
+            warnings,
+            safety_checkpoint_id,
+        })
+    }
+
+    /// Write the conversation at a checkpoint as a session's Claude transcript
+    ///
+    /// With this session's ID the transcript is rewound in place, so resuming
+    /// it continues from exactly the checkpointed history. With another ID a
+    /// new resumable session is written and this one is left alone.
+    pub fn write_session_transcript(
+        &self,
+        checkpoint_id: &str,
+        session_id: &str,
+    ) -> Result<PathBuf> {
+        let (_, _, messages) =
             self.storage
                 .load_checkpoint(&self.project_id, &self.session_id, checkpoint_id)?;
+        let new_session_id = (session_id != self.session_id).then_some(session_id);
+        let transcript = transcript::session_transcript(&messages, new_session_id);
+        self.storage
+            .write_session_transcript(&self.project_id, session_id, &transcript)
+    }
+
+    /// Start a new Claude session from a checkpoint's conversation and files
+    ///
+    /// The project files are restored to the checkpoint after a safety
+    /// checkpoint; this session's timeline, messages and transcript are left
+    /// alone so it can still be resumed where it was.
+    pub async fn fork_session(
+        &self,
+        checkpoint_id: &str,
+        new_session_id: &str,
+    ) -> Result<CheckpointResult> {
+        if new_session_id == self.session_id {
+            anyhow::bail!("A fork needs a new session ID");
+        }
+        self.write_session_transcript(checkpoint_id, new_session_id)?;
+
+        let safety_checkpoint_id = self
+            .save_safety_checkpoint(format!(
+                "Pre-fork snapshot (before forking {} into session {})",
+                short_id(checkpoint_id),
+                short_id(new_session_id)
+            ))
+            .await
+            .context("Failed to create pre-fork checkpoint")?;
 
-        // Restore to that checkpoint first
-        self.restore_checkpoint(checkpoint_id).await?;
+        let mut result = self
+            .restore_files(checkpoint_id, &["*".to_string()])
+            .await?;
+        result.safety_checkpoint_id = safety_checkpoint_id;
+        Ok(result)
+    }
 
-        // Create a new checkpoint with the fork
-        let fork_description =
-            description.unwrap_or_else(|| format!("Fork from checkpoint {}", &checkpoint_id[..8]));
+    /// Restore only the files matching `patterns` from a checkpoint
+    ///
+    /// Patterns are globs relative to the project root; a pattern matching a
+    /// directory selects everything under it. Matching files that did not
+    /// exist at the checkpoint are deleted. The rest of the project, the
+    /// message history and the current checkpoint are left alone, and the
+    /// restored files show up as modified since the current checkpoint.
+    pub async fn restore_files(
+        &self,
+        checkpoint_id: &str,
+        patterns: &[String],
+    ) -> Result<CheckpointResult> {
+        if patterns.is_empty() {
+            anyhow::bail!("No paths given to restore");
+        }
+        let matchers = patterns
+            .iter()
+            .map(|p| glob::Pattern::new(p).with_context(|| format!("Invalid path pattern {}", p)))
+            .collect::<Result<Vec<_>>>()?;
+        let options = glob::MatchOptions {
+            require_literal_separator: true,
+            ..Default::default()
+        };
+        let is_selected = |path: &Path| {
+            path.ancestors()
+                .filter(|p| !p.as_os_str().is_empty())
+                .any(|p| matchers.iter().any(|m| m.matches_path_with(p, options)))
+        };
 
-        self.create_checkpoint(Some(fork_description), Some(checkpoint_id.to_string()))
+        let timeline = self.timeline.read().await.clone();
+        let checkpoint = timeline
+            .find_checkpoint(checkpoint_id)
+            .map(|node| node.checkpoint.clone())
+            .with_context(|| format!("Checkpoint {} not found", checkpoint_id))?;
+        let max_file_size = timeline.max_file_size;
+        let tree = self.storage.load_checkpoint_tree(
+            &self.project_id,
+            &self.session_id,
+            &timeline,
+            checkpoint_id,
+        )?;
+
+        let selected: Vec<&FileSnapshot> = tree
+            .values()
+            .filter(|s| is_selected(&s.file_path))
+            .collect();
+        let to_delete: Vec<PathBuf> = self
+            .collect_checkpointed_entries(max_file_size)
+            .into_iter()
+            .map(|(path, _)| path)
+            .filter(|f| !tree.contains_key(f) && is_selected(f))
+            .filter(|f| !tree.keys().any(|p| p.starts_with(f)))
+            .collect();
+        if selected.is_empty() && to_delete.is_empty() {
+            anyhow::bail!(
+                "No files match {} in checkpoint {}",
+                patterns.join(", "),
+                short_id(checkpoint_id)
+            );
+        }
+
+        let mut warnings = Vec::new();
+        let mut changed = Vec::new();
+
+        for snapshot in selected {
+            let full_path = self.project_path.join(&snapshot.file_path);
+            let current = entry_metadata(&full_path);
+            let current_mode = current
+                .as_ref()
+                .and_then(|(kind, metadata)| entry_permissions(*kind, metadata));
+
+            let result = if !current
+                .as_ref()
+                .is_some_and(|(kind, _)| entry_matches(&full_path, *kind, snapshot))
+            {
+                self.restore_file_snapshot(snapshot).await
+            } else if let Some(mode) = snapshot.permissions.filter(|m| current_mode != Some(*m)) {
+                set_file_permissions(&full_path, mode).context("Failed to set file permissions")
+            } else {
+                continue;
+            };
+            match result {
+                Ok(_) => changed.push(snapshot.file_path.clone()),
+                Err(e) => warnings.push(format!(
+                    "Failed to restore {}: {}",
+                    snapshot.file_path.display(),
+                    e
+                )),
+            }
+        }
+
+        let kept_dirs = directory_paths(tree.values());
+        for rel_path in to_delete {
+            match remove_entry(&self.project_path.join(&rel_path)) {
+                Ok(_) => {
+                    self.remove_empty_parent_dirs(&rel_path, &kept_dirs);
+                    changed.push(rel_path);
+                }
+                Err(e) => {
+                    warnings.push(format!("Failed to delete {}: {}", rel_path.display(), e));
+                }
+            }
+        }
+
+        for rel_path in &changed {
+            self.track_file_modification(&rel_path.to_string_lossy())
+                .await?;
+        }
+
+        Ok(CheckpointResult {
+            checkpoint,
+            files_processed: changed.len(),
+            warnings,
+            safety_checkpoint_id: None,
+        })
+    }
+
+    /// Preview the changes `restore_checkpoint` would make, without touching disk
+    pub async fn plan_restore(&self, checkpoint_id: &str) -> Result<RestorePlan> {
+        let file_snapshots = self.load_tree_snapshots(checkpoint_id).await?;
+
+        let max_file_size = self.timeline.read().await.max_file_size;
+        Ok(self.build_restore_plan(checkpoint_id, &file_snapshots, max_file_size))
+    }
+
+    /// Snapshots of every file present at a checkpoint
+    async fn load_tree_snapshots(&self, checkpoint_id: &str) -> Result<Vec<FileSnapshot>> {
+        let timeline = self.timeline.read().await.clone();
+        let tree = self.storage.load_checkpoint_tree(
+            &self.project_id,
+            &self.session_id,
+            &timeline,
+            checkpoint_id,
+        )?;
+        Ok(tree.into_values().collect())
+    }
+
+    /// Entries of the project that checkpoints hold, leaving out files over
+    /// the size limit
+    fn collect_checkpointed_entries(&self, max_file_size: u64) -> Vec<(PathBuf, EntryKind)> {
+        ProjectWalker::new(&self.project_path)
+            .collect_entries()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|(path, kind)| {
+                *kind != EntryKind::File
+                    || fs::metadata(self.project_path.join(path))
+                        .is_ok_and(|m| m.len() <= max_file_size)
+            })
+            .collect()
+    }
+
+    /// Compare the project on disk against a checkpoint's snapshots
+    ///
+    /// Files over `max_file_size` are never checkpointed, so they are not
+    /// deleted either.
+    fn build_restore_plan(
+        &self,
+        checkpoint_id: &str,
+        file_snapshots: &[FileSnapshot],
+        max_file_size: u64,
+    ) -> RestorePlan {
+        // Ignored files (build output, dependencies) are left untouched
+        let current_entries = self.collect_checkpointed_entries(max_file_size);
+
+        // Files that should exist after restore
+        let checkpoint_files: HashSet<&Path> = file_snapshots
+            .iter()
+            .filter(|s| !s.is_deleted)
+            .map(|s| s.file_path.as_path())
+            .collect();
+
+        // Empty directories the checkpoint fills with files are kept
+        let mut files_to_delete: BTreeSet<PathBuf> = current_entries
+            .into_iter()
+            .filter(|(f, kind)| {
+                !checkpoint_files.contains(f.as_path())
+                    && (*kind != EntryKind::Directory
+                        || !checkpoint_files.iter().any(|p| p.starts_with(f)))
+            })
+            .map(|(f, _)| f)
+            .collect();
+
+        let mut plan = RestorePlan {
+            checkpoint_id: checkpoint_id.to_string(),
+            files_to_delete: Vec::new(),
+            files_to_create: Vec::new(),
+            files_to_overwrite: Vec::new(),
+            permission_changes: Vec::new(),
+            unchanged_files: 0,
+        };
+
+        for snapshot in file_snapshots {
+            let full_path = self.project_path.join(&snapshot.file_path);
+            let current = entry_metadata(&full_path);
+
+            if snapshot.is_deleted {
+                if current.is_some() {
+                    files_to_delete.insert(snapshot.file_path.clone());
+                }
+                continue;
+            }
+
+            let Some((kind, metadata)) = current else {
+                plan.files_to_create.push(snapshot.file_path.clone());
+                continue;
+            };
+
+            if entry_matches(&full_path, kind, snapshot) {
+                plan.unchanged_files += 1;
+            } else {
+                plan.files_to_overwrite.push(snapshot.file_path.clone());
+            }
+
+            // Symlinks have no mode of their own, and a replaced entry gets the snapshot's
+            if kind != snapshot.kind || kind == EntryKind::Symlink {
+                continue;
+            }
+            let current_mode = file_permissions(&metadata);
+            if let Some(mode) = snapshot.permissions {
+                if current_mode != Some(mode) {
+                    plan.permission_changes.push(PermissionChange {
+                        path: snapshot.file_path.clone(),
+                        from: current_mode,
+                        to: mode,
+                    });
+                }
+            }
+        }
+
+        plan.files_to_delete = files_to_delete.into_iter().collect();
+        plan.files_to_create.sort();
+        plan.files_to_overwrite.sort();
+        plan.permission_changes.sort_by(|a, b| a.path.cmp(&b.path));
+        plan
+    }
+
+    /// Remove directories left empty by deleting a file, stopping at the project root
+    ///
+    /// Only the deleted file's ancestors are considered, so empty directories
+    /// elsewhere (including ignored ones) are preserved, and the climb stops
+    /// at directories in `kept_dirs`.
+    fn remove_empty_parent_dirs(&self, deleted_file: &Path, kept_dirs: &HashSet<&Path>) {
+        let mut dir = deleted_file.parent();
+        while let Some(rel_dir) = dir {
+            if rel_dir.as_os_str().is_empty() || kept_dirs.contains(rel_dir) {
+                break;
+            }
+            // remove_dir fails on non-empty directories, which ends the climb
+            if fs::remove_dir(self.project_path.join(rel_dir)).is_err() {
+                break;
+            }
+            dir = rel_dir.parent();
+        }
+    }
+
+    /// Write a single file, symlink or empty directory from snapshot
+    async fn restore_file_snapshot(&self, snapshot: &FileSnapshot) -> Result<()> {
+        let full_path = self.project_path.join(&snapshot.file_path);
+
+        // Create parent directories if needed
+        if let Some(parent) = full_path.parent() {
+            fs::create_dir_all(parent).context("Failed to create parent directories")?;
+        }
+
+        // Remove an entry of another kind, so writes never follow a symlink
+        match entry_metadata(&full_path) {
+            Some((EntryKind::Directory, _)) if snapshot.kind != EntryKind::Directory => {
+                fs::remove_dir(&full_path).context("Failed to remove directory in the way")?
+            }
+            Some((kind, _))
+                if kind == EntryKind::Symlink || snapshot.kind == EntryKind::Symlink =>
+            {
+                fs::remove_file(&full_path).context("Failed to remove existing entry")?
+            }
+            _ => {}
+        }
+
+        match snapshot.kind {
+            EntryKind::File if snapshot.source == ContentSource::Pool => {
+                // Large content is decompressed straight to disk
+                let paths = CheckpointPaths::new(
+                    &self.storage.claude_dir,
+                    &self.project_id,
+                    &self.session_id,
+                );
+                let mut file = std::io::BufWriter::new(
+                    fs::File::create(&full_path).context("Failed to create file")?,
+                );
+                self.storage
+                    .copy_content_object(&paths, &snapshot.hash, &mut file)?;
+                file.flush().context("Failed to write file")?
+            }
+            EntryKind::File => {
+                fs::write(&full_path, &snapshot.content).context("Failed to write file")?
+            }
+            EntryKind::Symlink => {
+                create_symlink(&snapshot.content, &full_path).context("Failed to create symlink")?
+            }
+            EntryKind::Directory => {
+                fs::create_dir_all(&full_path).context("Failed to create directory")?
+            }
+        }
+
+        // Restore permissions if available; setting them on a symlink would change its target
+        if let Some(mode) = snapshot
+            .permissions
+            .filter(|_| snapshot.kind != EntryKind::Symlink)
+        {
+            set_file_permissions(&full_path, mode).context("Failed to set file permissions")?;
+        }
+
+        Ok(())
+    }
+
+    /// Verify this session's checkpoints, optionally pruning unrecoverable ones
+    pub async fn verify_integrity(&self, repair: bool) -> Result<VerifyReport> {
+        // Keep the in-memory timeline from changing while the files are checked
+        let mut timeline = self.timeline.write().await;
+
+        let report = self
+            .storage
+            .verify_session(&self.project_id, &self.session_id, repair)?;
+
+        if repair {
+            let paths =
+                CheckpointPaths::new(&self.storage.claude_dir, &self.project_id, &self.session_id);
+            if paths.timeline_file.exists() {
+                *timeline = self.storage.load_timeline(&paths.timeline_file)?;
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Prune checkpoints according to the session's retention policy
+    pub async fn apply_retention_policy(&self) -> Result<RetentionReport> {
+        let mut timeline = self.timeline.write().await;
+        if timeline.retention_policy.is_empty() {
+            return Ok(RetentionReport::default());
+        }
+
+        let policy = timeline.retention_policy.clone();
+        let report =
+            self.storage
+                .apply_retention_policy(&self.project_id, &self.session_id, &policy)?;
+        if !report.pruned_checkpoints.is_empty() {
+            *timeline = self.load_saved_timeline()?;
+        }
+
+        Ok(report)
+    }
+
+    /// Replace the session's retention policy and apply it
+    pub async fn update_retention_policy(
+        &self,
+        policy: RetentionPolicy,
+    ) -> Result<RetentionReport> {
+        {
+            let mut timeline = self.timeline.write().await;
+            timeline.retention_policy = policy;
+            let paths =
+                CheckpointPaths::new(&self.storage.claude_dir, &self.project_id, &self.session_id);
+            self.storage
+                .save_timeline(&paths.timeline_file, &timeline)?;
+        }
+
+        self.apply_retention_policy().await
+    }
+
+    /// Remove all but the newest `keep_count` checkpoints
+    pub async fn cleanup_old_checkpoints(&self, keep_count: usize) -> Result<usize> {
+        let mut timeline = self.timeline.write().await;
+        let removed =
+            self.storage
+                .cleanup_old_checkpoints(&self.project_id, &self.session_id, keep_count)?;
+        if removed > 0 {
+            *timeline = self.load_saved_timeline()?;
+        }
+
+        Ok(removed)
+    }
+
+    fn load_saved_timeline(&self) -> Result<SessionTimeline> {
+        let paths =
+            CheckpointPaths::new(&self.storage.claude_dir, &self.project_id, &self.session_id);
+        self.storage.load_timeline(&paths.timeline_file)
+    }
+
+    /// Write a checkpoint to git as a commit on `refs/claude/<session>/<checkpoint>`
+    ///
+    /// With `include_ancestors` every checkpoint on the way to the root is
+    /// exported too, each commit parented on its checkpoint's parents.
+    /// Otherwise the commit's parents are the refs of parent checkpoints that
+    /// were exported before. Returns the exported checkpoints, parents first.
+    /// Git has no empty directories, so those are left out.
+    pub async fn export_to_git(
+        &self,
+        checkpoint_id: &str,
+        include_ancestors: bool,
+    ) -> Result<Vec<GitExport>> {
+        let repo = GitRepo::open(&self.project_path)?;
+        let timeline = self.timeline.read().await.clone();
+        let paths =
+            CheckpointPaths::new(&self.storage.claude_dir, &self.project_id, &self.session_id);
+
+        let mut exports: Vec<GitExport> = Vec::new();
+        let mut commits: HashMap<String, String> = HashMap::new();
+        let mut blobs: HashMap<String, String> = HashMap::new();
+
+        // Post-order walk so parents are committed before their children
+        let mut stack = vec![(checkpoint_id.to_string(), false)];
+        while let Some((id, parents_visited)) = stack.pop() {
+            if commits.contains_key(&id) {
+                continue;
+            }
+            let checkpoint = timeline
+                .find_checkpoint(&id)
+                .map(|node| node.checkpoint.clone())
+                .with_context(|| format!("Checkpoint {} not found", id))?;
+            let parents: Vec<String> = [
+                checkpoint.parent_checkpoint_id.clone(),
+                checkpoint.merge_parent_checkpoint_id.clone(),
+            ]
+            .into_iter()
+            .flatten()
+            .filter(|p| timeline.find_checkpoint(p).is_some())
+            .collect();
+
+            if include_ancestors && !parents_visited {
+                stack.push((id, true));
+                stack.extend(
+                    parents
+                        .into_iter()
+                        .filter(|p| !commits.contains_key(p))
+                        .map(|p| (p, false)),
+                );
+                continue;
+            }
+
+            let mut parent_commits = Vec::new();
+            for parent in &parents {
+                let commit = match commits.get(parent) {
+                    Some(commit) => Some(commit.clone()),
+                    None => {
+                        repo.resolve_commit(&GitRepo::checkpoint_ref(&self.session_id, parent))?
+                    }
+                };
+                parent_commits.extend(commit);
+            }
+
+            let tree_refs: Vec<(PathBuf, FileRef)> = self
+                .storage
+                .load_checkpoint_tree_refs(&self.project_id, &self.session_id, &timeline, &id)?
+                .into_iter()
+                .filter(|(_, file_ref)| file_ref.kind != EntryKind::Directory)
+                .collect();
+
+            // Contents new to this export are streamed to temporary files and
+            // stored with one git call
+            let new_hashes: BTreeSet<&str> = tree_refs
+                .iter()
+                .map(|(_, file_ref)| file_ref.hash.as_str())
+                .filter(|hash| !blobs.contains_key(*hash))
+                .collect();
+            if !new_hashes.is_empty() {
+                let staging =
+                    tempfile::tempdir().context("Failed to create temporary directory")?;
+                let mut staged = Vec::new();
+                for hash in &new_hashes {
+                    let staged_path = staging.path().join(hash);
+                    let file = fs::File::create(&staged_path)
+                        .with_context(|| format!("Failed to create {}", staged_path.display()))?;
+                    let mut writer = std::io::BufWriter::new(file);
+                    self.storage
+                        .copy_content_object(&paths, hash, &mut writer)?;
+                    writer.flush().context("Failed to write content for git")?;
+                    staged.push(staged_path);
+                }
+                for (hash, blob) in new_hashes.iter().zip(repo.write_blobs(&staged)?) {
+                    blobs.insert(hash.to_string(), blob);
+                }
+            }
+
+            let files: Vec<GitFile> = tree_refs
+                .into_iter()
+                .map(|(path, file_ref)| GitFile {
+                    path,
+                    blob: blobs[&file_ref.hash].clone(),
+                    executable: file_ref.permissions.is_some_and(|m| m & 0o111 != 0),
+                    symlink: file_ref.kind == EntryKind::Symlink,
+                })
+                .collect();
+
+            let tree = repo.write_tree(&files)?;
+            let commit = repo.commit_tree(
+                &tree,
+                &parent_commits,
+                &git::commit_message(&checkpoint),
+                checkpoint.timestamp,
+            )?;
+            let ref_name = GitRepo::checkpoint_ref(&self.session_id, &id);
+            repo.update_ref(&ref_name, &commit)?;
+
+            commits.insert(id.clone(), commit.clone());
+            exports.push(GitExport {
+                checkpoint_id: id,
+                commit,
+                ref_name,
+            });
+        }
+
+        Ok(exports)
+    }
+
+    /// Create a checkpoint holding the files of a git commit
+    ///
+    /// The checkpoint becomes a child of the current checkpoint. The project
+    /// on disk is left alone, and so is the current checkpoint unless
+    /// `make_current` is set; restore the new checkpoint to bring the
+    /// commit's files into the project. Files matching the project's ignore
+    /// rules are left out and files over the session's size limit are
+    /// skipped, both without being read. Submodules are skipped with a
+    /// warning.
+    pub async fn seed_from_git(
+        &self,
+        rev: &str,
+        description: Option<String>,
+        make_current: bool,
+    ) -> Result<CheckpointResult> {
+        let repo = GitRepo::open(&self.project_path)?;
+        let commit = repo
+            .resolve_commit(rev)?
+            .with_context(|| format!("{} is not a commit", rev))?;
+        let commit_message = repo.commit_message(&commit)?;
+        let commit_files = repo.list_commit_files(&commit)?;
+
+        let (messages, message_index) = {
+            let messages = self.current_messages.read().await;
+            (messages.join("\n"), messages.len().saturating_sub(1))
+        };
+        // Held until the new checkpoint is saved, so no other checkpoint can
+        // change the timeline in between
+        let mut timeline = self.timeline.write().await;
+        let matcher = ProjectWalker::new(&self.project_path).ignore_matcher();
+        let mut included = Vec::new();
+        let mut skipped_files = Vec::new();
+        let mut ignored = 0;
+        for (file, size) in commit_files.files {
+            if matcher.is_ignored(&self.project_path.join(&file.path), false) {
+                ignored += 1;
+            } else if !file.symlink && size > timeline.max_file_size {
+                skipped_files.push(SkippedFile {
+                    path: file.path,
+                    size,
+                    reason: SkipReason::TooLarge,
+                });
+            } else {
+                included.push(file);
+            }
+        }
+
+        let checkpoint_id = storage::CheckpointStorage::generate_checkpoint_id();
+
+        // The commit is the whole tree, so files it lacks are simply left out
+        let parent_checkpoint_id = timeline.current_checkpoint_id.clone().or_else(|| {
+            timeline
+                .root_node
+                .as_ref()
+                .map(|root| root.checkpoint.id.clone())
+        });
+
+        let subject = commit_message.lines().next().unwrap_or_default();
+        let description = description
+            .unwrap_or_else(|| format!("Seeded from git {}: {}", short_id(&commit), subject));
+        let checkpoint = Checkpoint {
+            id: checkpoint_id.clone(),
+            session_id: self.session_id.clone(),
+            project_id: self.project_id.clone(),
+            message_index,
+            timestamp: Utc::now(),
+            description: Some(description),
+            parent_checkpoint_id,
+            merge_parent_checkpoint_id: None,
+            tags: Vec::new(),
+            full_tree: true,
+            metadata: CheckpointMetadata {
+                total_tokens: 0,
+                model_used: String::new(),
+                user_prompt: commit_message.clone(),
+                file_changes: included.len(),
+                snapshot_size: 0,
+                skipped_files: skipped_files.clone(),
+            },
+        };
+
+        // Blobs are read one at a time as they are stored
+        let mut blobs = repo.blob_reader()?;
+        let file_snapshots = included.into_iter().map(|file| {
+            let content = blobs
+                .read(&file.blob)
+                .with_context(|| format!("Failed to read {}", file.path.display()))?;
+            Ok(FileSnapshot {
+                checkpoint_id: checkpoint_id.clone(),
+                file_path: file.path,
+                hash: storage::CheckpointStorage::calculate_file_hash(&content),
+                size: content.len() as u64,
+                content,
+                is_deleted: false,
+                permissions: (cfg!(unix) && !file.symlink).then_some(if file.executable {
+                    0o100755
+                } else {
+                    0o100644
+                }),
+                kind: if file.symlink {
+                    EntryKind::Symlink
+                } else {
+                    EntryKind::File
+                },
+                source: ContentSource::Inline,
+            })
+        });
+
+        let mut result = self.storage.save_checkpoint_streaming(
+            &self.project_id,
+            &self.session_id,
+            &checkpoint,
+            file_snapshots,
+            Vec::new(),
+            &messages,
+        )?;
+        result
+            .warnings
+            .extend(skipped_files.iter().map(skipped_file_warning));
+        if ignored > 0 {
+            result.warnings.push(format!(
+                "Skipped {} files matching the project's ignore rules",
+                ignored
+            ));
+        }
+        result.warnings.extend(
+            commit_files
+                .skipped
+                .iter()
+                .map(|p| format!("Skipped submodule {}", p.display())),
+        );
+
+        // Saving makes the new checkpoint current, but the project still holds the old files
+        let paths =
+            CheckpointPaths::new(&self.storage.claude_dir, &self.project_id, &self.session_id);
+        let mut updated_timeline = self.storage.load_timeline(&paths.timeline_file)?;
+        if !make_current {
+            updated_timeline.current_checkpoint_id = timeline.current_checkpoint_id.take();
+            self.storage
+                .save_timeline(&paths.timeline_file, &updated_timeline)?;
+        }
+        *timeline = updated_timeline;
+
+        Ok(result)
+    }
+
+    /// Resolve a checkpoint ID, tag or unique ID prefix to a checkpoint ID
+    pub async fn resolve_checkpoint(&self, reference: &str) -> Result<String> {
+        self.timeline.read().await.resolve_checkpoint(reference)
+    }
+
+    /// Attach a tag to a checkpoint
+    ///
+    /// Tags are unique within a session; tagging a second checkpoint with the
+    /// same name fails.
+    pub async fn tag_checkpoint(&self, reference: &str, tag: &str) -> Result<Checkpoint> {
+        let tag = tag.trim();
+        validate_tag(tag)?;
+
+        let mut timeline = self.timeline.write().await;
+        let checkpoint_id = timeline.resolve_checkpoint(reference)?;
+        if let Some(existing) = timeline.tags().into_iter().find(|t| t.tag == tag) {
+            if existing.checkpoint_id == checkpoint_id {
+                return timeline
+                    .find_checkpoint(&checkpoint_id)
+                    .map(|node| node.checkpoint.clone())
+                    .context("Checkpoint not found");
+            }
+            anyhow::bail!(
+                "Tag {} already refers to checkpoint {}",
+                tag,
+                short_id(&existing.checkpoint_id)
+            );
+        }
+
+        let node = timeline
+            .find_checkpoint_mut(&checkpoint_id)
+            .context("Checkpoint not found")?;
+        node.checkpoint.tags.push(tag.to_string());
+        node.checkpoint.tags.sort();
+        let checkpoint = node.checkpoint.clone();
+
+        self.save_checkpoint_tags(&timeline, &checkpoint)?;
+        Ok(checkpoint)
+    }
+
+    /// Remove a tag from whichever checkpoint has it
+    pub async fn untag_checkpoint(&self, tag: &str) -> Result<Checkpoint> {
+        let mut timeline = self.timeline.write().await;
+        let checkpoint_id = timeline
+            .tags()
+            .into_iter()
+            .find(|t| t.tag == tag)
+            .map(|t| t.checkpoint_id)
+            .with_context(|| format!("No checkpoint is tagged {}", tag))?;
+
+        let node = timeline
+            .find_checkpoint_mut(&checkpoint_id)
+            .context("Checkpoint not found")?;
+        node.checkpoint.tags.retain(|t| t != tag);
+        let checkpoint = node.checkpoint.clone();
+
+        self.save_checkpoint_tags(&timeline, &checkpoint)?;
+        Ok(checkpoint)
+    }
+
+    /// List tags, optionally only those containing `query`
+    pub async fn list_tags(&self, query: Option<&str>) -> Vec<CheckpointTag> {
+        let query = query.map(str::to_lowercase);
+        self.timeline
+            .read()
             .await
+            .tags()
+            .into_iter()
+            .filter(|t| {
+                query
+                    .as_deref()
+                    .is_none_or(|q| t.tag.to_lowercase().contains(q))
+            })
+            .collect()
+    }
+
+    fn save_checkpoint_tags(
+        &self,
+        timeline: &SessionTimeline,
+        checkpoint: &Checkpoint,
+    ) -> Result<()> {
+        let paths =
+            CheckpointPaths::new(&self.storage.claude_dir, &self.project_id, &self.session_id);
+        self.storage
+            .update_checkpoint_metadata(&paths, checkpoint)?;
+        self.storage.save_timeline(&paths.timeline_file, timeline)
+    }
+
+    /// Get the current timeline
+    pub async fn get_timeline(&self) -> SessionTimeline {
+        self.timeline.read().await.clone()
+    }
+
+    /// List all checkpoints
+    pub async fn list_checkpoints(&self) -> Vec<Checkpoint> {
+        let timeline = self.timeline.read().await;
+        let mut checkpoints = Vec::new();
+
+        if let Some(root) = &timeline.root_node {
+            Self::collect_checkpoints_from_node(root, &mut checkpoints);
+        }
+
+        checkpoints
+    }
+
+    /// Recursively collect checkpoints from timeline tree
+    fn collect_checkpoints_from_node(
+        node: &super::TimelineNode,
+        checkpoints: &mut Vec<Checkpoint>,
+    ) {
+        checkpoints.push(node.checkpoint.clone());
+        for child in &node.children {
+            Self::collect_checkpoints_from_node(child, checkpoints);
+        }
+    }
+
+    /// The project's `Smart` rules, falling back to the defaults if they cannot be loaded
+    fn compiled_smart_rules(&self) -> CompiledSmartRules {
+        self.storage
+            .load_smart_rules(&self.project_id)
+            .and_then(|rules| rules.compile())
+            .unwrap_or_else(|e| {
+                log::warn!("Using default smart rules: {}", e);
+                SmartRules::default()
+                    .compile()
+                    .expect("default smart rules compile")
+            })
     }
 
     /// Check if auto-checkpoint should be triggered
@@ -716,21 +1989,20 @@ impl CheckpointManager {
                 }
             }
             CheckpointStrategy::Smart => {
-                // Smart strategy: checkpoint after destructive operations
+                // Smart strategy: checkpoint after tool calls the project's rules select
                 if let Ok(msg) = serde_json::from_str::<serde_json::Value>(message) {
                     if let Some(content) = msg
                         .get("message")
                         .and_then(|m| m.get("content"))
                         .and_then(|c| c.as_array())
                     {
+                        let rules = self.compiled_smart_rules();
                         content.iter().any(|item| {
                             if item.get("type").and_then(|t| t.as_str()) == Some("tool_use") {
                                 let tool_name =
                                     item.get("name").and_then(|n| n.as_str()).unwrap_or("");
-                                matches!(
-                                    tool_name.to_lowercase().as_str(),
-                                    "write" | "edit" | "multiedit" | "bash" | "rm" | "delete"
-                                )
+                                let input = item.get("input").unwrap_or(&serde_json::Value::Null);
+                                rules.should_checkpoint(tool_name, input, &self.project_path)
                             } else {
                                 false
                             }
@@ -745,6 +2017,166 @@ impl CheckpointManager {
         }
     }
 
+    /// Track a message and take an automatic checkpoint if the schedule allows
+    pub async fn track_message_with_auto_checkpoint(
+        &self,
+        message: String,
+    ) -> Result<Option<CheckpointResult>> {
+        let requested = self.should_auto_checkpoint(&message).await;
+        let end_of_turn = scheduler::is_end_of_turn(&message);
+        self.track_message(message).await?;
+
+        if requested {
+            self.scheduler.write().await.request();
+        }
+        self.run_auto_checkpoint(end_of_turn).await
+    }
+
+    /// Handle the end of an assistant turn
+    ///
+    /// `turn_messages` are the turn's session messages that have not been
+    /// tracked yet. Each is tracked and may request a checkpoint, as if it
+    /// had been tracked live. `message` is the turn's prompt or a session
+    /// message, and may itself request a checkpoint; a plain-text prompt
+    /// counts as a user message.
+    pub async fn check_auto_checkpoint(
+        &self,
+        turn_messages: Vec<String>,
+        message: &str,
+    ) -> Result<Option<CheckpointResult>> {
+        let is_plain_prompt = serde_json::from_str::<serde_json::Value>(message).is_err()
+            && matches!(
+                self.timeline.read().await.checkpoint_strategy,
+                CheckpointStrategy::PerPrompt
+            );
+        let mut requested = self.timeline.read().await.auto_checkpoint_enabled
+            && (is_plain_prompt || self.should_auto_checkpoint(message).await);
+        for turn_message in turn_messages {
+            requested |= self.should_auto_checkpoint(&turn_message).await;
+            self.track_message(turn_message).await?;
+        }
+
+        if requested {
+            self.scheduler.write().await.request();
+        }
+        self.run_auto_checkpoint(true).await
+    }
+
+    /// Take a pending automatic checkpoint if it is due and enough has changed
+    ///
+    /// Nothing is taken inside the debounce window, or mid-turn when the
+    /// schedule waits for the end of the turn. A pending request is dropped
+    /// when no file differs from the current checkpoint, and keeps waiting
+    /// while the changed files add up to less than the byte threshold.
+    pub async fn run_auto_checkpoint(&self, end_of_turn: bool) -> Result<Option<CheckpointResult>> {
+        let schedule = self.timeline.read().await.auto_checkpoint_schedule.clone();
+        if !self
+            .scheduler
+            .read()
+            .await
+            .is_due(&schedule, end_of_turn, Instant::now())
+        {
+            return Ok(None);
+        }
+
+        let Some(changed_bytes) = self.changed_bytes_since_current().await? else {
+            log::debug!("Skipping automatic checkpoint: no file changed");
+            self.scheduler.write().await.cancel();
+            return Ok(None);
+        };
+        if changed_bytes < schedule.min_changed_bytes {
+            log::debug!(
+                "Deferring automatic checkpoint: {} of {} bytes changed",
+                changed_bytes,
+                schedule.min_changed_bytes
+            );
+            return Ok(None);
+        }
+
+        let result = self.create_checkpoint(None, None).await?;
+        self.scheduler
+            .write()
+            .await
+            .record_checkpoint(Instant::now());
+        Ok(Some(result))
+    }
+
+    /// Total size of the files whose content differs from the current
+    /// checkpoint, or `None` if every file matches it
+    ///
+    /// Deleted files count with their size at the checkpoint.
+    async fn changed_bytes_since_current(&self) -> Result<Option<u64>> {
+        self.refresh_file_tracker().await;
+
+        let timeline = self.timeline.read().await.clone();
+        let saved = match &timeline.current_checkpoint_id {
+            Some(id) => self.storage.load_checkpoint_tree_refs(
+                &self.project_id,
+                &self.session_id,
+                &timeline,
+                id,
+            )?,
+            None => BTreeMap::new(),
+        };
+
+        let tracker = self.file_tracker.read().await;
+        let mut changed = false;
+        let mut bytes = 0;
+        for (path, state) in &tracker.tracked_files {
+            if !state.is_modified {
+                continue;
+            }
+            match (state.exists, saved.get(path)) {
+                (true, Some(saved))
+                    if saved.hash == state.last_hash && saved.kind == state.kind =>
+                {
+                    continue
+                }
+                (false, None) => continue,
+                (true, _) => {
+                    bytes += fs::symlink_metadata(self.project_path.join(path))
+                        .map(|m| m.len())
+                        .unwrap_or(0)
+                }
+                (false, Some(saved)) => bytes += saved.size,
+            }
+            changed = true;
+        }
+
+        Ok(changed.then_some(bytes))
+    }
+
+    /// Set the size above which files are left out of checkpoints
+    pub async fn update_max_file_size(&self, max_file_size: u64) -> Result<()> {
+        let mut timeline = self.timeline.write().await;
+        timeline.max_file_size = max_file_size;
+        let paths =
+            CheckpointPaths::new(&self.storage.claude_dir, &self.project_id, &self.session_id);
+        self.storage.save_timeline(&paths.timeline_file, &timeline)
+    }
+
+    /// Set when the session's checkpoint writes are flushed to stable storage
+    pub async fn update_fsync_policy(&self, fsync_policy: FsyncPolicy) -> Result<()> {
+        let mut timeline = self.timeline.write().await;
+        timeline.fsync_policy = fsync_policy;
+        self.storage.set_fsync_policy(fsync_policy);
+        let paths =
+            CheckpointPaths::new(&self.storage.claude_dir, &self.project_id, &self.session_id);
+        self.storage.save_timeline(&paths.timeline_file, &timeline)
+    }
+
+    /// Replace the session's automatic checkpoint schedule
+    pub async fn update_auto_checkpoint_schedule(
+        &self,
+        schedule: AutoCheckpointSchedule,
+    ) -> Result<()> {
+        let mut timeline = self.timeline.write().await;
+        timeline.auto_checkpoint_schedule = schedule;
+        let paths =
+            CheckpointPaths::new(&self.storage.claude_dir, &self.project_id, &self.session_id);
+        self.storage.save_timeline(&paths.timeline_file, &timeline)
+    }
+
     /// Update checkpoint settings
     pub async fn update_settings(
         &self,
@@ -785,3 +2217,519 @@ impl CheckpointManager {
             .max()
     }
 }
+
+/// Read the first `count` messages of a session's JSONL transcript
+///
+/// Falls back to the messages stored with the current checkpoint when the
+/// transcript cannot be read.
+fn load_session_messages(
+    storage: &CheckpointStorage,
+    project_id: &str,
+    session_id: &str,
+    timeline: &SessionTimeline,
+    count: usize,
+) -> Vec<String> {
+    if count == 0 {
+        return Vec::new();
+    }
+
+    let session_file = CheckpointPaths::session_file(&storage.claude_dir, project_id, session_id);
+    match fs::read_to_string(&session_file) {
+        Ok(transcript) => transcript.lines().take(count).map(str::to_string).collect(),
+        Err(e) => {
+            log::warn!(
+                "Failed to read session transcript {}: {}",
+                session_file.display(),
+                e
+            );
+            timeline
+                .current_checkpoint_id
+                .as_deref()
+                .and_then(|id| storage.load_checkpoint(project_id, session_id, id).ok())
+                .map(|(_, _, messages)| messages.lines().map(str::to_string).collect())
+                .unwrap_or_default()
+        }
+    }
+}
+
+/// Check that a tag can be used in place of a checkpoint ID
+fn validate_tag(tag: &str) -> Result<()> {
+    if tag.is_empty() || tag.len() > 64 {
+        anyhow::bail!("Tags must be between 1 and 64 characters");
+    }
+    if !tag
+        .chars()
+        .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.' | '/'))
+    {
+        anyhow::bail!("Tags may only contain letters, digits, '-', '_', '.' and '/'");
+    }
+    Ok(())
+}
+
+/// First eight characters of an ID, for descriptions and logs
+pub fn short_id(id: &str) -> &str {
+    match id.char_indices().nth(8) {
+        Some((end, _)) => &id[..end],
+        None => id,
+    }
+}
+
+/// Warning shown for a file left out of a checkpoint
+fn skipped_file_warning(file: &SkippedFile) -> String {
+    match file.reason {
+        SkipReason::TooLarge => format!(
+            "Skipped {} (too large, {} bytes)",
+            file.path.display(),
+            file.size
+        ),
+    }
+}
+
+/// Whether the entry on disk already has a snapshot's kind and content
+fn entry_matches(path: &Path, kind: EntryKind, snapshot: &FileSnapshot) -> bool {
+    kind == snapshot.kind
+        && (kind == EntryKind::Directory
+            || entry_hash(path, kind).is_ok_and(|hash| hash == snapshot.hash))
+}
+
+/// Mode bits to record for an entry; symlinks have none of their own
+fn entry_permissions(kind: EntryKind, metadata: &fs::Metadata) -> Option<u32> {
+    match kind {
+        EntryKind::Symlink => None,
+        _ => file_permissions(metadata),
+    }
+}
+
+/// Paths of the empty directories among a set of snapshots
+fn directory_paths<'a>(snapshots: impl Iterator<Item = &'a FileSnapshot>) -> HashSet<&'a Path> {
+    snapshots
+        .filter(|s| s.kind == EntryKind::Directory && !s.is_deleted)
+        .map(|s| s.file_path.as_path())
+        .collect()
+}
+
+/// Delete a file, symlink or empty directory
+fn remove_entry(path: &Path) -> std::io::Result<()> {
+    match entry_metadata(path) {
+        Some((EntryKind::Directory, _)) => fs::remove_dir(path),
+        _ => fs::remove_file(path),
+    }
+}
+
+/// Create a symlink from its recorded target; only supported on Unix
+fn create_symlink(target: &[u8], path: &Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        std::os::unix::fs::symlink(std::ffi::OsStr::from_bytes(target), path)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (target, path);
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "Symlinks can only be restored on Unix",
+        ))
+    }
+}
+
+/// Unix mode bits of a file, if the platform has them
+fn file_permissions(metadata: &fs::Metadata) -> Option<u32> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        Some(metadata.permissions().mode())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        None
+    }
+}
+
+/// Apply Unix mode bits to a file; a no-op on other platforms
+fn set_file_permissions(path: &Path, mode: u32) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (path, mode);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    async fn open_manager(temp_dir: &TempDir) -> CheckpointManager {
+        let project_path = temp_dir.path().join("project");
+        fs::create_dir_all(&project_path).unwrap();
+        CheckpointManager::new(
+            "proj".to_string(),
+            "sess".to_string(),
+            project_path,
+            temp_dir.path().join("claude"),
+        )
+        .await
+        .unwrap()
+    }
+
+    fn tracked_paths(tracker: &FileTracker) -> Vec<String> {
+        let mut paths: Vec<String> = tracker
+            .tracked_files
+            .iter()
+            .filter(|(_, state)| state.is_modified)
+            .map(|(path, _)| path.to_string_lossy().into_owned())
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    #[tokio::test]
+    async fn test_bash_changes_tracked_from_live_messages_only() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = open_manager(&temp_dir).await;
+        let project = manager.project_path().to_path_buf();
+        fs::write(project.join("kept.txt"), b"kept").unwrap();
+        fs::write(project.join("edited.txt"), b"one").unwrap();
+        manager.create_checkpoint(None, None).await.unwrap();
+
+        let tool_use = r#"{"type":"assistant","message":{"content":[{"type":"tool_use","id":"t1","name":"Bash","input":{"command":"make"}}]}}"#;
+        let tool_result = r#"{"type":"user","message":{"content":[{"type":"tool_result","tool_use_id":"t1","content":"ok"}]}}"#;
+
+        // Replaying a command after it ran has nothing to compare with
+        manager.track_message(tool_use.to_string()).await.unwrap();
+        fs::write(project.join("edited.txt"), b"two").unwrap();
+        manager
+            .track_message(tool_result.to_string())
+            .await
+            .unwrap();
+        assert!(manager.bash_manifests.read().await.is_empty());
+        assert!(tracked_paths(&*manager.file_tracker.read().await).is_empty());
+        assert_eq!(manager.message_count().await, 2);
+
+        manager.track_live_message(tool_use).await.unwrap();
+        fs::write(project.join("created.txt"), b"new").unwrap();
+        fs::write(project.join("edited.txt"), b"three").unwrap();
+        manager.track_live_message(tool_result).await.unwrap();
+        assert_eq!(
+            tracked_paths(&*manager.file_tracker.read().await),
+            vec!["created.txt".to_string(), "edited.txt".to_string()]
+        );
+        assert_eq!(manager.message_count().await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_tags_resolve_and_persist() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = open_manager(&temp_dir).await;
+        fs::write(manager.project_path().join("a.txt"), b"one").unwrap();
+        let first = manager
+            .create_checkpoint(None, None)
+            .await
+            .unwrap()
+            .checkpoint;
+        fs::write(manager.project_path().join("a.txt"), b"two").unwrap();
+        let second = manager
+            .create_checkpoint(None, None)
+            .await
+            .unwrap()
+            .checkpoint;
+
+        let tagged = manager
+            .tag_checkpoint(&first.id[..8], " v1.0 ")
+            .await
+            .unwrap();
+        assert_eq!(tagged.id, first.id);
+        assert_eq!(tagged.tags, vec!["v1.0".to_string()]);
+        manager
+            .tag_checkpoint(&second.id, "release/v2")
+            .await
+            .unwrap();
+
+        // Tagging the same checkpoint again is a no-op, another one an error
+        manager.tag_checkpoint(&first.id, "v1.0").await.unwrap();
+        assert!(manager.tag_checkpoint(&second.id, "v1.0").await.is_err());
+        assert!(manager
+            .tag_checkpoint(&second.id, "has space")
+            .await
+            .is_err());
+
+        assert_eq!(manager.resolve_checkpoint("v1.0").await.unwrap(), first.id);
+        let tags: Vec<String> = manager
+            .list_tags(Some("V1"))
+            .await
+            .into_iter()
+            .map(|t| t.tag)
+            .collect();
+        assert_eq!(tags, vec!["v1.0".to_string()]);
+
+        // Tags survive reloading the session and are kept in checkpoint metadata
+        let reloaded = open_manager(&temp_dir).await;
+        assert_eq!(
+            reloaded.resolve_checkpoint("release/v2").await.unwrap(),
+            second.id
+        );
+        let untagged = reloaded.untag_checkpoint("v1.0").await.unwrap();
+        assert!(untagged.tags.is_empty());
+        assert!(reloaded.resolve_checkpoint("v1.0").await.is_err());
+        let (stored, _, _) = reloaded
+            .storage
+            .load_checkpoint("proj", "sess", &first.id)
+            .unwrap();
+        assert!(stored.tags.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_restore_files_restores_only_selected_paths() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = open_manager(&temp_dir).await;
+        let project = manager.project_path().to_path_buf();
+        fs::create_dir_all(project.join("src")).unwrap();
+        fs::write(project.join("src/lib.rs"), b"lib one").unwrap();
+        fs::write(project.join("src/main.rs"), b"main one").unwrap();
+        fs::write(project.join("notes.txt"), b"notes one").unwrap();
+        let first = manager
+            .create_checkpoint(None, None)
+            .await
+            .unwrap()
+            .checkpoint;
+
+        fs::write(project.join("src/lib.rs"), b"lib two").unwrap();
+        fs::write(project.join("notes.txt"), b"notes two").unwrap();
+        fs::write(project.join("src/extra.rs"), b"extra").unwrap();
+        let second = manager
+            .create_checkpoint(None, None)
+            .await
+            .unwrap()
+            .checkpoint;
+
+        assert!(manager
+            .restore_files(&first.id, &["missing/*".to_string()])
+            .await
+            .is_err());
+
+        let result = manager
+            .restore_files(&first.id, &["src".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(result.files_processed, 2);
+        assert_eq!(fs::read(project.join("src/lib.rs")).unwrap(), b"lib one");
+        assert_eq!(fs::read(project.join("src/main.rs")).unwrap(), b"main one");
+        assert!(!project.join("src/extra.rs").exists());
+        assert_eq!(fs::read(project.join("notes.txt")).unwrap(), b"notes two");
+
+        // The current checkpoint stays put, with the restored files modified since
+        assert_eq!(
+            manager.get_timeline().await.current_checkpoint_id,
+            Some(second.id)
+        );
+        assert_eq!(
+            tracked_paths(&*manager.file_tracker.read().await),
+            vec!["src/extra.rs".to_string(), "src/lib.rs".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_end_of_turn_checks_tool_uses_of_the_turn() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = open_manager(&temp_dir).await;
+        manager
+            .update_settings(true, CheckpointStrategy::PerToolUse)
+            .await
+            .unwrap();
+        fs::write(manager.project_path().join("a.txt"), b"one").unwrap();
+        manager.create_checkpoint(None, None).await.unwrap();
+        fs::write(manager.project_path().join("a.txt"), b"two").unwrap();
+
+        // The prompt alone never asks for a checkpoint under this strategy
+        let result = manager
+            .check_auto_checkpoint(Vec::new(), "Edit a.txt")
+            .await
+            .unwrap();
+        assert!(result.is_none());
+
+        let turn = vec![
+            r#"{"type":"user","message":{"content":"Edit a.txt"}}"#.to_string(),
+            r#"{"type":"assistant","message":{"content":[{"type":"tool_use","id":"t1","name":"Edit","input":{"file_path":"a.txt"}}]}}"#.to_string(),
+        ];
+        let result = manager
+            .check_auto_checkpoint(turn, "Edit a.txt")
+            .await
+            .unwrap();
+        assert!(result.is_some());
+        assert_eq!(manager.message_count().await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_restore_persists_current_checkpoint_and_tracker() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = open_manager(&temp_dir).await;
+        let project = manager.project_path().to_path_buf();
+        fs::write(project.join("a.txt"), b"one").unwrap();
+        let first = manager
+            .create_checkpoint(None, None)
+            .await
+            .unwrap()
+            .checkpoint;
+        fs::write(project.join("a.txt"), b"two").unwrap();
+        let second = manager
+            .create_checkpoint(None, None)
+            .await
+            .unwrap()
+            .checkpoint;
+
+        manager.restore_checkpoint(&first.id).await.unwrap();
+        assert_eq!(fs::read(project.join("a.txt")).unwrap(), b"one");
+        drop(manager);
+
+        let reloaded = open_manager(&temp_dir).await;
+        assert_eq!(
+            reloaded.get_timeline().await.current_checkpoint_id,
+            Some(first.id.clone())
+        );
+        // The saved tracker matches the restored files, so nothing is modified
+        let tracker = reloaded.file_tracker.read().await;
+        let state = &tracker.tracked_files[Path::new("a.txt")];
+        assert_eq!(
+            state.last_hash,
+            storage::CheckpointStorage::calculate_file_hash(b"one")
+        );
+        assert!(tracked_paths(&tracker).is_empty());
+        drop(tracker);
+
+        // New checkpoints branch off the restored one
+        fs::write(project.join("a.txt"), b"three").unwrap();
+        let third = reloaded
+            .create_checkpoint(None, None)
+            .await
+            .unwrap()
+            .checkpoint;
+        assert_eq!(third.parent_checkpoint_id, Some(first.id));
+        assert_ne!(third.parent_checkpoint_id, Some(second.id));
+    }
+
+    #[tokio::test]
+    async fn test_tracker_and_messages_survive_reload() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = open_manager(&temp_dir).await;
+        let project = manager.project_path().to_path_buf();
+        let transcript = [
+            r#"{"type":"user","message":{"content":"first"}}"#,
+            r#"{"type":"assistant","message":{"content":"done"}}"#,
+            r#"{"type":"user","message":{"content":"second"}}"#,
+        ];
+        let session_file =
+            CheckpointPaths::session_file(&temp_dir.path().join("claude"), "proj", "sess");
+        fs::create_dir_all(session_file.parent().unwrap()).unwrap();
+        fs::write(&session_file, transcript.join("\n")).unwrap();
+
+        fs::write(project.join("a.txt"), b"a").unwrap();
+        fs::write(project.join("b.txt"), b"b").unwrap();
+        for message in &transcript[..2] {
+            manager.track_message(message.to_string()).await.unwrap();
+        }
+        manager.create_checkpoint(None, None).await.unwrap();
+        drop(manager);
+
+        // Messages are rebuilt from the transcript up to the saved cursor
+        let reloaded = open_manager(&temp_dir).await;
+        assert_eq!(
+            *reloaded.current_messages.read().await,
+            transcript[..2].to_vec()
+        );
+
+        // Files unchanged since the checkpoint are not stored again
+        fs::write(project.join("b.txt"), b"b2").unwrap();
+        let result = reloaded.create_checkpoint(None, None).await.unwrap();
+        assert_eq!(result.checkpoint.metadata.file_changes, 1);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_symlinks_and_empty_directories_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = open_manager(&temp_dir).await;
+        let project = manager.project_path().to_path_buf();
+        fs::write(project.join("a.sh"), b"#!/bin/sh").unwrap();
+        std::os::unix::fs::symlink("a.sh", project.join("link")).unwrap();
+        std::os::unix::fs::symlink("missing", project.join("broken")).unwrap();
+        fs::create_dir_all(project.join("empty/inner")).unwrap();
+        let first = manager
+            .create_checkpoint(None, None)
+            .await
+            .unwrap()
+            .checkpoint;
+
+        let (_, snapshots, _) = manager
+            .storage
+            .load_checkpoint("proj", "sess", &first.id)
+            .unwrap();
+        let mut kinds: Vec<(String, EntryKind)> = snapshots
+            .iter()
+            .map(|s| (s.file_path.to_string_lossy().into_owned(), s.kind))
+            .collect();
+        kinds.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            kinds,
+            vec![
+                ("a.sh".to_string(), EntryKind::File),
+                ("broken".to_string(), EntryKind::Symlink),
+                ("empty/inner".to_string(), EntryKind::Directory),
+                ("link".to_string(), EntryKind::Symlink),
+            ]
+        );
+
+        // Replace the link with a file, drop the others and fill a new directory
+        fs::remove_file(project.join("link")).unwrap();
+        fs::write(project.join("link"), b"not a link").unwrap();
+        fs::remove_file(project.join("broken")).unwrap();
+        fs::remove_dir_all(project.join("empty")).unwrap();
+        fs::create_dir_all(project.join("other_empty")).unwrap();
+        let second = manager
+            .create_checkpoint(None, None)
+            .await
+            .unwrap()
+            .checkpoint;
+
+        manager.restore_checkpoint(&first.id).await.unwrap();
+        assert_eq!(
+            fs::read_link(project.join("link")).unwrap(),
+            PathBuf::from("a.sh")
+        );
+        assert_eq!(
+            fs::read_link(project.join("broken")).unwrap(),
+            PathBuf::from("missing")
+        );
+        assert!(project.join("empty/inner").is_dir());
+        assert!(!project.join("other_empty").exists());
+        assert_eq!(fs::read(project.join("a.sh")).unwrap(), b"#!/bin/sh");
+
+        manager.restore_checkpoint(&second.id).await.unwrap();
+        let link = fs::symlink_metadata(project.join("link")).unwrap();
+        assert!(!link.file_type().is_symlink());
+        assert_eq!(fs::read(project.join("link")).unwrap(), b"not a link");
+        assert!(fs::symlink_metadata(project.join("broken")).is_err());
+        assert!(!project.join("empty").exists());
+        assert!(project.join("other_empty").is_dir());
+
+        // Nothing is left to restore once the round trip is done
+        let plan = manager.plan_restore(&second.id).await.unwrap();
+        assert!(plan.files_to_create.is_empty());
+        assert!(plan.files_to_delete.is_empty());
+        assert!(plan.files_to_overwrite.is_empty());
+    }
+
+    #[test]
+    fn test_short_id_cuts_on_character_boundaries() {
+        assert_eq!(short_id("0123456789abcdef"), "01234567");
+        assert_eq!(short_id("abc"), "abc");
+        assert_eq!(short_id("ééééééééé"), "éééééééé");
+        assert_eq!(short_id("a€€€€€€€€"), "a€€€€€€€");
+    }
+}
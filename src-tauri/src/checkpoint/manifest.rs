@@ -0,0 +1,306 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use super::{storage::CheckpointStorage, walker::ProjectWalker, EntryKind};
+
+/// Recorded state of one entry in a working tree manifest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    /// File, symlink or empty directory
+    pub kind: EntryKind,
+    /// Last modification time, if the platform reports one
+    pub modified: Option<SystemTime>,
+    /// Size in bytes
+    pub size: u64,
+    /// SHA-256 hash of the content, or empty for a file over the size limit
+    pub hash: String,
+}
+
+/// Snapshot of the size, mtime and hash of every entry in a project
+#[derive(Debug, Clone, Default)]
+pub struct WorkingTreeManifest {
+    entries: HashMap<PathBuf, ManifestEntry>,
+}
+
+/// Files that differ between two manifests
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ManifestChanges {
+    pub created: Vec<PathBuf>,
+    pub modified: Vec<PathBuf>,
+    pub deleted: Vec<PathBuf>,
+}
+
+impl WorkingTreeManifest {
+    /// Manifest of already known file states, to seed the first `capture`
+    pub fn from_entries(entries: HashMap<PathBuf, ManifestEntry>) -> Self {
+        Self { entries }
+    }
+
+    /// Record the state of every entry the project walker visits
+    ///
+    /// Files whose size and mtime match `previous` reuse its hash instead of
+    /// being read again, and files over `max_file_size` are not read at all.
+    pub fn capture(
+        root: &Path,
+        previous: Option<&WorkingTreeManifest>,
+        max_file_size: u64,
+    ) -> Result<Self> {
+        let mut entries = HashMap::new();
+
+        for (rel, _) in ProjectWalker::new(root).collect_entries()? {
+            let full_path = root.join(&rel);
+            let Some((kind, metadata)) = entry_metadata(&full_path) else {
+                // Removed while walking
+                continue;
+            };
+            let modified = metadata.modified().ok();
+            let size = if kind == EntryKind::Directory {
+                0
+            } else {
+                metadata.len()
+            };
+
+            let cached = previous.and_then(|p| p.entries.get(&rel)).filter(|e| {
+                e.kind == kind && e.size == size && e.modified.is_some() && e.modified == modified
+            });
+            let hash = match cached {
+                Some(entry) if kind == EntryKind::File => entry.hash.clone(),
+                _ if kind == EntryKind::File && size > max_file_size => String::new(),
+                _ => match entry_hash(&full_path, kind) {
+                    Ok(hash) => hash,
+                    Err(e) => {
+                        log::warn!("Failed to read {} for manifest: {}", rel.display(), e);
+                        continue;
+                    }
+                },
+            };
+
+            entries.insert(
+                rel,
+                ManifestEntry {
+                    kind,
+                    modified,
+                    size,
+                    hash,
+                },
+            );
+        }
+
+        Ok(Self { entries })
+    }
+
+    /// Files created, modified or deleted between this manifest and `after`
+    ///
+    /// An entry counts as modified when its kind or content hash changed.
+    /// Files over the size limit have no hash, so any change in their size
+    /// or mtime counts instead.
+    pub fn changes_since(&self, after: &WorkingTreeManifest) -> ManifestChanges {
+        let mut changes = ManifestChanges::default();
+
+        for (path, entry) in &after.entries {
+            match self.entries.get(path) {
+                None => changes.created.push(path.clone()),
+                Some(before) if before.differs_from(entry) => changes.modified.push(path.clone()),
+                Some(_) => {}
+            }
+        }
+        for path in self.entries.keys() {
+            if !after.entries.contains_key(path) {
+                changes.deleted.push(path.clone());
+            }
+        }
+
+        changes.created.sort();
+        changes.modified.sort();
+        changes.deleted.sort();
+        changes
+    }
+}
+
+impl ManifestEntry {
+    fn differs_from(&self, other: &ManifestEntry) -> bool {
+        if self.kind != other.kind || self.hash != other.hash {
+            return true;
+        }
+        self.hash.is_empty()
+            && self.kind == EntryKind::File
+            && (self.size != other.size || self.modified != other.modified)
+    }
+}
+
+impl ManifestChanges {
+    pub fn is_empty(&self) -> bool {
+        self.created.is_empty() && self.modified.is_empty() && self.deleted.is_empty()
+    }
+
+    /// All changed paths
+    pub fn paths(&self) -> impl Iterator<Item = &PathBuf> {
+        self.created
+            .iter()
+            .chain(&self.modified)
+            .chain(&self.deleted)
+    }
+}
+
+/// Kind and metadata of the entry at a path, without following symlinks
+pub fn entry_metadata(path: &Path) -> Option<(EntryKind, fs::Metadata)> {
+    let metadata = fs::symlink_metadata(path).ok()?;
+    let file_type = metadata.file_type();
+    let kind = if file_type.is_symlink() {
+        EntryKind::Symlink
+    } else if file_type.is_dir() {
+        EntryKind::Directory
+    } else {
+        EntryKind::File
+    };
+    Some((kind, metadata))
+}
+
+/// Content recorded for an entry: a file's bytes, a symlink's target or
+/// nothing for a directory
+pub fn read_entry_content(path: &Path, kind: EntryKind) -> std::io::Result<Vec<u8>> {
+    match kind {
+        EntryKind::File => fs::read(path),
+        EntryKind::Symlink => {
+            let target = fs::read_link(path)?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::ffi::OsStrExt;
+                Ok(target.as_os_str().as_bytes().to_vec())
+            }
+            #[cfg(not(unix))]
+            {
+                Ok(target.to_string_lossy().into_owned().into_bytes())
+            }
+        }
+        EntryKind::Directory => Ok(Vec::new()),
+    }
+}
+
+/// Hash of an entry's content, streaming files instead of reading them whole
+pub fn entry_hash(path: &Path, kind: EntryKind) -> std::io::Result<String> {
+    match kind {
+        EntryKind::File => CheckpointStorage::calculate_reader_hash(fs::File::open(path)?),
+        _ => read_entry_content(path, kind)
+            .map(|content| CheckpointStorage::calculate_file_hash(&content)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn entry(hash: &str) -> ManifestEntry {
+        ManifestEntry {
+            kind: EntryKind::File,
+            modified: None,
+            size: 1,
+            hash: hash.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_changes_since() {
+        let before = WorkingTreeManifest::from_entries(HashMap::from([
+            (PathBuf::from("kept.txt"), entry("a")),
+            (PathBuf::from("edited.txt"), entry("b")),
+            (PathBuf::from("removed.txt"), entry("c")),
+        ]));
+        let after = WorkingTreeManifest::from_entries(HashMap::from([
+            (PathBuf::from("kept.txt"), entry("a")),
+            (PathBuf::from("edited.txt"), entry("d")),
+            (PathBuf::from("added.txt"), entry("e")),
+        ]));
+
+        let changes = before.changes_since(&after);
+        assert_eq!(changes.created, vec![PathBuf::from("added.txt")]);
+        assert_eq!(changes.modified, vec![PathBuf::from("edited.txt")]);
+        assert_eq!(changes.deleted, vec![PathBuf::from("removed.txt")]);
+        assert!(after.changes_since(&after).is_empty());
+    }
+
+    #[test]
+    fn test_capture_reuses_hashes_of_unchanged_files() {
+        let root = TempDir::new().unwrap();
+        fs::write(root.path().join("a.txt"), b"hello").unwrap();
+        let path = PathBuf::from("a.txt");
+
+        let first = WorkingTreeManifest::capture(root.path(), None, u64::MAX).unwrap();
+        let recorded = first.entries[&path].clone();
+        assert_eq!(
+            recorded.hash,
+            CheckpointStorage::calculate_file_hash(b"hello")
+        );
+
+        // A matching size and mtime is trusted without reading the file
+        let seeded = WorkingTreeManifest::from_entries(HashMap::from([(
+            path.clone(),
+            ManifestEntry {
+                hash: "seeded".to_string(),
+                ..recorded.clone()
+            },
+        )]));
+        let cached = WorkingTreeManifest::capture(root.path(), Some(&seeded), u64::MAX).unwrap();
+        assert_eq!(cached.entries[&path].hash, "seeded");
+
+        // Any difference in size, or a missing mtime, means reading it again
+        for stale in [
+            ManifestEntry {
+                size: recorded.size + 1,
+                hash: "seeded".to_string(),
+                ..recorded.clone()
+            },
+            ManifestEntry {
+                modified: None,
+                hash: "seeded".to_string(),
+                ..recorded.clone()
+            },
+        ] {
+            let stale = WorkingTreeManifest::from_entries(HashMap::from([(path.clone(), stale)]));
+            let fresh = WorkingTreeManifest::capture(root.path(), Some(&stale), u64::MAX).unwrap();
+            assert_eq!(fresh.entries[&path].hash, recorded.hash);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_capture_records_entry_kinds_and_skips_large_files() {
+        let root = TempDir::new().unwrap();
+        fs::write(root.path().join("small.txt"), b"hi").unwrap();
+        fs::write(root.path().join("large.bin"), vec![0u8; 64]).unwrap();
+        fs::create_dir(root.path().join("empty")).unwrap();
+        std::os::unix::fs::symlink("small.txt", root.path().join("link")).unwrap();
+
+        let before = WorkingTreeManifest::capture(root.path(), None, 16).unwrap();
+        assert_eq!(
+            before.entries[Path::new("empty")].kind,
+            EntryKind::Directory
+        );
+        let link = &before.entries[Path::new("link")];
+        assert_eq!(link.kind, EntryKind::Symlink);
+        assert_eq!(
+            link.hash,
+            CheckpointStorage::calculate_file_hash(b"small.txt")
+        );
+        assert!(before.entries[Path::new("large.bin")].hash.is_empty());
+
+        // Retargeting the link and growing the large file both count as changes
+        fs::remove_file(root.path().join("link")).unwrap();
+        std::os::unix::fs::symlink("large.bin", root.path().join("link")).unwrap();
+        fs::write(root.path().join("large.bin"), vec![0u8; 128]).unwrap();
+        fs::remove_dir(root.path().join("empty")).unwrap();
+
+        let after = WorkingTreeManifest::capture(root.path(), Some(&before), 16).unwrap();
+        let changes = before.changes_since(&after);
+        assert_eq!(
+            changes.modified,
+            vec![PathBuf::from("large.bin"), PathBuf::from("link")]
+        );
+        assert_eq!(changes.deleted, vec![PathBuf::from("empty")]);
+        assert!(changes.created.is_empty());
+    }
+}
@@ -0,0 +1,422 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use similar::{capture_diff_slices, Algorithm, DiffTag};
+use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
+use std::path::PathBuf;
+
+use super::{
+    diff::SnapshotText, storage::CheckpointStorage, Checkpoint, CheckpointPaths, ContentSource,
+    FileSnapshot, SessionTimeline,
+};
+
+/// Result of merging one checkpoint into another
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeResult {
+    /// The merge checkpoint, whose parents are the two merged checkpoints
+    pub checkpoint: Checkpoint,
+    /// Common ancestor the merge was computed against, if the branches share one
+    pub base_checkpoint_id: Option<String>,
+    /// Files that could not be merged cleanly
+    pub conflicts: Vec<MergeConflict>,
+    /// Number of files written, deleted or updated on disk
+    pub files_processed: usize,
+    /// Any warnings during the operation
+    pub warnings: Vec<String>,
+    /// Checkpoint holding the working tree as it was before the merge
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub safety_checkpoint_id: Option<String>,
+}
+
+/// A file that both branches changed in incompatible ways
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeConflict {
+    /// File path relative to the project root
+    pub path: PathBuf,
+    /// How the branches disagree
+    pub kind: ConflictKind,
+}
+
+/// Kinds of merge conflict
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictKind {
+    /// Overlapping text edits; the file is written with conflict markers
+    Content,
+    /// One branch modified the file and the other deleted it; the modified
+    /// version is kept
+    ModifyDelete,
+    /// Both branches changed a binary file; the first branch's version is kept
+    Binary,
+    /// Both branches changed a file too large to merge line by line; the
+    /// first branch's version is kept
+    TooLarge,
+}
+
+/// Files of a merged tree and the conflicts found while merging it
+#[derive(Debug, Default)]
+pub struct MergedTree {
+    pub files: BTreeMap<PathBuf, FileSnapshot>,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+impl SessionTimeline {
+    /// Nearest checkpoint that is an ancestor of (or equal to) both checkpoints
+    ///
+    /// Both parents of merge checkpoints are followed, so branches that were
+    /// merged before use their last merge as the base.
+    pub fn merge_base(&self, a: &str, b: &str) -> Option<String> {
+        let ancestors_of_a: HashSet<String> = self.ancestors(a).into_iter().collect();
+        self.ancestors(b)
+            .into_iter()
+            .find(|id| ancestors_of_a.contains(id))
+    }
+
+    /// A checkpoint and all of its ancestors, nearest first
+    fn ancestors(&self, checkpoint_id: &str) -> Vec<String> {
+        let mut seen = HashSet::new();
+        let mut ordered = Vec::new();
+        let mut queue = VecDeque::from([checkpoint_id.to_string()]);
+
+        while let Some(id) = queue.pop_front() {
+            let Some(node) = self.find_checkpoint(&id) else {
+                continue;
+            };
+            if !seen.insert(id.clone()) {
+                continue;
+            }
+            queue.extend(node.checkpoint.parent_checkpoint_id.clone());
+            queue.extend(node.checkpoint.merge_parent_checkpoint_id.clone());
+            ordered.push(id);
+        }
+
+        ordered
+    }
+}
+
+/// Three-way merge of two file trees against their common ancestor
+///
+/// `labels` name the two sides in conflict markers. Content left in the
+/// pool is read from `paths` when a file has to be merged line by line.
+pub fn merge_trees(
+    storage: &CheckpointStorage,
+    paths: &CheckpointPaths,
+    base: &BTreeMap<PathBuf, FileSnapshot>,
+    ours: &BTreeMap<PathBuf, FileSnapshot>,
+    theirs: &BTreeMap<PathBuf, FileSnapshot>,
+    labels: (&str, &str),
+) -> Result<MergedTree> {
+    let file_paths: BTreeSet<&PathBuf> = base
+        .keys()
+        .chain(ours.keys())
+        .chain(theirs.keys())
+        .collect();
+    let mut merged = MergedTree::default();
+
+    for path in file_paths {
+        let (b, o, t) = (base.get(path), ours.get(path), theirs.get(path));
+        let result = if same_file(o, t) || same_file(b, t) {
+            o.cloned()
+        } else if same_file(b, o) {
+            t.cloned()
+        } else {
+            match (o, t) {
+                (Some(o), Some(t)) => {
+                    let (snapshot, kind) = merge_file(storage, paths, b, o, t, labels)
+                        .with_context(|| format!("Failed to merge {}", path.display()))?;
+                    if let Some(kind) = kind {
+                        merged.conflicts.push(MergeConflict {
+                            path: path.clone(),
+                            kind,
+                        });
+                    }
+                    Some(snapshot)
+                }
+                (modified, deleted) => {
+                    merged.conflicts.push(MergeConflict {
+                        path: path.clone(),
+                        kind: ConflictKind::ModifyDelete,
+                    });
+                    modified.or(deleted).cloned()
+                }
+            }
+        };
+
+        if let Some(snapshot) = result {
+            merged.files.insert(path.clone(), snapshot);
+        }
+    }
+
+    Ok(merged)
+}
+
+fn same_file(a: Option<&FileSnapshot>, b: Option<&FileSnapshot>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a.hash == b.hash && a.permissions == b.permissions,
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+/// Merge a file both branches changed, returning any conflict
+fn merge_file(
+    storage: &CheckpointStorage,
+    paths: &CheckpointPaths,
+    base: Option<&FileSnapshot>,
+    ours: &FileSnapshot,
+    theirs: &FileSnapshot,
+    labels: (&str, &str),
+) -> Result<(FileSnapshot, Option<ConflictKind>)> {
+    // A mode change on one side is kept alongside content changes on the other
+    let permissions = if base.map(|b| b.permissions) == Some(ours.permissions) {
+        theirs.permissions
+    } else {
+        ours.permissions
+    };
+
+    let base_text = match base {
+        Some(base) => SnapshotText::load(storage, paths, base)?,
+        None => SnapshotText::Text("".into()),
+    };
+    let texts = [
+        base_text,
+        SnapshotText::load(storage, paths, ours)?,
+        SnapshotText::load(storage, paths, theirs)?,
+    ];
+    let [SnapshotText::Text(base_text), SnapshotText::Text(ours_text), SnapshotText::Text(theirs_text)] =
+        &texts
+    else {
+        let kind = if texts.contains(&SnapshotText::Binary) {
+            ConflictKind::Binary
+        } else {
+            ConflictKind::TooLarge
+        };
+        return Ok((ours.clone(), Some(kind)));
+    };
+
+    let (text, conflicts) = merge_text(base_text, ours_text, theirs_text, labels);
+    let content = text.into_bytes();
+    let snapshot = FileSnapshot {
+        hash: CheckpointStorage::calculate_file_hash(&content),
+        size: content.len() as u64,
+        content,
+        permissions,
+        source: ContentSource::Inline,
+        ..ours.clone()
+    };
+    Ok((snapshot, (conflicts > 0).then_some(ConflictKind::Content)))
+}
+
+/// Line-based three-way merge, returning the merged text and the number of
+/// conflicting hunks
+///
+/// Regions changed by only one side take that side's lines. Regions changed
+/// differently by both sides are written between `<<<<<<<`, `=======` and
+/// `>>>>>>>` markers labelled with `labels`.
+pub fn merge_text(base: &str, ours: &str, theirs: &str, labels: (&str, &str)) -> (String, usize) {
+    let base_lines: Vec<&str> = base.split_inclusive('\n').collect();
+    let ours_lines: Vec<&str> = ours.split_inclusive('\n').collect();
+    let theirs_lines: Vec<&str> = theirs.split_inclusive('\n').collect();
+    let ours_matches = matched_lines(&base_lines, &ours_lines);
+    let theirs_matches = matched_lines(&base_lines, &theirs_lines);
+
+    let mut out = String::new();
+    let mut conflicts = 0;
+    let (mut b, mut o, mut t) = (0, 0, 0);
+    loop {
+        // Next base line that both sides kept
+        let stable =
+            (b..base_lines.len()).find_map(|i| Some((i, ours_matches[i]?, theirs_matches[i]?)));
+
+        if stable == Some((b, o, t)) {
+            out.push_str(base_lines[b]);
+            (b, o, t) = (b + 1, o + 1, t + 1);
+            continue;
+        }
+
+        let (next_b, next_o, next_t) =
+            stable.unwrap_or((base_lines.len(), ours_lines.len(), theirs_lines.len()));
+        if merge_hunk(
+            &mut out,
+            &base_lines[b..next_b],
+            &ours_lines[o..next_o],
+            &theirs_lines[t..next_t],
+            labels,
+        ) {
+            conflicts += 1;
+        }
+
+        if stable.is_none() {
+            break;
+        }
+        (b, o, t) = (next_b, next_o, next_t);
+    }
+
+    (out, conflicts)
+}
+
+/// For each base line, the index of the equal line in `side`, if it was kept
+fn matched_lines(base: &[&str], side: &[&str]) -> Vec<Option<usize>> {
+    let mut matches = vec![None; base.len()];
+    for op in capture_diff_slices(Algorithm::Myers, base, side) {
+        let (tag, base_range, side_range) = op.as_tag_tuple();
+        if tag == DiffTag::Equal {
+            for (i, j) in base_range.zip(side_range) {
+                matches[i] = Some(j);
+            }
+        }
+    }
+    matches
+}
+
+/// Write one unstable region, returning whether it conflicted
+fn merge_hunk(
+    out: &mut String,
+    base: &[&str],
+    ours: &[&str],
+    theirs: &[&str],
+    labels: (&str, &str),
+) -> bool {
+    if ours == theirs || theirs == base {
+        out.extend(ours.iter().copied());
+        return false;
+    }
+    if ours == base {
+        out.extend(theirs.iter().copied());
+        return false;
+    }
+
+    out.push_str(&format!("<<<<<<< {}\n", labels.0));
+    push_lines(out, ours);
+    out.push_str("=======\n");
+    push_lines(out, theirs);
+    out.push_str(&format!(">>>>>>> {}\n", labels.1));
+    true
+}
+
+/// Append lines, ending with a newline so a marker can follow
+fn push_lines(out: &mut String, lines: &[&str]) {
+    for line in lines {
+        out.push_str(line);
+    }
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checkpoint::diff::MAX_DIFF_FILE_SIZE;
+    use crate::checkpoint::manager::CheckpointManager;
+    use crate::checkpoint::storage::CHUNKED_OBJECT_THRESHOLD;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_merge_text_combines_separate_edits() {
+        let base = "a\nb\nc\nd\ne\n";
+        let ours = "A\nb\nc\nd\ne\n";
+        let theirs = "a\nb\nc\nd\nE\nf\n";
+        let (merged, conflicts) = merge_text(base, ours, theirs, ("ours", "theirs"));
+
+        assert_eq!(conflicts, 0);
+        assert_eq!(merged, "A\nb\nc\nd\nE\nf\n");
+    }
+
+    #[test]
+    fn test_merge_text_marks_overlapping_edits() {
+        let base = "a\nb\nc\n";
+        let ours = "a\nB1\nc\n";
+        let theirs = "a\nB2\nc\n";
+        let (merged, conflicts) = merge_text(base, ours, theirs, ("ours", "theirs"));
+
+        assert_eq!(conflicts, 1);
+        assert_eq!(
+            merged,
+            "a\n<<<<<<< ours\nB1\n=======\nB2\n>>>>>>> theirs\nc\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_large_text_files_are_merged_line_by_line() {
+        let temp_dir = TempDir::new().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        let project_path = temp_dir.path().join("project");
+        fs::create_dir_all(&project_path).unwrap();
+        let manager = CheckpointManager::new(
+            "proj".to_string(),
+            "sess".to_string(),
+            project_path.clone(),
+            claude_dir.clone(),
+        )
+        .await
+        .unwrap();
+
+        let lines: Vec<String> = (0..CHUNKED_OBJECT_THRESHOLD / 16 + 100)
+            .map(|i| format!("line {:010}\n", i))
+            .collect();
+        let last = lines.len() - 1;
+        let edit = |index: usize, line: &str| {
+            let mut edited = lines.clone();
+            edited[index] = line.to_string();
+            edited.concat()
+        };
+        let big = project_path.join("big.txt");
+        fs::write(&big, lines.concat()).unwrap();
+        let base = manager.create_checkpoint(None, None).await.unwrap();
+        fs::write(&big, edit(0, "ours\n")).unwrap();
+        let ours = manager.create_checkpoint(None, None).await.unwrap();
+        manager
+            .restore_checkpoint(&base.checkpoint.id)
+            .await
+            .unwrap();
+        fs::write(&big, edit(last, "theirs\n")).unwrap();
+        let theirs = manager.create_checkpoint(None, None).await.unwrap();
+
+        let result = manager
+            .merge_checkpoints(&ours.checkpoint.id, &theirs.checkpoint.id, None)
+            .await
+            .unwrap();
+        assert!(result.conflicts.is_empty(), "{:?}", result.conflicts);
+        let mut expected = lines.clone();
+        expected[0] = "ours\n".to_string();
+        expected[last] = "theirs\n".to_string();
+        assert_eq!(fs::read_to_string(&big).unwrap(), expected.concat());
+
+        // A file over the limit is kept from the first branch without reading it
+        let storage = CheckpointStorage::new(claude_dir.clone());
+        let paths = CheckpointPaths::new(&claude_dir, "proj", "sess");
+        let snapshot = |content: &[u8]| FileSnapshot {
+            checkpoint_id: String::new(),
+            file_path: PathBuf::from("huge.txt"),
+            content: content.to_vec(),
+            hash: CheckpointStorage::calculate_file_hash(content),
+            is_deleted: false,
+            permissions: None,
+            size: MAX_DIFF_FILE_SIZE + 1,
+            kind: Default::default(),
+            source: ContentSource::Pool,
+        };
+        let tree =
+            |content: &[u8]| BTreeMap::from([(PathBuf::from("huge.txt"), snapshot(content))]);
+        let merged = merge_trees(
+            &storage,
+            &paths,
+            &tree(b"base"),
+            &tree(b"ours"),
+            &tree(b"theirs"),
+            ("ours", "theirs"),
+        )
+        .unwrap();
+        assert_eq!(
+            merged.conflicts,
+            vec![MergeConflict {
+                path: PathBuf::from("huge.txt"),
+                kind: ConflictKind::TooLarge,
+            }]
+        );
+        assert_eq!(merged.files[&PathBuf::from("huge.txt")].content, b"ours");
+    }
+}
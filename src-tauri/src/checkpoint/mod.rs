@@ -1,11 +1,26 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
+pub mod archive;
+pub mod chunks;
+pub mod diff;
+pub mod git;
 pub mod manager;
+pub mod manifest;
+pub mod merge;
+pub mod retention;
+pub mod rules;
+pub mod scheduler;
+pub mod search;
 pub mod state;
+pub mod stats;
 pub mod storage;
+pub mod transcript;
+pub mod verify;
+pub mod walker;
+pub mod watcher;
 
 /// Represents a checkpoint in the session timeline
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -25,6 +40,17 @@ pub struct Checkpoint {
     pub description: Option<String>,
     /// Parent checkpoint ID for fork tracking
     pub parent_checkpoint_id: Option<String>,
+    /// Second parent of a merge checkpoint, the branch merged into the first
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub merge_parent_checkpoint_id: Option<String>,
+    /// Names that refer to this checkpoint, unique within the session
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Whether the file references describe the whole tree, with unchanged
+    /// files pointing at existing pool content. Older checkpoints only
+    /// reference the files that changed since their parent.
+    #[serde(default)]
+    pub full_tree: bool,
     /// Metadata about the checkpoint
     pub metadata: CheckpointMetadata,
 }
@@ -41,8 +67,32 @@ pub struct CheckpointMetadata {
     pub user_prompt: String,
     /// Number of file changes in this checkpoint
     pub file_changes: usize,
-    /// Size of all file snapshots in bytes
+    /// Bytes the checkpoint added to disk: its compressed messages plus
+    /// compressed file content not already in the pool
     pub snapshot_size: u64,
+    /// Files present in the project that the checkpoint does not hold
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skipped_files: Vec<SkippedFile>,
+}
+
+/// A file left out of a checkpoint
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SkippedFile {
+    /// Relative path from project root
+    pub path: PathBuf,
+    /// File size in bytes
+    pub size: u64,
+    /// Why the file was left out
+    pub reason: SkipReason,
+}
+
+/// Why a file was left out of a checkpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    /// Larger than the session's `max_file_size`
+    TooLarge,
 }
 
 /// Represents a snapshot of a file at a checkpoint
@@ -53,8 +103,8 @@ pub struct FileSnapshot {
     pub checkpoint_id: String,
     /// Relative path from project root
     pub file_path: PathBuf,
-    /// Full content of the file (will be compressed)
-    pub content: String,
+    /// Raw content of the file (will be compressed)
+    pub content: Vec<u8>,
     /// SHA-256 hash for integrity verification
     pub hash: String,
     /// Whether this file was deleted at this checkpoint
@@ -63,6 +113,37 @@ pub struct FileSnapshot {
     pub permissions: Option<u32>,
     /// File size in bytes
     pub size: u64,
+    /// What kind of entry the path is. A symlink's content is its target and
+    /// a directory, which is only recorded while empty, has no content.
+    #[serde(default)]
+    pub kind: EntryKind,
+    /// Where the content is streamed from when it is too large to hold in
+    /// `content`
+    #[serde(skip)]
+    pub source: ContentSource,
+}
+
+/// Where a file snapshot's content is kept
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ContentSource {
+    /// In the snapshot's `content`
+    #[default]
+    Inline,
+    /// In a file on disk, read when the snapshot is stored
+    File(PathBuf),
+    /// In the pool object named by the snapshot's hash, read when the
+    /// snapshot is restored
+    Pool,
+}
+
+/// Kind of filesystem entry a snapshot records
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryKind {
+    #[default]
+    File,
+    Symlink,
+    Directory,
 }
 
 /// Represents a node in the timeline tree
@@ -93,6 +174,112 @@ pub struct SessionTimeline {
     pub checkpoint_strategy: CheckpointStrategy,
     /// Total number of checkpoints in timeline
     pub total_checkpoints: usize,
+    /// Rules for pruning old checkpoints
+    #[serde(default)]
+    pub retention_policy: RetentionPolicy,
+    /// When automatic checkpoints requested by the strategy are taken
+    #[serde(default)]
+    pub auto_checkpoint_schedule: AutoCheckpointSchedule,
+    /// Files larger than this many bytes are never read or stored
+    #[serde(default = "default_max_file_size")]
+    pub max_file_size: u64,
+    /// When checkpoint writes are flushed to stable storage
+    #[serde(default)]
+    pub fsync_policy: storage::FsyncPolicy,
+}
+
+/// Default `SessionTimeline::max_file_size`, 100 MiB
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 100 * 1024 * 1024;
+
+fn default_max_file_size() -> u64 {
+    DEFAULT_MAX_FILE_SIZE
+}
+
+/// Declarative rules for which checkpoints are kept when pruning
+///
+/// A checkpoint survives if any keep rule selects it. With no keep rules
+/// set, only the disk cap prunes. The current checkpoint and tagged
+/// checkpoints are never pruned.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionPolicy {
+    /// Keep the newest N checkpoints on every branch
+    pub keep_last_per_branch: Option<usize>,
+    /// Keep the newest checkpoint of each day for this many days
+    pub keep_daily_for_days: Option<u32>,
+    /// Keep the newest checkpoint of each week for this many days
+    pub keep_weekly_for_days: Option<u32>,
+    /// Never prune checkpoints that have a description
+    ///
+    /// Off by default: safety, merge and fork checkpoints are described
+    /// automatically, so this would keep all of them.
+    pub keep_described: bool,
+    /// Prune the oldest unprotected checkpoints until the session uses at most this many bytes
+    pub max_disk_bytes: Option<u64>,
+}
+
+/// Limits on automatic checkpoints
+///
+/// The checkpoint strategy decides which messages ask for a checkpoint; the
+/// schedule decides when one is actually taken. A checkpoint is never taken
+/// when no file differs from the current checkpoint.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AutoCheckpointSchedule {
+    /// Minimum seconds between automatic checkpoints
+    pub debounce_secs: u64,
+    /// Wait until the changed files add up to at least this many bytes
+    pub min_changed_bytes: u64,
+    /// Hold checkpoints requested mid-turn until the assistant's turn ends
+    pub at_end_of_turn: bool,
+}
+
+/// A tag and the checkpoint it names
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckpointTag {
+    /// The tag
+    pub tag: String,
+    /// Checkpoint the tag refers to
+    pub checkpoint_id: String,
+    /// When the checkpoint was created
+    pub timestamp: DateTime<Utc>,
+    /// Description of the checkpoint
+    pub description: Option<String>,
+}
+
+/// Ordered rules deciding which tool calls the `Smart` strategy checkpoints
+///
+/// Rules are shared by every session of a project. The first rule that
+/// matches a tool call decides; tool calls no rule matches are not
+/// checkpointed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SmartRules {
+    pub rules: Vec<SmartRule>,
+}
+
+/// A single `Smart` strategy rule
+///
+/// Every non-empty condition must match; within a condition any pattern may
+/// match. A rule with no conditions matches every tool call.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SmartRule {
+    /// Glob patterns for the tool name, matched case-insensitively (e.g. `mcp__*__write*`)
+    #[serde(default)]
+    pub tools: Vec<String>,
+    /// Regular expressions searched for in the Bash command
+    #[serde(default)]
+    pub bash_commands: Vec<String>,
+    /// Glob patterns for the file the tool touches, relative to the project
+    #[serde(default)]
+    pub paths: Vec<String>,
+    /// Whether a matching tool call creates a checkpoint
+    pub checkpoint: bool,
+    /// Optional note explaining the rule
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
 }
 
 /// Strategy for automatic checkpoint creation
@@ -105,7 +292,7 @@ pub enum CheckpointStrategy {
     PerPrompt,
     /// Create checkpoint after each tool use
     PerToolUse,
-    /// Create checkpoint after destructive operations
+    /// Create checkpoint after tool calls selected by the project's `SmartRules`
     Smart,
 }
 
@@ -116,8 +303,22 @@ pub struct FileTracker {
     pub tracked_files: HashMap<PathBuf, FileState>,
 }
 
+/// File tracker state and message cursor saved alongside a session's timeline
+///
+/// Saved whenever the tracker is reset to a checkpoint. Changes made after
+/// that are found again by hashing on the next project scan.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrackerState {
+    /// Tracked files as of the last save
+    pub tracked_files: HashMap<PathBuf, FileState>,
+    /// Number of session JSONL messages tracked
+    pub message_cursor: usize,
+}
+
 /// State of a tracked file
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct FileState {
     /// Last known hash of the file
     pub last_hash: String,
@@ -127,10 +328,14 @@ pub struct FileState {
     pub last_modified: DateTime<Utc>,
     /// Whether the file currently exists
     pub exists: bool,
+    /// Kind of entry last seen at the path
+    #[serde(default)]
+    pub kind: EntryKind,
 }
 
 /// Result of a checkpoint operation
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct CheckpointResult {
     /// The created/restored checkpoint
     pub checkpoint: Checkpoint,
@@ -138,10 +343,44 @@ pub struct CheckpointResult {
     pub files_processed: usize,
     /// Any warnings during the operation
     pub warnings: Vec<String>,
+    /// Checkpoint holding the working tree as it was before a restore
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub safety_checkpoint_id: Option<String>,
+}
+
+/// Changes a restore would make, computed without touching disk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestorePlan {
+    /// Checkpoint the plan restores to
+    pub checkpoint_id: String,
+    /// Files that exist now but are not part of the checkpoint
+    pub files_to_delete: Vec<PathBuf>,
+    /// Files in the checkpoint that are missing from disk
+    pub files_to_create: Vec<PathBuf>,
+    /// Files whose current content differs from the checkpoint
+    pub files_to_overwrite: Vec<PathBuf>,
+    /// Files whose mode bits will change
+    pub permission_changes: Vec<PermissionChange>,
+    /// Number of files that already match the checkpoint
+    pub unchanged_files: usize,
+}
+
+/// A planned change to a file's Unix permissions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PermissionChange {
+    /// File path relative to the project root
+    pub path: PathBuf,
+    /// Current mode, if known
+    pub from: Option<u32>,
+    /// Mode after restore
+    pub to: u32,
 }
 
 /// Diff between two checkpoints
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct CheckpointDiff {
     /// Source checkpoint ID
     pub from_checkpoint_id: String,
@@ -159,6 +398,7 @@ pub struct CheckpointDiff {
 
 /// Diff for a single file
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct FileDiff {
     /// File path
     pub path: PathBuf,
@@ -170,6 +410,60 @@ pub struct FileDiff {
     pub diff_content: Option<String>,
 }
 
+impl Default for AutoCheckpointSchedule {
+    fn default() -> Self {
+        Self {
+            debounce_secs: 30,
+            min_changed_bytes: 0,
+            at_end_of_turn: true,
+        }
+    }
+}
+
+impl Default for SmartRules {
+    fn default() -> Self {
+        let rule = |description: &str, tools: &[&str]| SmartRule {
+            tools: tools.iter().map(|t| t.to_string()).collect(),
+            bash_commands: Vec::new(),
+            paths: Vec::new(),
+            checkpoint: true,
+            description: Some(description.to_string()),
+        };
+
+        Self {
+            rules: vec![
+                rule(
+                    "File edits",
+                    &["write", "edit", "multiedit", "notebookedit"],
+                ),
+                SmartRule {
+                    tools: vec!["bash".to_string()],
+                    bash_commands: vec![
+                        r"^\s*(ls|cat|head|tail|pwd|grep|rg|find|git\s+(status|log|diff|show))\b[^;&|>]*$"
+                            .to_string(),
+                    ],
+                    paths: Vec::new(),
+                    checkpoint: false,
+                    description: Some("Read-only shell commands".to_string()),
+                },
+                rule("Shell commands", &["bash"]),
+                rule("Deletions", &["rm", "delete"]),
+                rule(
+                    "MCP tools that modify files",
+                    &[
+                        "mcp__*write*",
+                        "mcp__*edit*",
+                        "mcp__*create*",
+                        "mcp__*delete*",
+                        "mcp__*move*",
+                        "mcp__*rename*",
+                    ],
+                ),
+            ],
+        }
+    }
+}
+
 impl Default for CheckpointStrategy {
     fn default() -> Self {
         CheckpointStrategy::Smart
@@ -186,6 +480,10 @@ impl SessionTimeline {
             auto_checkpoint_enabled: false,
             checkpoint_strategy: CheckpointStrategy::default(),
             total_checkpoints: 0,
+            retention_policy: RetentionPolicy::default(),
+            auto_checkpoint_schedule: AutoCheckpointSchedule::default(),
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+            fsync_policy: storage::FsyncPolicy::default(),
         }
     }
 
@@ -196,6 +494,71 @@ impl SessionTimeline {
             .and_then(|root| Self::find_in_tree(root, checkpoint_id))
     }
 
+    /// Find a checkpoint by ID for modification
+    pub fn find_checkpoint_mut(&mut self, checkpoint_id: &str) -> Option<&mut TimelineNode> {
+        fn find<'a>(
+            node: &'a mut TimelineNode,
+            checkpoint_id: &str,
+        ) -> Option<&'a mut TimelineNode> {
+            if node.checkpoint.id == checkpoint_id {
+                return Some(node);
+            }
+            node.children
+                .iter_mut()
+                .find_map(|child| find(child, checkpoint_id))
+        }
+
+        self.root_node
+            .as_mut()
+            .and_then(|root| find(root, checkpoint_id))
+    }
+
+    /// Every tag in the timeline, sorted by name
+    pub fn tags(&self) -> Vec<CheckpointTag> {
+        fn collect(node: &TimelineNode, tags: &mut Vec<CheckpointTag>) {
+            for tag in &node.checkpoint.tags {
+                tags.push(CheckpointTag {
+                    tag: tag.clone(),
+                    checkpoint_id: node.checkpoint.id.clone(),
+                    timestamp: node.checkpoint.timestamp,
+                    description: node.checkpoint.description.clone(),
+                });
+            }
+            for child in &node.children {
+                collect(child, tags);
+            }
+        }
+
+        let mut tags = Vec::new();
+        if let Some(root) = &self.root_node {
+            collect(root, &mut tags);
+        }
+        tags.sort_by(|a, b| a.tag.cmp(&b.tag));
+        tags
+    }
+
+    /// Resolve a checkpoint ID, tag or unique ID prefix to a checkpoint ID
+    pub fn resolve_checkpoint(&self, reference: &str) -> anyhow::Result<String> {
+        if self.find_checkpoint(reference).is_some() {
+            return Ok(reference.to_string());
+        }
+
+        if let Some(tag) = self.tags().into_iter().find(|t| t.tag == reference) {
+            return Ok(tag.checkpoint_id);
+        }
+
+        let matches: Vec<String> = self
+            .checkpoint_ids()
+            .into_iter()
+            .filter(|id| !reference.is_empty() && id.starts_with(reference))
+            .collect();
+        match matches.as_slice() {
+            [id] => Ok(id.clone()),
+            [] => anyhow::bail!("No checkpoint or tag named {}", reference),
+            _ => anyhow::bail!("Checkpoint prefix {} is ambiguous", reference),
+        }
+    }
+
     fn find_in_tree<'a>(node: &'a TimelineNode, checkpoint_id: &str) -> Option<&'a TimelineNode> {
         if node.checkpoint.id == checkpoint_id {
             return Some(node);
@@ -209,30 +572,166 @@ impl SessionTimeline {
 
         None
     }
+
+    /// IDs of every checkpoint in the tree, parents before children
+    pub fn checkpoint_ids(&self) -> Vec<String> {
+        fn collect(node: &TimelineNode, ids: &mut Vec<String>) {
+            ids.push(node.checkpoint.id.clone());
+            for child in &node.children {
+                collect(child, ids);
+            }
+        }
+
+        let mut ids = Vec::new();
+        if let Some(root) = &self.root_node {
+            collect(root, &mut ids);
+        }
+        ids
+    }
+
+    /// Remove a checkpoint from the tree, attaching its children to its parent
+    ///
+    /// When the root is removed its first child becomes the new root. The
+    /// current checkpoint moves to the parent if it was removed, as do merges
+    /// that recorded it as their second parent. Returns every checkpoint whose
+    /// parents changed, or `None` if the checkpoint is not in the tree.
+    pub fn remove_checkpoint(&mut self, checkpoint_id: &str) -> Option<Vec<Checkpoint>> {
+        let root = self.root_node.as_mut()?;
+
+        let (parent_id, mut reparented) = if root.checkpoint.id == checkpoint_id {
+            let removed = self.root_node.take()?;
+            let mut children = removed.children.into_iter();
+            let mut reparented = Vec::new();
+            if let Some(mut new_root) = children.next() {
+                new_root.checkpoint.parent_checkpoint_id = None;
+                reparented.push(new_root.checkpoint.clone());
+                for mut child in children {
+                    child.checkpoint.parent_checkpoint_id = Some(new_root.checkpoint.id.clone());
+                    reparented.push(child.checkpoint.clone());
+                    new_root.children.push(child);
+                }
+                self.root_node = Some(new_root);
+            }
+            let new_root_id = self.root_node.as_ref().map(|r| r.checkpoint.id.clone());
+            (new_root_id, reparented)
+        } else {
+            let (parent_id, reparented) = Self::remove_from_tree(root, checkpoint_id)?;
+            (Some(parent_id), reparented)
+        };
+
+        // Merges of the removed checkpoint now record its parent instead
+        if let Some(root) = self.root_node.as_mut() {
+            Self::redirect_merge_parents(
+                root,
+                checkpoint_id,
+                parent_id.as_deref(),
+                &mut reparented,
+            );
+        }
+
+        if self.current_checkpoint_id.as_deref() == Some(checkpoint_id) {
+            self.current_checkpoint_id = parent_id;
+        }
+        self.total_checkpoints = self.total_checkpoints.saturating_sub(1);
+
+        Some(reparented)
+    }
+
+    fn redirect_merge_parents(
+        node: &mut TimelineNode,
+        removed_id: &str,
+        new_parent_id: Option<&str>,
+        updated: &mut Vec<Checkpoint>,
+    ) {
+        let checkpoint = &mut node.checkpoint;
+        if checkpoint.merge_parent_checkpoint_id.as_deref() == Some(removed_id) {
+            checkpoint.merge_parent_checkpoint_id = new_parent_id
+                .filter(|id| checkpoint.parent_checkpoint_id.as_deref() != Some(*id))
+                .map(str::to_string);
+            updated.push(checkpoint.clone());
+        }
+        for child in &mut node.children {
+            Self::redirect_merge_parents(child, removed_id, new_parent_id, updated);
+        }
+    }
+
+    fn remove_from_tree(
+        node: &mut TimelineNode,
+        checkpoint_id: &str,
+    ) -> Option<(String, Vec<Checkpoint>)> {
+        if let Some(index) = node
+            .children
+            .iter()
+            .position(|c| c.checkpoint.id == checkpoint_id)
+        {
+            let removed = node.children.remove(index);
+            let mut reparented = Vec::new();
+            for (offset, mut child) in removed.children.into_iter().enumerate() {
+                child.checkpoint.parent_checkpoint_id = Some(node.checkpoint.id.clone());
+                reparented.push(child.checkpoint.clone());
+                node.children.insert(index + offset, child);
+            }
+            return Some((node.checkpoint.id.clone(), reparented));
+        }
+
+        node.children
+            .iter_mut()
+            .find_map(|child| Self::remove_from_tree(child, checkpoint_id))
+    }
 }
 
 /// Checkpoint storage paths
 pub struct CheckpointPaths {
     pub timeline_file: PathBuf,
+    /// Saved `TrackerState` of the session
+    pub tracker_file: PathBuf,
     pub checkpoints_dir: PathBuf,
     pub files_dir: PathBuf,
+    /// Content-addressed pool shared by every session of the project
+    pub content_pool_dir: PathBuf,
 }
 
 impl CheckpointPaths {
     pub fn new(claude_dir: &PathBuf, project_id: &str, session_id: &str) -> Self {
-        let base_dir = claude_dir
-            .join("projects")
-            .join(project_id)
-            .join(".timelines")
-            .join(session_id);
+        let timelines_dir = Self::timelines_dir(claude_dir, project_id);
+        Self::with_base_dir(&timelines_dir.join(session_id), &timelines_dir)
+    }
 
+    /// Paths of a session kept in `base_dir`, sharing the content pool of the
+    /// project whose timelines are in `timelines_dir`
+    ///
+    /// Lets a session be built elsewhere and renamed into place once complete.
+    pub fn with_base_dir(base_dir: &Path, timelines_dir: &Path) -> Self {
         Self {
             timeline_file: base_dir.join("timeline.json"),
+            tracker_file: base_dir.join("tracker.json"),
             checkpoints_dir: base_dir.join("checkpoints"),
             files_dir: base_dir.join("files"),
+            content_pool_dir: timelines_dir.join("content_pool"),
         }
     }
 
+    /// Directory holding the timelines of every session in a project
+    pub fn timelines_dir(claude_dir: &Path, project_id: &str) -> PathBuf {
+        claude_dir
+            .join("projects")
+            .join(project_id)
+            .join(".timelines")
+    }
+
+    /// Claude Code's JSONL transcript of a session
+    pub fn session_file(claude_dir: &Path, project_id: &str, session_id: &str) -> PathBuf {
+        claude_dir
+            .join("projects")
+            .join(project_id)
+            .join(format!("{}.jsonl", session_id))
+    }
+
+    /// File holding a project's `Smart` strategy rules
+    pub fn smart_rules_file(claude_dir: &Path, project_id: &str) -> PathBuf {
+        Self::timelines_dir(claude_dir, project_id).join("smart_rules.json")
+    }
+
     pub fn checkpoint_dir(&self, checkpoint_id: &str) -> PathBuf {
         self.checkpoints_dir.join(checkpoint_id)
     }
@@ -245,18 +744,66 @@ impl CheckpointPaths {
         self.checkpoint_dir(checkpoint_id).join("messages.jsonl")
     }
 
+    /// Journal of checkpoint writes that have not been committed to the timeline
+    pub fn journal_dir(&self) -> PathBuf {
+        self.checkpoints_dir.with_file_name("journal")
+    }
+
+    /// Journal entry for an in-progress checkpoint write
+    pub fn journal_entry_file(&self, checkpoint_id: &str) -> PathBuf {
+        self.journal_dir().join(format!("{}.json", checkpoint_id))
+    }
+
+    /// Directory of per-file references written by checkpoints before manifests
+    pub fn checkpoint_refs_dir(&self, checkpoint_id: &str) -> PathBuf {
+        self.files_dir.join("refs").join(checkpoint_id)
+    }
+
+    /// Directory holding one manifest per checkpoint
+    pub fn manifests_dir(&self) -> PathBuf {
+        self.files_dir.join("manifests")
+    }
+
+    /// Manifest of every path a checkpoint records and its content in the pool
+    pub fn checkpoint_manifest_file(&self, checkpoint_id: &str) -> PathBuf {
+        self.manifests_dir().join(format!("{}.json", checkpoint_id))
+    }
+
+    /// Compressed content for a hash in the shared pool
+    pub fn content_object_path(&self, file_hash: &str) -> PathBuf {
+        self.content_pool_dir.join("objects").join(file_hash)
+    }
+
+    /// Directory of compressed chunks that large pool objects are split into
+    pub fn chunks_dir(&self) -> PathBuf {
+        self.content_pool_dir.join("chunks")
+    }
+
+    /// Compressed chunk for a hash in the shared pool
+    pub fn chunk_path(&self, chunk_hash: &str) -> PathBuf {
+        self.chunks_dir().join(chunk_hash)
+    }
+
+    /// Reference counts for objects in the shared pool
+    pub fn refcounts_file(&self) -> PathBuf {
+        self.content_pool_dir.join("refcounts.json")
+    }
+
+    /// Per-session pool used before content was shared across sessions
+    pub fn legacy_content_pool_dir(&self) -> PathBuf {
+        self.files_dir.join("content_pool")
+    }
+
     #[allow(dead_code)]
     pub fn file_snapshot_path(&self, _checkpoint_id: &str, file_hash: &str) -> PathBuf {
         // In content-addressable storage, files are stored by hash in the content pool
-        self.files_dir.join("content_pool").join(file_hash)
+        self.content_object_path(file_hash)
     }
 
     #[allow(dead_code)]
     pub fn file_reference_path(&self, checkpoint_id: &str, safe_filename: &str) -> PathBuf {
         // References are stored per checkpoint
-        self.files_dir
-            .join("refs")
-            .join(checkpoint_id)
+        self.checkpoint_refs_dir(checkpoint_id)
             .join(format!("{}.json", safe_filename))
     }
 }
@@ -0,0 +1,399 @@
+use anyhow::Result;
+use chrono::{DateTime, Datelike, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+use super::{
+    storage::{checkpoint_metadata_bytes, CheckpointStorage},
+    Checkpoint, CheckpointPaths, RetentionPolicy, SessionTimeline, TimelineNode,
+};
+
+/// Outcome of applying a retention policy
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionReport {
+    /// Checkpoints removed from the timeline, oldest first
+    pub pruned_checkpoints: Vec<String>,
+    /// Session disk usage before pruning, in bytes
+    pub bytes_before: u64,
+    /// Session disk usage after pruning, in bytes
+    pub bytes_after: u64,
+}
+
+impl RetentionPolicy {
+    /// Whether the policy can prune anything
+    pub fn is_empty(&self) -> bool {
+        !self.has_keep_rules() && self.max_disk_bytes.is_none()
+    }
+
+    fn has_keep_rules(&self) -> bool {
+        self.keep_last_per_branch.is_some()
+            || self.keep_daily_for_days.is_some()
+            || self.keep_weekly_for_days.is_some()
+    }
+
+    /// Checkpoints that are never pruned, whatever their age or size
+    fn is_protected(&self, checkpoint: &Checkpoint, timeline: &SessionTimeline) -> bool {
+        timeline.current_checkpoint_id.as_deref() == Some(checkpoint.id.as_str())
+            || !checkpoint.tags.is_empty()
+            || (self.keep_described
+                && checkpoint
+                    .description
+                    .as_deref()
+                    .is_some_and(|d| !d.trim().is_empty()))
+    }
+
+    /// Checkpoints selected by the keep rules
+    fn kept_by_rules(&self, timeline: &SessionTimeline, now: DateTime<Utc>) -> HashSet<String> {
+        let mut kept = HashSet::new();
+        let Some(root) = &timeline.root_node else {
+            return kept;
+        };
+
+        if let Some(count) = self.keep_last_per_branch {
+            let mut path = Vec::new();
+            keep_branch_tips(root, count, &mut path, &mut kept);
+        }
+
+        let mut checkpoints = Vec::new();
+        collect_checkpoints(root, &mut checkpoints);
+        if let Some(days) = self.keep_daily_for_days {
+            let since = now - Duration::days(days as i64);
+            kept.extend(newest_per_bucket(&checkpoints, since, |t| {
+                (t.year(), t.ordinal())
+            }));
+        }
+        if let Some(days) = self.keep_weekly_for_days {
+            let since = now - Duration::days(days as i64);
+            kept.extend(newest_per_bucket(&checkpoints, since, |t| {
+                let week = t.iso_week();
+                (week.year(), week.week())
+            }));
+        }
+
+        kept
+    }
+}
+
+impl CheckpointStorage {
+    /// Prune a session's checkpoints according to a retention policy
+    ///
+    /// Pruned checkpoints are removed from the tree with their children
+    /// re-parented, and unreferenced pool content is garbage collected.
+    /// Disk usage counts the session's checkpoint files plus every pool
+    /// object it references, once.
+    pub fn apply_retention_policy(
+        &self,
+        project_id: &str,
+        session_id: &str,
+        policy: &RetentionPolicy,
+    ) -> Result<RetentionReport> {
+        let paths = CheckpointPaths::new(&self.claude_dir, project_id, session_id);
+        let mut timeline = self.load_timeline(&paths.timeline_file)?;
+        let mut report = RetentionReport::default();
+
+        let mut checkpoints = Vec::new();
+        if let Some(root) = &timeline.root_node {
+            collect_checkpoints(root, &mut checkpoints);
+        }
+        checkpoints.sort_by_key(|c| c.timestamp);
+
+        let mut usage = SessionUsage::measure(self, &paths, &checkpoints)?;
+        report.bytes_before = usage.total();
+
+        let mut to_prune = Vec::new();
+        let mut remaining = Vec::new();
+        let kept = policy.kept_by_rules(&timeline, Utc::now());
+        for checkpoint in checkpoints {
+            if policy.is_protected(&checkpoint, &timeline)
+                || !policy.has_keep_rules()
+                || kept.contains(&checkpoint.id)
+            {
+                remaining.push(checkpoint);
+            } else {
+                usage.release(&checkpoint.id);
+                to_prune.push(checkpoint.id);
+            }
+        }
+
+        if let Some(max_bytes) = policy.max_disk_bytes {
+            for checkpoint in remaining {
+                if usage.total() <= max_bytes {
+                    break;
+                }
+                if !policy.is_protected(&checkpoint, &timeline) {
+                    usage.release(&checkpoint.id);
+                    to_prune.push(checkpoint.id);
+                }
+            }
+        }
+
+        for checkpoint_id in &to_prune {
+            self.prune_checkpoint(&paths, &mut timeline, checkpoint_id)?;
+        }
+        if !to_prune.is_empty() {
+            self.save_timeline(&paths.timeline_file, &timeline)?;
+            match self.garbage_collect_content(project_id, session_id) {
+                Ok(gc_count) => {
+                    log::info!("Garbage collected {} orphaned content files", gc_count);
+                }
+                Err(e) => {
+                    log::warn!("Failed to garbage collect content: {}", e);
+                }
+            }
+        }
+
+        report.pruned_checkpoints = to_prune;
+        report.bytes_after = usage.total();
+        Ok(report)
+    }
+}
+
+/// Disk usage of a session, tracked as checkpoints are released
+struct SessionUsage {
+    checkpoint_bytes: HashMap<String, u64>,
+    checkpoint_hashes: HashMap<String, Vec<String>>,
+    hash_refs: HashMap<String, usize>,
+    /// Size of each content's own pool object
+    hash_bytes: HashMap<String, u64>,
+    /// Chunks each content lists, with their sizes; chunks can be shared
+    /// between contents so they are summed separately
+    hash_chunks: HashMap<String, HashMap<String, u64>>,
+}
+
+impl SessionUsage {
+    fn measure(
+        storage: &CheckpointStorage,
+        paths: &CheckpointPaths,
+        checkpoints: &[Checkpoint],
+    ) -> Result<Self> {
+        let mut usage = Self {
+            checkpoint_bytes: HashMap::new(),
+            checkpoint_hashes: HashMap::new(),
+            hash_refs: HashMap::new(),
+            hash_bytes: HashMap::new(),
+            hash_chunks: HashMap::new(),
+        };
+
+        for checkpoint in checkpoints {
+            let id = &checkpoint.id;
+            let bytes = checkpoint_metadata_bytes(paths, id);
+            usage.checkpoint_bytes.insert(id.clone(), bytes);
+
+            let mut hashes: Vec<String> = CheckpointStorage::read_checkpoint_refs(paths, id)?
+                .into_iter()
+                .filter(|r| !r.is_deleted)
+                .map(|r| r.hash)
+                .collect();
+            hashes.sort();
+            hashes.dedup();
+            for hash in &hashes {
+                *usage.hash_refs.entry(hash.clone()).or_insert(0) += 1;
+                if !usage.hash_bytes.contains_key(hash) {
+                    let (object_size, chunk_sizes) = storage.stored_object_parts(paths, hash);
+                    usage.hash_bytes.insert(hash.clone(), object_size);
+                    usage.hash_chunks.insert(hash.clone(), chunk_sizes);
+                }
+            }
+            usage.checkpoint_hashes.insert(id.clone(), hashes);
+        }
+
+        Ok(usage)
+    }
+
+    fn total(&self) -> u64 {
+        let mut total = self.checkpoint_bytes.values().sum::<u64>();
+        let mut chunks: HashMap<&str, u64> = HashMap::new();
+        for hash in self.hash_refs.keys() {
+            total += self.hash_bytes.get(hash).copied().unwrap_or(0);
+            if let Some(chunk_sizes) = self.hash_chunks.get(hash) {
+                chunks.extend(chunk_sizes.iter().map(|(h, size)| (h.as_str(), *size)));
+            }
+        }
+        total + chunks.values().sum::<u64>()
+    }
+
+    fn release(&mut self, checkpoint_id: &str) {
+        self.checkpoint_bytes.remove(checkpoint_id);
+        for hash in self
+            .checkpoint_hashes
+            .remove(checkpoint_id)
+            .unwrap_or_default()
+        {
+            if let Some(count) = self.hash_refs.get_mut(&hash) {
+                *count -= 1;
+                if *count == 0 {
+                    self.hash_refs.remove(&hash);
+                }
+            }
+        }
+    }
+}
+
+fn collect_checkpoints(node: &TimelineNode, checkpoints: &mut Vec<Checkpoint>) {
+    checkpoints.push(node.checkpoint.clone());
+    for child in &node.children {
+        collect_checkpoints(child, checkpoints);
+    }
+}
+
+/// Keep the last `count` checkpoints on the path from the root to every leaf
+fn keep_branch_tips<'a>(
+    node: &'a TimelineNode,
+    count: usize,
+    path: &mut Vec<&'a str>,
+    kept: &mut HashSet<String>,
+) {
+    path.push(&node.checkpoint.id);
+    if node.children.is_empty() {
+        let start = path.len().saturating_sub(count);
+        kept.extend(path[start..].iter().map(|id| id.to_string()));
+    }
+    for child in &node.children {
+        keep_branch_tips(child, count, path, kept);
+    }
+    path.pop();
+}
+
+/// The newest checkpoint in each time bucket since a cutoff
+fn newest_per_bucket<K: std::hash::Hash + Eq>(
+    checkpoints: &[Checkpoint],
+    since: DateTime<Utc>,
+    bucket: impl Fn(&DateTime<Utc>) -> K,
+) -> Vec<String> {
+    let mut newest: HashMap<K, &Checkpoint> = HashMap::new();
+    for checkpoint in checkpoints.iter().filter(|c| c.timestamp >= since) {
+        let entry = newest
+            .entry(bucket(&checkpoint.timestamp))
+            .or_insert(checkpoint);
+        if checkpoint.timestamp > entry.timestamp {
+            *entry = checkpoint;
+        }
+    }
+    newest.into_values().map(|c| c.id.clone()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checkpoint::manager::CheckpointManager;
+    use crate::checkpoint::storage::FileRef;
+    use std::fs;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    /// Rewrite checkpoints as older versions stored them, with only the
+    /// files that changed since their parent
+    fn make_changes_only(
+        storage: &CheckpointStorage,
+        paths: &CheckpointPaths,
+        timeline: &mut SessionTimeline,
+        ids: &[String],
+    ) {
+        let trees: Vec<_> = ids
+            .iter()
+            .map(|id| {
+                storage
+                    .load_checkpoint_tree_refs("proj", "sess", timeline, id)
+                    .unwrap()
+            })
+            .collect();
+
+        for (i, id) in ids.iter().enumerate().skip(1) {
+            let (parent, tree) = (&trees[i - 1], &trees[i]);
+            let mut changes: Vec<FileRef> = tree
+                .values()
+                .filter(|r| parent.get(&r.path).is_none_or(|p| p.hash != r.hash))
+                .cloned()
+                .collect();
+            changes.extend(
+                parent
+                    .values()
+                    .filter(|r| !tree.contains_key(&r.path))
+                    .map(|r| FileRef {
+                        is_deleted: true,
+                        ..r.clone()
+                    }),
+            );
+            storage
+                .write_checkpoint_manifest(paths, id, &changes)
+                .unwrap();
+
+            let node = timeline.find_checkpoint_mut(id).unwrap();
+            node.checkpoint.full_tree = false;
+            storage
+                .update_checkpoint_metadata(paths, &node.checkpoint)
+                .unwrap();
+        }
+        storage
+            .save_timeline(&paths.timeline_file, timeline)
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_pruning_keeps_trees_of_changes_only_children() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_path = temp_dir.path().join("project");
+        let claude_dir = temp_dir.path().join("claude");
+        fs::create_dir_all(&project_path).unwrap();
+        let manager = CheckpointManager::new(
+            "proj".to_string(),
+            "sess".to_string(),
+            project_path.clone(),
+            claude_dir.clone(),
+        )
+        .await
+        .unwrap();
+
+        let mut ids = Vec::new();
+        fs::write(project_path.join("a.txt"), b"one").unwrap();
+        fs::write(project_path.join("b.txt"), b"kept").unwrap();
+        ids.push(manager.create_checkpoint(None, None).await.unwrap());
+        fs::write(project_path.join("a.txt"), b"two").unwrap();
+        ids.push(manager.create_checkpoint(None, None).await.unwrap());
+        fs::remove_file(project_path.join("b.txt")).unwrap();
+        fs::write(project_path.join("c.txt"), b"new").unwrap();
+        ids.push(manager.create_checkpoint(None, None).await.unwrap());
+        let ids: Vec<String> = ids.into_iter().map(|r| r.checkpoint.id).collect();
+
+        let storage = CheckpointStorage::new(claude_dir.clone());
+        let paths = CheckpointPaths::new(&claude_dir, "proj", "sess");
+        let mut timeline = storage.load_timeline(&paths.timeline_file).unwrap();
+        make_changes_only(&storage, &paths, &mut timeline, &ids);
+        storage.rebuild_refcounts("proj", &paths).unwrap();
+
+        let policy = RetentionPolicy {
+            keep_last_per_branch: Some(1),
+            ..Default::default()
+        };
+        let report = storage
+            .apply_retention_policy("proj", "sess", &policy)
+            .unwrap();
+        assert_eq!(report.pruned_checkpoints, ids[..2].to_vec());
+
+        let timeline = storage.load_timeline(&paths.timeline_file).unwrap();
+        assert_eq!(timeline.checkpoint_ids(), vec![ids[2].clone()]);
+        let (checkpoint, _, _) = storage.load_checkpoint("proj", "sess", &ids[2]).unwrap();
+        assert!(checkpoint.full_tree);
+
+        let tree = storage
+            .load_checkpoint_tree("proj", "sess", &timeline, &ids[2])
+            .unwrap();
+        let contents: Vec<(PathBuf, Vec<u8>)> = tree
+            .into_iter()
+            .map(|(path, snapshot)| (path, snapshot.content))
+            .collect();
+        assert_eq!(
+            contents,
+            vec![
+                (PathBuf::from("a.txt"), b"two".to_vec()),
+                (PathBuf::from("c.txt"), b"new".to_vec()),
+            ]
+        );
+        assert!(storage
+            .verify_session("proj", "sess", false)
+            .unwrap()
+            .issues
+            .is_empty());
+    }
+}
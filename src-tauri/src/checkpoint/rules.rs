@@ -0,0 +1,181 @@
+use anyhow::{Context, Result};
+use glob::{MatchOptions, Pattern};
+use regex::Regex;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+use super::{SmartRule, SmartRules};
+
+/// Keys of a tool's input that name the file it touches
+const PATH_KEYS: &[&str] = &["file_path", "path", "notebook_path"];
+
+/// `SmartRules` with every pattern compiled
+#[derive(Debug)]
+pub struct CompiledSmartRules {
+    rules: Vec<CompiledRule>,
+}
+
+#[derive(Debug)]
+struct CompiledRule {
+    tools: Vec<Pattern>,
+    bash_commands: Vec<Regex>,
+    paths: Vec<Pattern>,
+    checkpoint: bool,
+}
+
+impl SmartRules {
+    /// Compile the rules, failing on the first invalid pattern
+    pub fn compile(&self) -> Result<CompiledSmartRules> {
+        let rules = self
+            .rules
+            .iter()
+            .enumerate()
+            .map(|(i, rule)| CompiledRule::new(rule).with_context(|| format!("Rule {}", i + 1)))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(CompiledSmartRules { rules })
+    }
+}
+
+impl CompiledSmartRules {
+    /// Whether a tool call should create a checkpoint
+    ///
+    /// Absolute paths in the tool input are made relative to the project
+    /// before they are matched.
+    pub fn should_checkpoint(&self, tool_name: &str, input: &Value, project_path: &Path) -> bool {
+        let command = input.get("command").and_then(|c| c.as_str());
+        let paths: Vec<PathBuf> = PATH_KEYS
+            .iter()
+            .filter_map(|key| input.get(*key).and_then(|p| p.as_str()))
+            .map(|p| {
+                let path = Path::new(p);
+                path.strip_prefix(project_path)
+                    .unwrap_or(path)
+                    .to_path_buf()
+            })
+            .collect();
+
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(tool_name, command, &paths))
+            .is_some_and(|rule| rule.checkpoint)
+    }
+}
+
+impl CompiledRule {
+    fn new(rule: &SmartRule) -> Result<Self> {
+        let globs = |patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|p| Pattern::new(p).with_context(|| format!("Invalid pattern {}", p)))
+                .collect::<Result<Vec<_>>>()
+        };
+        let bash_commands = rule
+            .bash_commands
+            .iter()
+            .map(|r| Regex::new(r).with_context(|| format!("Invalid command regex {}", r)))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            tools: globs(&rule.tools)?,
+            bash_commands,
+            paths: globs(&rule.paths)?,
+            checkpoint: rule.checkpoint,
+        })
+    }
+
+    fn matches(&self, tool_name: &str, command: Option<&str>, paths: &[PathBuf]) -> bool {
+        let tool_options = MatchOptions {
+            case_sensitive: false,
+            ..Default::default()
+        };
+        let path_options = MatchOptions {
+            require_literal_separator: true,
+            ..Default::default()
+        };
+
+        let tool_matches = self.tools.is_empty()
+            || self
+                .tools
+                .iter()
+                .any(|p| p.matches_with(tool_name, tool_options));
+        let command_matches = self.bash_commands.is_empty()
+            || command.is_some_and(|c| self.bash_commands.iter().any(|r| r.is_match(c)));
+        // A pattern naming a directory selects everything below it
+        let path_matches = self.paths.is_empty()
+            || paths.iter().any(|path| {
+                path.ancestors()
+                    .filter(|p| !p.as_os_str().is_empty())
+                    .any(|p| {
+                        self.paths
+                            .iter()
+                            .any(|m| m.matches_path_with(p, path_options))
+                    })
+            });
+
+        tool_matches && command_matches && path_matches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_default_rules() {
+        let rules = SmartRules::default().compile().unwrap();
+        let project = Path::new("/project");
+
+        assert!(rules.should_checkpoint("Edit", &json!({"file_path": "/project/a.rs"}), project));
+        assert!(rules.should_checkpoint("mcp__fs__write_file", &json!({}), project));
+        assert!(rules.should_checkpoint("Bash", &json!({"command": "cargo fmt"}), project));
+        assert!(!rules.should_checkpoint("Bash", &json!({"command": "git status"}), project));
+        assert!(rules.should_checkpoint("Bash", &json!({"command": "ls > out"}), project));
+        assert!(!rules.should_checkpoint("Read", &json!({"file_path": "a.rs"}), project));
+    }
+
+    #[test]
+    fn test_first_matching_rule_decides() {
+        let rules = SmartRules {
+            rules: vec![
+                SmartRule {
+                    tools: vec!["write".to_string()],
+                    bash_commands: Vec::new(),
+                    paths: vec!["target".to_string(), "**/*.log".to_string()],
+                    checkpoint: false,
+                    description: None,
+                },
+                SmartRule {
+                    tools: Vec::new(),
+                    bash_commands: Vec::new(),
+                    paths: Vec::new(),
+                    checkpoint: true,
+                    description: None,
+                },
+            ],
+        }
+        .compile()
+        .unwrap();
+        let project = Path::new("/project");
+
+        assert!(!rules.should_checkpoint(
+            "Write",
+            &json!({"file_path": "/project/target/debug/x"}),
+            project
+        ));
+        assert!(!rules.should_checkpoint("Write", &json!({"file_path": "logs/a.log"}), project));
+        assert!(rules.should_checkpoint("Write", &json!({"file_path": "src/a.rs"}), project));
+        assert!(rules.should_checkpoint("AnyTool", &json!({}), project));
+
+        let invalid = SmartRules {
+            rules: vec![SmartRule {
+                tools: Vec::new(),
+                bash_commands: vec!["(".to_string()],
+                paths: Vec::new(),
+                checkpoint: true,
+                description: None,
+            }],
+        };
+        assert!(invalid.compile().is_err());
+    }
+}
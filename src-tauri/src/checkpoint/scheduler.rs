@@ -0,0 +1,102 @@
+use std::time::{Duration, Instant};
+
+use super::AutoCheckpointSchedule;
+
+/// Automatic checkpoint requests waiting for their schedule
+#[derive(Debug, Default)]
+pub struct AutoCheckpointScheduler {
+    /// Whether the strategy asked for a checkpoint that has not been taken
+    pending: bool,
+    /// When the last automatic checkpoint was taken
+    last_checkpoint: Option<Instant>,
+}
+
+impl AutoCheckpointScheduler {
+    /// Record that the strategy asked for a checkpoint
+    pub fn request(&mut self) {
+        self.pending = true;
+    }
+
+    /// Drop a pending request, e.g. because nothing changed
+    pub fn cancel(&mut self) {
+        self.pending = false;
+    }
+
+    /// Whether a pending request may be acted on now
+    pub fn is_due(
+        &self,
+        schedule: &AutoCheckpointSchedule,
+        end_of_turn: bool,
+        now: Instant,
+    ) -> bool {
+        let debounced = self.last_checkpoint.is_some_and(|last| {
+            now.saturating_duration_since(last) < Duration::from_secs(schedule.debounce_secs)
+        });
+        self.pending && (end_of_turn || !schedule.at_end_of_turn) && !debounced
+    }
+
+    /// Record an automatic checkpoint, starting a new debounce window
+    pub fn record_checkpoint(&mut self, now: Instant) {
+        self.pending = false;
+        self.last_checkpoint = Some(now);
+    }
+}
+
+/// Whether a session message ends the assistant's turn
+///
+/// That is the final `result` message of a run, or an assistant message that
+/// stopped without asking for a tool.
+pub fn is_end_of_turn(message: &str) -> bool {
+    let Ok(msg) = serde_json::from_str::<serde_json::Value>(message) else {
+        return false;
+    };
+
+    match msg.get("type").and_then(|t| t.as_str()) {
+        Some("result") => true,
+        Some("assistant") => {
+            msg.get("message")
+                .and_then(|m| m.get("stop_reason"))
+                .and_then(|r| r.as_str())
+                == Some("end_turn")
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_requests_wait_for_turn_end_and_debounce() {
+        let schedule = AutoCheckpointSchedule {
+            debounce_secs: 60,
+            min_changed_bytes: 0,
+            at_end_of_turn: true,
+        };
+        let start = Instant::now();
+        let mut scheduler = AutoCheckpointScheduler::default();
+        assert!(!scheduler.is_due(&schedule, true, start));
+
+        scheduler.request();
+        assert!(!scheduler.is_due(&schedule, false, start));
+        assert!(scheduler.is_due(&schedule, true, start));
+
+        scheduler.record_checkpoint(start);
+        scheduler.request();
+        assert!(!scheduler.is_due(&schedule, true, start + Duration::from_secs(30)));
+        assert!(scheduler.is_due(&schedule, true, start + Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn test_end_of_turn_messages() {
+        assert!(is_end_of_turn(r#"{"type":"result","subtype":"success"}"#));
+        assert!(is_end_of_turn(
+            r#"{"type":"assistant","message":{"stop_reason":"end_turn"}}"#
+        ));
+        assert!(!is_end_of_turn(
+            r#"{"type":"assistant","message":{"stop_reason":"tool_use"}}"#
+        ));
+        assert!(!is_end_of_turn("not json"));
+    }
+}
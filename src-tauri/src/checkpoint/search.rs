@@ -0,0 +1,407 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use super::{
+    storage::{CheckpointStorage, FileRef},
+    Checkpoint, CheckpointPaths, EntryKind,
+};
+
+/// Characters of context kept on each side of a match in a snippet
+const SNIPPET_CONTEXT: usize = 40;
+
+/// Files larger than this are left out of content searches
+const MAX_SEARCHED_FILE_SIZE: u64 = 4 * 1024 * 1024;
+
+/// Options for searching checkpoints
+#[derive(Debug, Clone)]
+pub struct SearchOptions {
+    /// Only search this session instead of every session of the project
+    pub session_id: Option<String>,
+    /// Also search the text each checkpoint added to the files it changed
+    pub include_contents: bool,
+    /// Maximum number of results returned
+    pub limit: usize,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            session_id: None,
+            include_contents: false,
+            limit: 50,
+        }
+    }
+}
+
+/// A checkpoint matching a search
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResult {
+    pub checkpoint_id: String,
+    pub session_id: String,
+    pub timestamp: DateTime<Utc>,
+    pub description: Option<String>,
+    /// Sum of the scores of the matches; results are ranked by it
+    pub score: u32,
+    /// Where the checkpoint matched, best first
+    pub matches: Vec<SearchMatch>,
+}
+
+/// One place a checkpoint matched a search
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchMatch {
+    pub field: SearchField,
+    /// File the match is in, for path and content matches
+    pub path: Option<PathBuf>,
+    /// 1-based line of a content match
+    pub line: Option<usize>,
+    /// Text around the match, on one line
+    pub snippet: String,
+    pub score: u32,
+}
+
+/// Part of a checkpoint a search matched
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchField {
+    Description,
+    Prompt,
+    /// Path of a file the checkpoint added, modified or deleted
+    Path,
+    /// Text the checkpoint added to a file
+    Content,
+}
+
+impl SearchField {
+    /// Score of a match on the whole query; matching every word of the
+    /// query, but not the query itself, scores half
+    fn weight(self) -> u32 {
+        match self {
+            SearchField::Description => 8,
+            SearchField::Prompt => 6,
+            SearchField::Path => 4,
+            SearchField::Content => 4,
+        }
+    }
+}
+
+impl CheckpointStorage {
+    /// Search the checkpoints of a project
+    ///
+    /// Matching ignores ASCII case. Descriptions, prompts and the paths of
+    /// changed files match the whole query or, with a lower score, every word
+    /// of it in any order. Content only matches when a checkpoint adds
+    /// occurrences of the whole query to a file, so a search for a definition
+    /// finds the checkpoint that introduced it rather than every later one.
+    pub fn search_checkpoints(
+        &self,
+        project_id: &str,
+        query: &str,
+        options: &SearchOptions,
+    ) -> Result<Vec<SearchResult>> {
+        let Some(query) = Query::new(query) else {
+            return Ok(Vec::new());
+        };
+        let session_ids = match &options.session_id {
+            Some(session_id) => vec![session_id.clone()],
+            None => self.list_session_ids(project_id)?,
+        };
+
+        let mut results = Vec::new();
+        for session_id in &session_ids {
+            let paths = CheckpointPaths::new(&self.claude_dir, project_id, session_id);
+            if !paths.timeline_file.exists() {
+                continue;
+            }
+            let timeline = self.load_timeline(&paths.timeline_file)?;
+
+            // Parents come before children, so each parent's tree is cached
+            // by the time its children are compared against it. A tree is
+            // dropped once all of its children have been visited.
+            let mut trees: HashMap<String, (BTreeMap<PathBuf, FileRef>, usize)> = HashMap::new();
+            for checkpoint_id in timeline.checkpoint_ids() {
+                let Some(node) = timeline.find_checkpoint(&checkpoint_id) else {
+                    continue;
+                };
+                let checkpoint = &node.checkpoint;
+                let tree = self.load_checkpoint_tree_refs(
+                    project_id,
+                    session_id,
+                    &timeline,
+                    &checkpoint_id,
+                )?;
+                let parent_tree = checkpoint
+                    .parent_checkpoint_id
+                    .as_ref()
+                    .and_then(|id| trees.get(id))
+                    .map(|(tree, _)| tree);
+
+                let mut matches = self.match_checkpoint(
+                    &paths,
+                    checkpoint,
+                    &tree,
+                    parent_tree,
+                    &query,
+                    options.include_contents,
+                );
+                if !matches.is_empty() {
+                    matches.sort_by_key(|m| Reverse(m.score));
+                    results.push(SearchResult {
+                        checkpoint_id: checkpoint_id.clone(),
+                        session_id: session_id.clone(),
+                        timestamp: checkpoint.timestamp,
+                        description: checkpoint.description.clone(),
+                        score: matches.iter().map(|m| m.score).sum(),
+                        matches,
+                    });
+                }
+
+                if let Some(parent_id) = &checkpoint.parent_checkpoint_id {
+                    if let Some((_, unvisited)) = trees.get_mut(parent_id) {
+                        *unvisited -= 1;
+                        if *unvisited == 0 {
+                            trees.remove(parent_id);
+                        }
+                    }
+                }
+                if !node.children.is_empty() {
+                    trees.insert(checkpoint_id, (tree, node.children.len()));
+                }
+            }
+        }
+
+        results.sort_by(|a, b| {
+            b.score
+                .cmp(&a.score)
+                .then_with(|| b.timestamp.cmp(&a.timestamp))
+        });
+        results.truncate(options.limit);
+        Ok(results)
+    }
+
+    fn match_checkpoint(
+        &self,
+        paths: &CheckpointPaths,
+        checkpoint: &Checkpoint,
+        tree: &BTreeMap<PathBuf, FileRef>,
+        parent_tree: Option<&BTreeMap<PathBuf, FileRef>>,
+        query: &Query,
+        include_contents: bool,
+    ) -> Vec<SearchMatch> {
+        let mut matches = Vec::new();
+        if let Some(description) = &checkpoint.description {
+            matches.extend(query.match_text(SearchField::Description, description));
+        }
+        matches.extend(query.match_text(SearchField::Prompt, &checkpoint.metadata.user_prompt));
+
+        let empty = BTreeMap::new();
+        let parent_tree = parent_tree.unwrap_or(&empty);
+        let changed = tree
+            .iter()
+            .filter(|(path, file_ref)| {
+                parent_tree
+                    .get(*path)
+                    .is_none_or(|parent| parent.hash != file_ref.hash)
+            })
+            .map(|(path, file_ref)| (path, Some(file_ref)))
+            .chain(
+                parent_tree
+                    .keys()
+                    .filter(|path| !tree.contains_key(*path))
+                    .map(|path| (path, None)),
+            );
+
+        for (path, file_ref) in changed {
+            if let Some(mut found) = query.match_text(SearchField::Path, &path.to_string_lossy()) {
+                found.path = Some(path.clone());
+                matches.push(found);
+            }
+
+            let Some(file_ref) = file_ref else {
+                continue;
+            };
+            if include_contents
+                && file_ref.kind == EntryKind::File
+                && file_ref.size <= MAX_SEARCHED_FILE_SIZE
+            {
+                let old_hash = parent_tree.get(path).map(|r| r.hash.as_str());
+                matches.extend(self.match_content(paths, path, &file_ref.hash, old_hash, query));
+            }
+        }
+
+        matches
+    }
+
+    /// Match text a checkpoint added to a file
+    ///
+    /// The snippet shows the first matching line that is not in the old
+    /// version. Binary files never match.
+    fn match_content(
+        &self,
+        paths: &CheckpointPaths,
+        path: &Path,
+        hash: &str,
+        old_hash: Option<&str>,
+        query: &Query,
+    ) -> Option<SearchMatch> {
+        let read_text = |hash: &str| match self.read_content_object(paths, hash) {
+            Ok(content) => String::from_utf8(content).ok(),
+            Err(e) => {
+                log::warn!("Skipping {} in checkpoint search: {}", path.display(), e);
+                None
+            }
+        };
+
+        let new = read_text(hash)?;
+        let new_lower = new.to_ascii_lowercase();
+        let count = new_lower.matches(query.phrase.as_str()).count();
+        if count == 0 {
+            return None;
+        }
+        let old = old_hash.and_then(read_text).unwrap_or_default();
+        let old_lower = old.to_ascii_lowercase();
+        if count <= old_lower.matches(query.phrase.as_str()).count() {
+            return None;
+        }
+
+        let old_lines: HashSet<&str> = old.lines().collect();
+        let mut found = None;
+        for (index, line) in new.lines().enumerate() {
+            let Some(start) = line.to_ascii_lowercase().find(&query.phrase) else {
+                continue;
+            };
+            found = Some((index, line, start));
+            if !old_lines.contains(line) {
+                break;
+            }
+        }
+        let (index, line, start) = found?;
+
+        Some(SearchMatch {
+            field: SearchField::Content,
+            path: Some(path.to_path_buf()),
+            line: Some(index + 1),
+            snippet: snippet(line, start, query.phrase.len()),
+            score: SearchField::Content.weight(),
+        })
+    }
+}
+
+/// A search query, lowercased
+struct Query {
+    phrase: String,
+    words: Vec<String>,
+}
+
+impl Query {
+    fn new(text: &str) -> Option<Self> {
+        let phrase = text.trim().to_ascii_lowercase();
+        if phrase.is_empty() {
+            return None;
+        }
+        let mut words: Vec<String> = phrase.split_whitespace().map(str::to_string).collect();
+        words.sort();
+        words.dedup();
+        Some(Self { phrase, words })
+    }
+
+    /// Match a metadata field or path against the whole query, then against
+    /// every word of it
+    fn match_text(&self, field: SearchField, text: &str) -> Option<SearchMatch> {
+        let lower = text.to_ascii_lowercase();
+        let (start, len, score) = match lower.find(&self.phrase) {
+            Some(start) => (start, self.phrase.len(), field.weight()),
+            None => {
+                let mut first: Option<(usize, usize)> = None;
+                for word in &self.words {
+                    let start = lower.find(word.as_str())?;
+                    if first.is_none_or(|(s, _)| start < s) {
+                        first = Some((start, word.len()));
+                    }
+                }
+                let (start, len) = first?;
+                (start, len, field.weight() / 2)
+            }
+        };
+
+        Some(SearchMatch {
+            field,
+            path: None,
+            line: None,
+            snippet: snippet(text, start, len),
+            score,
+        })
+    }
+}
+
+/// Text around a match at `start..start + len`, cut to `SNIPPET_CONTEXT`
+/// characters on each side with whitespace collapsed
+fn snippet(text: &str, start: usize, len: usize) -> String {
+    let end = start + len;
+    let from = text[..start]
+        .char_indices()
+        .rev()
+        .nth(SNIPPET_CONTEXT - 1)
+        .map_or(0, |(i, _)| i);
+    let to = text[end..]
+        .char_indices()
+        .nth(SNIPPET_CONTEXT)
+        .map_or(text.len(), |(i, _)| end + i);
+
+    let mut snippet = text[from..to]
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    if from > 0 {
+        snippet.insert(0, '…');
+    }
+    if to < text.len() {
+        snippet.push('…');
+    }
+    snippet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_match_text() {
+        let query = Query::new("  Parse Config ").unwrap();
+        assert!(Query::new("   ").is_none());
+
+        let found = query
+            .match_text(SearchField::Prompt, "Please fix how we parse config files")
+            .unwrap();
+        assert_eq!(found.score, SearchField::Prompt.weight());
+        assert_eq!(found.snippet, "Please fix how we parse config files");
+
+        // Every word, in any order, scores half
+        let found = query
+            .match_text(SearchField::Prompt, "the config is hard to parse")
+            .unwrap();
+        assert_eq!(found.score, SearchField::Prompt.weight() / 2);
+        assert!(found.snippet.starts_with("the config"));
+
+        assert!(query
+            .match_text(SearchField::Prompt, "only config here")
+            .is_none());
+    }
+
+    #[test]
+    fn test_snippet() {
+        let text = format!("{}needle\n\n{}", "é".repeat(60), "b ".repeat(60));
+        let start = text.find("needle").unwrap();
+        let cut = snippet(&text, start, "needle".len());
+
+        assert!(cut.starts_with('…') && cut.ends_with('…'));
+        assert!(cut.contains(&format!("{}needle b b", "é".repeat(SNIPPET_CONTEXT))));
+        assert!(!cut.contains('\n'));
+        assert_eq!(snippet("short", 0, 5), "short");
+    }
+}
@@ -5,6 +5,7 @@ use std::sync::Arc;
 use tokio::sync::RwLock;
 
 use super::manager::CheckpointManager;
+use super::watcher::ProjectWatcher;
 
 /// Manages checkpoint managers for active sessions
 ///
@@ -18,6 +19,8 @@ pub struct CheckpointState {
     managers: Arc<RwLock<HashMap<String, Arc<CheckpointManager>>>>,
     /// The Claude directory path for consistent access
     claude_dir: Arc<RwLock<Option<PathBuf>>>,
+    /// Map of session_id to the watcher keeping that manager's file tracker live
+    watchers: Arc<RwLock<HashMap<String, ProjectWatcher>>>,
 }
 
 impl CheckpointState {
@@ -26,6 +29,7 @@ impl CheckpointState {
         Self {
             managers: Arc::new(RwLock::new(HashMap::new())),
             claude_dir: Arc::new(RwLock::new(None)),
+            watchers: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 
@@ -76,7 +80,15 @@ impl CheckpointState {
                 .await?;
 
         let manager_arc = Arc::new(manager);
-        managers.insert(session_id, Arc::clone(&manager_arc));
+        managers.insert(session_id.clone(), Arc::clone(&manager_arc));
+
+        // Without a watcher the manager falls back to scanning the whole project
+        match ProjectWatcher::start(Arc::clone(&manager_arc)) {
+            Ok(watcher) => {
+                self.watchers.write().await.insert(session_id, watcher);
+            }
+            Err(e) => log::warn!("Checkpoint file watcher unavailable: {:#}", e),
+        }
 
         Ok(manager_arc)
     }
@@ -95,6 +107,7 @@ impl CheckpointState {
     /// This should be called when a session ends to free resources
     pub async fn remove_manager(&self, session_id: &str) -> Option<Arc<CheckpointManager>> {
         let mut managers = self.managers.write().await;
+        self.watchers.write().await.remove(session_id);
         managers.remove(session_id)
     }
 
@@ -104,6 +117,7 @@ impl CheckpointState {
     #[allow(dead_code)]
     pub async fn clear_all(&self) {
         let mut managers = self.managers.write().await;
+        self.watchers.write().await.clear();
         managers.clear();
     }
 
@@ -0,0 +1,282 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+use super::{
+    storage::{checkpoint_metadata_bytes, CheckpointStorage},
+    CheckpointPaths,
+};
+
+/// Number of files listed in `StorageReport::largest_files`
+const LARGEST_FILES_LIMIT: usize = 10;
+
+/// Storage used by a session or by every session of a project
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageReport {
+    /// Sessions the report covers
+    pub session_ids: Vec<String>,
+    /// Usage of each checkpoint, oldest first
+    pub checkpoints: Vec<CheckpointUsage>,
+    /// Bytes on disk: checkpoint files plus every pool object they reference, once
+    pub total_bytes: u64,
+    /// Size of every stored file if nothing were shared or compressed
+    pub logical_bytes: u64,
+    /// Uncompressed size of the distinct file contents
+    pub unique_bytes: u64,
+    /// Compressed size of the distinct file contents in the pool, counting
+    /// chunks shared by several contents once
+    pub stored_bytes: u64,
+    /// How many times over the pool avoids storing the same content, `logical / unique`
+    pub dedupe_ratio: f64,
+    /// How much compression shrinks the pool, `unique / stored`
+    pub compression_ratio: f64,
+    /// Largest distinct file contents, biggest first
+    pub largest_files: Vec<LargeFile>,
+}
+
+/// Disk usage of a single checkpoint
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckpointUsage {
+    pub checkpoint_id: String,
+    pub session_id: String,
+    pub timestamp: DateTime<Utc>,
+    pub description: Option<String>,
+    /// Number of file references the checkpoint holds
+    pub file_count: usize,
+    /// Bytes of the checkpoint's metadata, messages and file references
+    pub metadata_bytes: u64,
+    /// Compressed bytes of the pool content the checkpoint references
+    pub content_bytes: u64,
+    /// Bytes freed by pruning the checkpoint: its own files plus pool
+    /// content no other checkpoint in the project references
+    pub prune_savings_bytes: u64,
+}
+
+/// A file content in the pool
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LargeFile {
+    /// Path of the first snapshot found with this content
+    pub path: PathBuf,
+    pub hash: String,
+    /// Uncompressed size in bytes
+    pub size: u64,
+    /// Compressed size in the pool, counting every chunk of chunked content
+    pub stored_bytes: u64,
+    /// Number of checkpoints referencing the content
+    pub checkpoint_count: usize,
+}
+
+impl CheckpointStorage {
+    /// Report the storage used by one session
+    pub fn session_storage_report(
+        &self,
+        project_id: &str,
+        session_id: &str,
+    ) -> Result<StorageReport> {
+        self.storage_report(project_id, vec![session_id.to_string()])
+    }
+
+    /// Report the storage used by every session of a project
+    pub fn project_storage_report(&self, project_id: &str) -> Result<StorageReport> {
+        let session_ids = self.list_session_ids(project_id)?;
+        self.storage_report(project_id, session_ids)
+    }
+
+    fn storage_report(&self, project_id: &str, session_ids: Vec<String>) -> Result<StorageReport> {
+        // Pool objects are shared by the whole project, so savings are
+        // judged against references from every session
+        let project_refs = self.project_reference_counts(project_id)?;
+
+        let mut report = StorageReport::default();
+        let mut contents: HashMap<String, LargeFile> = HashMap::new();
+        let mut counted_chunks: HashSet<String> = HashSet::new();
+
+        for session_id in &session_ids {
+            let paths = CheckpointPaths::new(&self.claude_dir, project_id, session_id);
+            if !paths.timeline_file.exists() {
+                continue;
+            }
+            let timeline = self.load_timeline(&paths.timeline_file)?;
+
+            for checkpoint_id in timeline.checkpoint_ids() {
+                let Some(node) = timeline.find_checkpoint(&checkpoint_id) else {
+                    continue;
+                };
+                let file_refs = Self::read_checkpoint_refs(&paths, &checkpoint_id)?;
+
+                let mut ref_counts: HashMap<&str, u64> = HashMap::new();
+                for file_ref in file_refs.iter().filter(|r| !r.is_deleted) {
+                    report.logical_bytes += file_ref.size;
+                    *ref_counts.entry(&file_ref.hash).or_insert(0) += 1;
+                    if contents.contains_key(&file_ref.hash) {
+                        continue;
+                    }
+
+                    let (object_size, chunk_sizes) =
+                        self.stored_object_parts(&paths, &file_ref.hash);
+                    report.stored_bytes += object_size;
+                    for (chunk_hash, size) in &chunk_sizes {
+                        if counted_chunks.insert(chunk_hash.clone()) {
+                            report.stored_bytes += size;
+                        }
+                    }
+                    contents.insert(
+                        file_ref.hash.clone(),
+                        LargeFile {
+                            path: file_ref.path.clone(),
+                            hash: file_ref.hash.clone(),
+                            size: file_ref.size,
+                            stored_bytes: object_size + chunk_sizes.values().sum::<u64>(),
+                            checkpoint_count: 0,
+                        },
+                    );
+                }
+
+                let metadata_bytes = checkpoint_metadata_bytes(&paths, &checkpoint_id);
+                let mut content_bytes = 0;
+                let mut exclusive_bytes = 0;
+                for (hash, count) in ref_counts {
+                    let Some(content) = contents.get_mut(hash) else {
+                        continue;
+                    };
+                    content.checkpoint_count += 1;
+                    content_bytes += content.stored_bytes;
+                    if project_refs.get(hash).copied().unwrap_or(0) <= count {
+                        exclusive_bytes += content.stored_bytes;
+                    }
+                }
+
+                report.total_bytes += metadata_bytes;
+                report.checkpoints.push(CheckpointUsage {
+                    checkpoint_id,
+                    session_id: session_id.clone(),
+                    timestamp: node.checkpoint.timestamp,
+                    description: node.checkpoint.description.clone(),
+                    file_count: file_refs.len(),
+                    metadata_bytes,
+                    content_bytes,
+                    prune_savings_bytes: metadata_bytes + exclusive_bytes,
+                });
+            }
+        }
+
+        report.unique_bytes = contents.values().map(|c| c.size).sum();
+        report.total_bytes += report.stored_bytes;
+        report.dedupe_ratio = ratio(report.logical_bytes, report.unique_bytes);
+        report.compression_ratio = ratio(report.unique_bytes, report.stored_bytes);
+
+        let mut largest: Vec<LargeFile> = contents.into_values().collect();
+        largest.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+        largest.truncate(LARGEST_FILES_LIMIT);
+        report.largest_files = largest;
+
+        report.checkpoints.sort_by_key(|c| c.timestamp);
+        report.session_ids = session_ids;
+        Ok(report)
+    }
+}
+
+/// `numerator / denominator`, or 1 when there is nothing to compare
+fn ratio(numerator: u64, denominator: u64) -> f64 {
+    if denominator == 0 {
+        1.0
+    } else {
+        numerator as f64 / denominator as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checkpoint::manager::CheckpointManager;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_storage_report_counts_shared_and_exclusive_content() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_path = temp_dir.path().join("project");
+        let claude_dir = temp_dir.path().join("claude");
+        fs::create_dir_all(&project_path).unwrap();
+        let manager = CheckpointManager::new(
+            "proj".to_string(),
+            "sess".to_string(),
+            project_path.clone(),
+            claude_dir.clone(),
+        )
+        .await
+        .unwrap();
+
+        fs::write(project_path.join("big.txt"), "a".repeat(1000)).unwrap();
+        fs::write(project_path.join("small.txt"), b"shared").unwrap();
+        let first = manager
+            .create_checkpoint(None, None)
+            .await
+            .unwrap()
+            .checkpoint;
+        fs::write(project_path.join("new.txt"), b"new").unwrap();
+        let second = manager
+            .create_checkpoint(None, None)
+            .await
+            .unwrap()
+            .checkpoint;
+
+        let storage = CheckpointStorage::new(claude_dir.clone());
+        let report = storage.session_storage_report("proj", "sess").unwrap();
+
+        assert_eq!(report.logical_bytes, 1006 + 1009);
+        assert_eq!(report.unique_bytes, 1009);
+        assert_eq!(report.dedupe_ratio, 2015.0 / 1009.0);
+        assert!(report.compression_ratio > 1.0);
+        let metadata_bytes: u64 = report.checkpoints.iter().map(|c| c.metadata_bytes).sum();
+        assert_eq!(report.total_bytes, metadata_bytes + report.stored_bytes);
+
+        let big = &report.largest_files[0];
+        assert_eq!(big.path, PathBuf::from("big.txt"));
+        assert_eq!(big.checkpoint_count, 2);
+        assert_eq!(report.largest_files.len(), 3);
+
+        // Only content no other checkpoint uses is freed by pruning
+        let ids: Vec<&str> = report
+            .checkpoints
+            .iter()
+            .map(|c| c.checkpoint_id.as_str())
+            .collect();
+        assert_eq!(ids, vec![first.id.as_str(), second.id.as_str()]);
+        let (first_usage, second_usage) = (&report.checkpoints[0], &report.checkpoints[1]);
+        assert_eq!(first_usage.prune_savings_bytes, first_usage.metadata_bytes);
+        let new_file = report
+            .largest_files
+            .iter()
+            .find(|f| f.path == std::path::Path::new("new.txt"))
+            .unwrap();
+        assert_eq!(
+            second_usage.prune_savings_bytes,
+            second_usage.metadata_bytes + new_file.stored_bytes
+        );
+
+        // Another session sharing the pool is included in the project report
+        CheckpointManager::new(
+            "proj".to_string(),
+            "other".to_string(),
+            project_path,
+            claude_dir,
+        )
+        .await
+        .unwrap()
+        .create_checkpoint(None, None)
+        .await
+        .unwrap();
+        let project_report = storage.project_storage_report("proj").unwrap();
+        let mut session_ids = project_report.session_ids.clone();
+        session_ids.sort();
+        assert_eq!(session_ids, vec!["other".to_string(), "sess".to_string()]);
+        assert_eq!(project_report.unique_bytes, report.unique_bytes);
+        assert_eq!(project_report.checkpoints.len(), 3);
+    }
+}
@@ -1,18 +1,86 @@
 use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs;
+use std::io::{BufReader, Read, Write};
 use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, RwLock};
 use uuid::Uuid;
+use walkdir::WalkDir;
 use zstd::stream::{decode_all, encode_all};
 
 use super::{
-    Checkpoint, CheckpointPaths, CheckpointResult, FileSnapshot, SessionTimeline, TimelineNode,
+    chunks::{self, ChunkEntry},
+    Checkpoint, CheckpointPaths, CheckpointResult, ContentSource, EntryKind, FileSnapshot,
+    SessionTimeline, SmartRules, TimelineNode, TrackerState,
 };
 
+/// Content larger than this is stored as content-defined chunks
+pub const CHUNKED_OBJECT_THRESHOLD: usize = 1024 * 1024;
+
+/// Serialises writes to the shared content pool and its reference counts
+static POOL_LOCK: Mutex<()> = Mutex::new(());
+
+/// Lock the shared content pool of every project
+pub fn lock_pool() -> MutexGuard<'static, ()> {
+    POOL_LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// When checkpoint files are flushed to stable storage
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FsyncPolicy {
+    /// Never fsync; a crash may lose recently written checkpoints
+    Never,
+    /// Fsync only the journal and timeline that commit a checkpoint
+    #[default]
+    Commit,
+    /// Fsync every file and directory before a checkpoint is committed
+    Always,
+}
+
+/// On-disk reference from a checkpoint to a file's content in the pool
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileRef {
+    /// Relative path from project root
+    pub path: PathBuf,
+    /// Hash of the content in the pool
+    pub hash: String,
+    /// Whether the file was deleted at this checkpoint
+    #[serde(default)]
+    pub is_deleted: bool,
+    /// File permissions (Unix mode)
+    #[serde(default)]
+    pub permissions: Option<u32>,
+    /// File size in bytes
+    #[serde(default)]
+    pub size: u64,
+    /// Whether the path is a file, symlink or empty directory
+    #[serde(default)]
+    pub kind: EntryKind,
+}
+
+/// Content written to the pool by `save_chunked_content`
+struct StoredContent {
+    hash: String,
+    size: u64,
+    bytes_added: u64,
+}
+
+/// Journal entry for a checkpoint whose write has not been committed yet
+#[derive(Debug, Serialize, Deserialize)]
+struct JournalEntry {
+    checkpoint_id: String,
+    started_at: DateTime<Utc>,
+}
+
 /// Manages checkpoint storage operations
 pub struct CheckpointStorage {
     pub claude_dir: PathBuf,
     compression_level: i32,
+    fsync_policy: RwLock<FsyncPolicy>,
 }
 
 impl CheckpointStorage {
@@ -21,9 +89,19 @@ impl CheckpointStorage {
         Self {
             claude_dir,
             compression_level: 3, // Default zstd compression level
+            fsync_policy: RwLock::new(FsyncPolicy::default()),
         }
     }
 
+    /// Use a different fsync policy for later checkpoint writes
+    pub fn set_fsync_policy(&self, fsync_policy: FsyncPolicy) {
+        *self.fsync_policy.write().unwrap_or_else(|e| e.into_inner()) = fsync_policy;
+    }
+
+    fn fsync_policy(&self) -> FsyncPolicy {
+        *self.fsync_policy.read().unwrap_or_else(|e| e.into_inner())
+    }
+
     /// Initialize checkpoint storage for a session
     pub fn init_storage(&self, project_id: &str, session_id: &str) -> Result<()> {
         let paths = CheckpointPaths::new(&self.claude_dir, project_id, session_id);
@@ -32,6 +110,14 @@ impl CheckpointStorage {
         fs::create_dir_all(&paths.checkpoints_dir)
             .context("Failed to create checkpoints directory")?;
         fs::create_dir_all(&paths.files_dir).context("Failed to create files directory")?;
+        fs::create_dir_all(paths.content_pool_dir.join("objects"))
+            .context("Failed to create content pool directory")?;
+
+        // Sessions created before the shared pool keep content in their own directory
+        self.migrate_legacy_pool(project_id, &paths)?;
+
+        // Roll back checkpoints whose write was interrupted by a crash
+        self.recover_incomplete_checkpoints(project_id, &paths)?;
 
         // Initialize empty timeline if it doesn't exist
         if !paths.timeline_file.exists() {
@@ -43,104 +129,290 @@ impl CheckpointStorage {
     }
 
     /// Save a checkpoint to disk
+    ///
+    /// `unchanged_files` reference content already in the pool for files that
+    /// did not change, so a checkpoint marked `full_tree` lists every file.
+    /// If any file can't be stored or its content has left the pool, the
+    /// whole checkpoint fails rather than recording an incomplete tree.
+    ///
+    /// The write is recorded in the session journal first and only committed
+    /// by the final timeline update. If anything fails before that, the
+    /// partial checkpoint is rolled back; if the process dies, the next
+    /// `init_storage` rolls it back instead.
     pub fn save_checkpoint(
         &self,
         project_id: &str,
         session_id: &str,
         checkpoint: &Checkpoint,
         file_snapshots: Vec<FileSnapshot>,
+        unchanged_files: Vec<FileRef>,
         messages: &str, // JSONL content up to checkpoint
+    ) -> Result<CheckpointResult> {
+        self.save_checkpoint_streaming(
+            project_id,
+            session_id,
+            checkpoint,
+            file_snapshots.into_iter().map(Ok),
+            unchanged_files,
+            messages,
+        )
+    }
+
+    /// Like `save_checkpoint`, but with snapshots produced one at a time, so
+    /// only one file's content needs to be in memory
+    ///
+    /// A snapshot that fails to load fails the checkpoint.
+    pub fn save_checkpoint_streaming(
+        &self,
+        project_id: &str,
+        session_id: &str,
+        checkpoint: &Checkpoint,
+        file_snapshots: impl Iterator<Item = Result<FileSnapshot>>,
+        unchanged_files: Vec<FileRef>,
+        messages: &str,
     ) -> Result<CheckpointResult> {
         let paths = CheckpointPaths::new(&self.claude_dir, project_id, session_id);
+
+        self.begin_journal(&paths, &checkpoint.id)?;
+
+        match self.write_checkpoint(
+            &paths,
+            checkpoint,
+            file_snapshots,
+            unchanged_files,
+            messages,
+        ) {
+            Ok(result) => {
+                // The timeline is committed; a leftover entry is rolled forward on load
+                if let Err(e) = fs::remove_file(paths.journal_entry_file(&checkpoint.id)) {
+                    log::warn!("Failed to clear journal for {}: {}", checkpoint.id, e);
+                }
+                Ok(result)
+            }
+            Err(e) => {
+                if let Err(rollback_err) =
+                    self.rollback_checkpoint(project_id, &paths, &checkpoint.id)
+                {
+                    log::warn!(
+                        "Failed to roll back checkpoint {}: {}",
+                        checkpoint.id,
+                        rollback_err
+                    );
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Write all files of a checkpoint, finishing with the timeline commit
+    fn write_checkpoint(
+        &self,
+        paths: &CheckpointPaths,
+        checkpoint: &Checkpoint,
+        file_snapshots: impl Iterator<Item = Result<FileSnapshot>>,
+        unchanged_files: Vec<FileRef>,
+        messages: &str,
+    ) -> Result<CheckpointResult> {
         let checkpoint_dir = paths.checkpoint_dir(&checkpoint.id);
+        let mut checkpoint = checkpoint.clone();
 
         // Create checkpoint directory
         fs::create_dir_all(&checkpoint_dir).context("Failed to create checkpoint directory")?;
 
-        // Save checkpoint metadata
-        let metadata_path = paths.checkpoint_metadata_file(&checkpoint.id);
-        let metadata_json = serde_json::to_string_pretty(checkpoint)
-            .context("Failed to serialize checkpoint metadata")?;
-        fs::write(&metadata_path, metadata_json).context("Failed to write checkpoint metadata")?;
-
         // Save messages (compressed)
         let messages_path = paths.checkpoint_messages_file(&checkpoint.id);
         let compressed_messages = encode_all(messages.as_bytes(), self.compression_level)
             .context("Failed to compress messages")?;
-        fs::write(&messages_path, compressed_messages)
+        self.write_data(&messages_path, &compressed_messages)
             .context("Failed to write compressed messages")?;
 
-        // Save file snapshots
-        let mut warnings = Vec::new();
+        // Save file snapshots. The pool lock is held until the references are
+        // counted so garbage collection from another session can't remove
+        // content between it being written and being referenced.
         let mut files_processed = 0;
+        let mut pool_bytes_added = 0;
+        let mut snapshot_hashes = Vec::new();
+        {
+            let _pool_guard = lock_pool();
+            let mut file_refs =
+                Vec::with_capacity(file_snapshots.size_hint().0 + unchanged_files.len());
+
+            // A file left out of a full tree manifest would be deleted on
+            // restore, so any file that can't be referenced fails the checkpoint
+            for snapshot in file_snapshots {
+                let snapshot = snapshot?;
+                let (file_ref, bytes_added) = self
+                    .save_file_snapshot(paths, &snapshot)
+                    .with_context(|| format!("Failed to save {}", snapshot.file_path.display()))?;
+                files_processed += 1;
+                pool_bytes_added += bytes_added;
+                file_refs.push(file_ref);
+                snapshot_hashes.push(snapshot.hash);
+            }
 
-        for snapshot in &file_snapshots {
-            match self.save_file_snapshot(&paths, snapshot) {
-                Ok(_) => files_processed += 1,
-                Err(e) => warnings.push(format!(
-                    "Failed to save {}: {}",
-                    snapshot.file_path.display(),
-                    e
-                )),
+            for file_ref in &unchanged_files {
+                if !paths.content_object_path(&file_ref.hash).exists() {
+                    anyhow::bail!(
+                        "Content of {} was removed from the pool before the checkpoint was saved",
+                        file_ref.path.display()
+                    );
+                }
+                file_refs.push(file_ref.clone());
             }
+
+            self.write_checkpoint_manifest(paths, &checkpoint.id, &file_refs)?;
+            self.adjust_refcounts(paths, &Self::content_hashes(&file_refs), 1)?;
         }
 
+        // Save checkpoint metadata, now that its size on disk is known
+        checkpoint.metadata.snapshot_size = compressed_messages.len() as u64 + pool_bytes_added;
+        let metadata_path = paths.checkpoint_metadata_file(&checkpoint.id);
+        let metadata_json = serde_json::to_string_pretty(&checkpoint)
+            .context("Failed to serialize checkpoint metadata")?;
+        self.write_data(&metadata_path, metadata_json.as_bytes())
+            .context("Failed to write checkpoint metadata")?;
+
         // Update timeline
-        self.update_timeline_with_checkpoint(&paths.timeline_file, checkpoint, &file_snapshots)?;
+        self.update_timeline_with_checkpoint(&paths.timeline_file, &checkpoint, snapshot_hashes)?;
 
         Ok(CheckpointResult {
-            checkpoint: checkpoint.clone(),
+            checkpoint,
             files_processed,
-            warnings,
+            warnings: Vec::new(),
+            safety_checkpoint_id: None,
         })
     }
 
     /// Save a single file snapshot
-    fn save_file_snapshot(&self, paths: &CheckpointPaths, snapshot: &FileSnapshot) -> Result<()> {
-        // Use content-addressable storage: store files by their hash
-        // This prevents duplication of identical file content across checkpoints
-        let content_pool_dir = paths.files_dir.join("content_pool");
-        fs::create_dir_all(&content_pool_dir).context("Failed to create content pool directory")?;
+    ///
+    /// Returns the reference to record in the checkpoint manifest and the
+    /// compressed bytes added to the pool, which is zero when the content
+    /// was already stored.
+    fn save_file_snapshot(
+        &self,
+        paths: &CheckpointPaths,
+        snapshot: &FileSnapshot,
+    ) -> Result<(FileRef, u64)> {
+        let mut file_ref = FileRef {
+            path: snapshot.file_path.clone(),
+            hash: snapshot.hash.clone(),
+            is_deleted: snapshot.is_deleted,
+            permissions: snapshot.permissions,
+            size: snapshot.size,
+            kind: snapshot.kind,
+        };
 
-        // Store the actual content in the content pool
-        let content_file = content_pool_dir.join(&snapshot.hash);
+        // Use content-addressable storage: store files by their hash in the
+        // project-wide pool. This prevents duplication of identical file
+        // content across checkpoints and sessions. Only write the content if
+        // it doesn't already exist; deleted files have no content to store.
+        if snapshot.is_deleted || paths.content_object_path(&snapshot.hash).exists() {
+            return Ok((file_ref, 0));
+        }
 
-        // Only write the content if it doesn't already exist
-        if !content_file.exists() {
-            // Compress and save file content
-            let compressed_content =
-                encode_all(snapshot.content.as_bytes(), self.compression_level)
+        let bytes_added = match &snapshot.source {
+            ContentSource::File(source) => {
+                let file = fs::File::open(source).context("Failed to open file")?;
+                // The file may have changed since it was hashed, so record
+                // what was actually stored
+                let stored = self.save_chunked_content(paths, BufReader::new(file))?;
+                file_ref.hash = stored.hash;
+                file_ref.size = stored.size;
+                stored.bytes_added
+            }
+            ContentSource::Pool => {
+                anyhow::bail!("Content missing from pool for hash {}", snapshot.hash)
+            }
+            ContentSource::Inline if snapshot.content.len() > CHUNKED_OBJECT_THRESHOLD => {
+                self.save_chunked_content(paths, &snapshot.content[..])?
+                    .bytes_added
+            }
+            ContentSource::Inline => {
+                // Compress and save file content
+                let compressed_content = encode_all(&snapshot.content[..], self.compression_level)
                     .context("Failed to compress file content")?;
-            fs::write(&content_file, compressed_content)
+                self.write_data(
+                    &paths.content_object_path(&snapshot.hash),
+                    &compressed_content,
+                )
                 .context("Failed to write file content to pool")?;
-        }
+                compressed_content.len() as u64
+            }
+        };
 
-        // Create a reference in the checkpoint-specific directory
-        let checkpoint_refs_dir = paths.files_dir.join("refs").join(&snapshot.checkpoint_id);
-        fs::create_dir_all(&checkpoint_refs_dir)
-            .context("Failed to create checkpoint refs directory")?;
+        Ok((file_ref, bytes_added))
+    }
 
-        // Save file metadata with reference to content
-        let ref_metadata = serde_json::json!({
-            "path": snapshot.file_path,
-            "hash": snapshot.hash,
-            "is_deleted": snapshot.is_deleted,
-            "permissions": snapshot.permissions,
-            "size": snapshot.size,
-        });
+    /// Store large content as content-defined chunks plus a chunk list
+    ///
+    /// The content is streamed, so only one chunk is held in memory at a
+    /// time. Each chunk is compressed on its own and only written if no other
+    /// content in the project already stored it, so an edit to a large file
+    /// adds just the chunks around the change. The chunk list is named by the
+    /// hash of everything read.
+    fn save_chunked_content(
+        &self,
+        paths: &CheckpointPaths,
+        content: impl Read,
+    ) -> Result<StoredContent> {
+        fs::create_dir_all(paths.chunks_dir()).context("Failed to create chunks directory")?;
 
-        // Use a sanitized filename for the reference
-        let safe_filename = snapshot
-            .file_path
-            .to_string_lossy()
-            .replace('/', "_")
-            .replace('\\', "_");
-        let ref_path = checkpoint_refs_dir.join(format!("{}.json", safe_filename));
+        let mut hasher = Sha256::new();
+        let mut size = 0;
+        let mut bytes_added = 0;
+        let mut entries = Vec::new();
+        let mut chunks = chunks::reader_chunks(content);
+        while let Some(chunk) = chunks.next_chunk().context("Failed to read file content")? {
+            hasher.update(chunk);
+            size += chunk.len() as u64;
+
+            let hash = Self::calculate_file_hash(chunk);
+            let chunk_file = paths.chunk_path(&hash);
+            if !chunk_file.exists() {
+                let compressed = encode_all(chunk, self.compression_level)
+                    .context("Failed to compress chunk")?;
+                self.write_data(&chunk_file, &compressed)
+                    .context("Failed to write chunk to pool")?;
+                bytes_added += compressed.len() as u64;
+            }
+            entries.push(ChunkEntry {
+                hash,
+                size: chunk.len() as u64,
+            });
+        }
 
-        fs::write(&ref_path, serde_json::to_string_pretty(&ref_metadata)?)
-            .context("Failed to write file reference")?;
+        let hash = format!("{:x}", hasher.finalize());
+        let content_file = paths.content_object_path(&hash);
+        if !content_file.exists() {
+            let chunk_list = chunks::encode_chunk_list(&entries);
+            self.write_data(&content_file, &chunk_list)
+                .context("Failed to write chunk list to pool")?;
+            bytes_added += chunk_list.len() as u64;
+        }
 
-        Ok(())
+        Ok(StoredContent {
+            hash,
+            size,
+            bytes_added,
+        })
+    }
+
+    /// Write the manifest of a checkpoint in a single atomic write
+    pub fn write_checkpoint_manifest(
+        &self,
+        paths: &CheckpointPaths,
+        checkpoint_id: &str,
+        file_refs: &[FileRef],
+    ) -> Result<()> {
+        fs::create_dir_all(paths.manifests_dir())
+            .context("Failed to create manifests directory")?;
+        let manifest_json =
+            serde_json::to_string(file_refs).context("Failed to serialize checkpoint manifest")?;
+        self.write_data(
+            &paths.checkpoint_manifest_file(checkpoint_id),
+            manifest_json.as_bytes(),
+        )
+        .context("Failed to write checkpoint manifest")
     }
 
     /// Load a checkpoint from disk
@@ -174,74 +446,301 @@ impl CheckpointStorage {
         Ok((checkpoint, file_snapshots, messages))
     }
 
+    /// Load every file present at a checkpoint, keyed by path
+    ///
+    /// Checkpoints written before full tree manifests store only the files
+    /// that changed since their parent; for those, snapshots are overlaid
+    /// from the nearest full tree ancestor, or the root, down to the
+    /// checkpoint.
+    pub fn load_checkpoint_tree(
+        &self,
+        project_id: &str,
+        session_id: &str,
+        timeline: &SessionTimeline,
+        checkpoint_id: &str,
+    ) -> Result<BTreeMap<PathBuf, FileSnapshot>> {
+        let paths = CheckpointPaths::new(&self.claude_dir, project_id, session_id);
+
+        let mut tree = BTreeMap::new();
+        for (path, file_ref) in
+            self.load_checkpoint_tree_refs(project_id, session_id, timeline, checkpoint_id)?
+        {
+            tree.insert(path, self.load_snapshot(&paths, checkpoint_id, file_ref)?);
+        }
+        Ok(tree)
+    }
+
+    /// Like `load_checkpoint_tree`, but only the references into the pool
+    pub fn load_checkpoint_tree_refs(
+        &self,
+        project_id: &str,
+        session_id: &str,
+        timeline: &SessionTimeline,
+        checkpoint_id: &str,
+    ) -> Result<BTreeMap<PathBuf, FileRef>> {
+        let paths = CheckpointPaths::new(&self.claude_dir, project_id, session_id);
+        Self::read_tree_refs(&paths, timeline, checkpoint_id)
+    }
+
+    fn read_tree_refs(
+        paths: &CheckpointPaths,
+        timeline: &SessionTimeline,
+        checkpoint_id: &str,
+    ) -> Result<BTreeMap<PathBuf, FileRef>> {
+        // Older checkpoints hold only changes, so walk up to one with the whole tree
+        let mut chain = Vec::new();
+        let mut next = Some(checkpoint_id.to_string());
+        while let Some(id) = next {
+            if chain.contains(&id) {
+                break;
+            }
+            let checkpoint = timeline.find_checkpoint(&id).map(|node| &node.checkpoint);
+            next = checkpoint
+                .filter(|c| !c.full_tree)
+                .and_then(|c| c.parent_checkpoint_id.clone());
+            chain.push(id);
+        }
+
+        let mut tree = BTreeMap::new();
+        for id in chain.iter().rev() {
+            for file_ref in Self::read_checkpoint_refs(paths, id)? {
+                if file_ref.is_deleted {
+                    tree.remove(&file_ref.path);
+                } else {
+                    tree.insert(file_ref.path.clone(), file_ref);
+                }
+            }
+        }
+        Ok(tree)
+    }
+
     /// Load all file snapshots for a checkpoint
     fn load_file_snapshots(
         &self,
         paths: &CheckpointPaths,
         checkpoint_id: &str,
     ) -> Result<Vec<FileSnapshot>> {
-        let refs_dir = paths.files_dir.join("refs").join(checkpoint_id);
+        Self::read_checkpoint_refs(paths, checkpoint_id)?
+            .into_iter()
+            .map(|file_ref| self.load_snapshot(paths, checkpoint_id, file_ref))
+            .collect()
+    }
+
+    /// Snapshot of a file recorded by a checkpoint
+    ///
+    /// Files over `CHUNKED_OBJECT_THRESHOLD` are left in the pool and
+    /// streamed to disk when they are restored.
+    fn load_snapshot(
+        &self,
+        paths: &CheckpointPaths,
+        checkpoint_id: &str,
+        file_ref: FileRef,
+    ) -> Result<FileSnapshot> {
+        let streamed = !file_ref.is_deleted
+            && file_ref.kind == EntryKind::File
+            && file_ref.size > CHUNKED_OBJECT_THRESHOLD as u64;
+
+        let content = if file_ref.is_deleted {
+            Vec::new()
+        } else if streamed {
+            if !paths.content_object_path(&file_ref.hash).is_file() {
+                anyhow::bail!(
+                    "Content of {} is missing from the pool",
+                    file_ref.path.display()
+                );
+            }
+            Vec::new()
+        } else {
+            self.read_content_object(paths, &file_ref.hash)
+                .with_context(|| format!("Failed to load content of {}", file_ref.path.display()))?
+        };
+
+        Ok(FileSnapshot {
+            checkpoint_id: checkpoint_id.to_string(),
+            file_path: file_ref.path,
+            content,
+            hash: file_ref.hash,
+            is_deleted: file_ref.is_deleted,
+            permissions: file_ref.permissions,
+            size: file_ref.size,
+            kind: file_ref.kind,
+            source: if streamed {
+                ContentSource::Pool
+            } else {
+                ContentSource::Inline
+            },
+        })
+    }
+
+    /// Read and decompress a pool object, joining its chunks if it has any
+    pub fn read_content_object(&self, paths: &CheckpointPaths, hash: &str) -> Result<Vec<u8>> {
+        let mut content = Vec::new();
+        self.copy_content_object(paths, hash, &mut content)?;
+        Ok(content)
+    }
+
+    /// Decompress a pool object into `writer` one chunk at a time
+    pub fn copy_content_object(
+        &self,
+        paths: &CheckpointPaths,
+        hash: &str,
+        mut writer: impl Write,
+    ) -> Result<()> {
+        let content_file = paths.content_object_path(hash);
+        if !content_file.is_file() {
+            anyhow::bail!("Content missing from pool for hash {}", hash);
+        }
+
+        let mut object =
+            fs::File::open(&content_file).context("Failed to read file content from pool")?;
+        let Some(entries) = chunks::read_chunk_list(&mut object)? else {
+            zstd::stream::copy_decode(BufReader::new(object), &mut writer)
+                .context("Failed to decompress file content")?;
+            return Ok(());
+        };
+
+        for entry in entries {
+            let chunk_file = fs::File::open(paths.chunk_path(&entry.hash))
+                .with_context(|| format!("Chunk {} is missing from the pool", entry.hash))?;
+            zstd::stream::copy_decode(chunk_file, &mut writer)
+                .with_context(|| format!("Failed to decompress chunk {}", entry.hash))?;
+        }
+        Ok(())
+    }
+
+    /// Chunks a pool object is split into, or nothing for whole objects
+    ///
+    /// Only the header of a whole object is read.
+    pub fn content_chunks(&self, paths: &CheckpointPaths, hash: &str) -> Result<Vec<ChunkEntry>> {
+        let mut object = fs::File::open(paths.content_object_path(hash))
+            .with_context(|| format!("Content missing from pool for hash {}", hash))?;
+        Ok(chunks::read_chunk_list(&mut object)?.unwrap_or_default())
+    }
+
+    /// Bytes a pool object's own file takes on disk, and those of each
+    /// distinct chunk it lists
+    ///
+    /// Chunks can be shared with other objects, so totals over several
+    /// objects should count each chunk hash once.
+    pub fn stored_object_parts(
+        &self,
+        paths: &CheckpointPaths,
+        hash: &str,
+    ) -> (u64, HashMap<String, u64>) {
+        let object_size = fs::metadata(paths.content_object_path(hash))
+            .map(|m| m.len())
+            .unwrap_or(0);
+        let chunk_sizes = self
+            .content_chunks(paths, hash)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|entry| {
+                let size = fs::metadata(paths.chunk_path(&entry.hash))
+                    .map(|m| m.len())
+                    .unwrap_or(0);
+                (entry.hash, size)
+            })
+            .collect();
+        (object_size, chunk_sizes)
+    }
+
+    /// Read the file references recorded by a checkpoint
+    ///
+    /// Checkpoints written before manifests have one reference file per path
+    /// in their refs directory instead.
+    pub fn read_checkpoint_refs(
+        paths: &CheckpointPaths,
+        checkpoint_id: &str,
+    ) -> Result<Vec<FileRef>> {
+        let manifest_file = paths.checkpoint_manifest_file(checkpoint_id);
+        if !manifest_file.exists() {
+            return Self::read_file_refs(&paths.checkpoint_refs_dir(checkpoint_id));
+        }
+        let manifest_json =
+            fs::read_to_string(&manifest_file).context("Failed to read checkpoint manifest")?;
+        serde_json::from_str(&manifest_json)
+            .with_context(|| format!("Failed to parse manifest {}", manifest_file.display()))
+    }
+
+    /// IDs of every checkpoint with a manifest or refs directory in a session
+    pub fn list_manifest_ids(paths: &CheckpointPaths) -> Result<BTreeSet<String>> {
+        let mut ids = BTreeSet::new();
+        let manifests_dir = paths.manifests_dir();
+        if manifests_dir.is_dir() {
+            for entry in fs::read_dir(&manifests_dir)? {
+                let path = entry?.path();
+                if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                    continue;
+                }
+                if let Some(id) = path.file_stem().and_then(|s| s.to_str()) {
+                    ids.insert(id.to_string());
+                }
+            }
+        }
+        let refs_root = paths.files_dir.join("refs");
+        if refs_root.is_dir() {
+            for entry in fs::read_dir(&refs_root)? {
+                let entry = entry?;
+                if entry.file_type()?.is_dir() {
+                    ids.insert(entry.file_name().to_string_lossy().to_string());
+                }
+            }
+        }
+        Ok(ids)
+    }
+
+    /// Delete the manifest or legacy refs directory of a checkpoint
+    fn remove_checkpoint_refs(paths: &CheckpointPaths, checkpoint_id: &str) -> Result<()> {
+        let manifest_file = paths.checkpoint_manifest_file(checkpoint_id);
+        if manifest_file.exists() {
+            fs::remove_file(&manifest_file).context("Failed to remove checkpoint manifest")?;
+        }
+        let refs_dir = paths.checkpoint_refs_dir(checkpoint_id);
+        if refs_dir.exists() {
+            fs::remove_dir_all(&refs_dir).context("Failed to remove file references")?;
+        }
+        Ok(())
+    }
+
+    /// Pool objects referenced by a set of file references
+    fn content_hashes(file_refs: &[FileRef]) -> Vec<String> {
+        file_refs
+            .iter()
+            .filter(|file_ref| !file_ref.is_deleted)
+            .map(|file_ref| file_ref.hash.clone())
+            .collect()
+    }
+
+    /// Read the per-file references in a legacy refs directory
+    pub fn read_file_refs(refs_dir: &Path) -> Result<Vec<FileRef>> {
         if !refs_dir.exists() {
             return Ok(Vec::new());
         }
 
-        let content_pool_dir = paths.files_dir.join("content_pool");
-        let mut snapshots = Vec::new();
-
-        // Read all reference files
-        for entry in fs::read_dir(&refs_dir)? {
-            let entry = entry?;
-            let path = entry.path();
+        let mut file_refs = Vec::new();
+        for entry in fs::read_dir(refs_dir)? {
+            let path = entry?.path();
 
             // Skip non-JSON files
             if path.extension().and_then(|e| e.to_str()) != Some("json") {
                 continue;
             }
 
-            // Load reference metadata
             let ref_json = fs::read_to_string(&path).context("Failed to read file reference")?;
-            let ref_metadata: serde_json::Value =
-                serde_json::from_str(&ref_json).context("Failed to parse file reference")?;
-
-            let hash = ref_metadata["hash"]
-                .as_str()
-                .ok_or_else(|| anyhow::anyhow!("Missing hash in reference"))?;
-
-            // Load content from pool
-            let content_file = content_pool_dir.join(hash);
-            let content = if content_file.exists() {
-                let compressed_content =
-                    fs::read(&content_file).context("Failed to read file content from pool")?;
-                String::from_utf8(
-                    decode_all(&compressed_content[..])
-                        .context("Failed to decompress file content")?,
-                )
-                .context("Invalid UTF-8 in file content")?
-            } else {
-                // Handle missing content gracefully
-                log::warn!("Content file missing for hash: {}", hash);
-                String::new()
-            };
-
-            snapshots.push(FileSnapshot {
-                checkpoint_id: checkpoint_id.to_string(),
-                file_path: PathBuf::from(ref_metadata["path"].as_str().unwrap_or("")),
-                content,
-                hash: hash.to_string(),
-                is_deleted: ref_metadata["is_deleted"].as_bool().unwrap_or(false),
-                permissions: ref_metadata["permissions"].as_u64().map(|p| p as u32),
-                size: ref_metadata["size"].as_u64().unwrap_or(0),
-            });
+            let file_ref: FileRef = serde_json::from_str(&ref_json)
+                .with_context(|| format!("Failed to parse file reference {}", path.display()))?;
+            file_refs.push(file_ref);
         }
 
-        Ok(snapshots)
+        Ok(file_refs)
     }
 
     /// Save timeline to disk
     pub fn save_timeline(&self, timeline_path: &Path, timeline: &SessionTimeline) -> Result<()> {
         let timeline_json =
             serde_json::to_string_pretty(timeline).context("Failed to serialize timeline")?;
-        fs::write(timeline_path, timeline_json).context("Failed to write timeline")?;
+        self.write_commit(timeline_path, timeline_json.as_bytes())
+            .context("Failed to write timeline")?;
         Ok(())
     }
 
@@ -253,19 +752,73 @@ impl CheckpointStorage {
         Ok(timeline)
     }
 
+    /// Save the file tracker state and message cursor of a session
+    pub fn save_tracker_state(&self, tracker_path: &Path, state: &TrackerState) -> Result<()> {
+        let state_json =
+            serde_json::to_string(state).context("Failed to serialize tracker state")?;
+        self.write_data(tracker_path, state_json.as_bytes())
+            .context("Failed to write tracker state")
+    }
+
+    /// Load the file tracker state and message cursor of a session
+    pub fn load_tracker_state(&self, tracker_path: &Path) -> Result<TrackerState> {
+        let state_json =
+            fs::read_to_string(tracker_path).context("Failed to read tracker state")?;
+        serde_json::from_str(&state_json).context("Failed to parse tracker state")
+    }
+
+    /// Load a project's `Smart` strategy rules, or the defaults if none are saved
+    pub fn load_smart_rules(&self, project_id: &str) -> Result<SmartRules> {
+        let rules_path = CheckpointPaths::smart_rules_file(&self.claude_dir, project_id);
+        if !rules_path.exists() {
+            return Ok(SmartRules::default());
+        }
+        let rules_json = fs::read_to_string(&rules_path).context("Failed to read smart rules")?;
+        serde_json::from_str(&rules_json).context("Failed to parse smart rules")
+    }
+
+    /// Save a project's `Smart` strategy rules after checking every pattern compiles
+    pub fn save_smart_rules(&self, project_id: &str, rules: &SmartRules) -> Result<()> {
+        rules.compile()?;
+        let rules_path = CheckpointPaths::smart_rules_file(&self.claude_dir, project_id);
+        if let Some(parent) = rules_path.parent() {
+            fs::create_dir_all(parent).context("Failed to create timelines directory")?;
+        }
+        let rules_json =
+            serde_json::to_string_pretty(rules).context("Failed to serialize smart rules")?;
+        self.write_commit(&rules_path, rules_json.as_bytes())
+            .context("Failed to write smart rules")
+    }
+
+    /// Replace the Claude session transcript of a session
+    pub fn write_session_transcript(
+        &self,
+        project_id: &str,
+        session_id: &str,
+        transcript: &str,
+    ) -> Result<PathBuf> {
+        let session_file = CheckpointPaths::session_file(&self.claude_dir, project_id, session_id);
+        if let Some(parent) = session_file.parent() {
+            fs::create_dir_all(parent).context("Failed to create project directory")?;
+        }
+        self.write_commit(&session_file, transcript.as_bytes())
+            .context("Failed to write session transcript")?;
+        Ok(session_file)
+    }
+
     /// Update timeline with a new checkpoint
     fn update_timeline_with_checkpoint(
         &self,
         timeline_path: &Path,
         checkpoint: &Checkpoint,
-        file_snapshots: &[FileSnapshot],
+        file_snapshot_ids: Vec<String>,
     ) -> Result<()> {
         let mut timeline = self.load_timeline(timeline_path)?;
 
         let new_node = TimelineNode {
             checkpoint: checkpoint.clone(),
             children: Vec::new(),
-            file_snapshot_ids: file_snapshots.iter().map(|s| s.hash.clone()).collect(),
+            file_snapshot_ids,
         };
 
         // If this is the first checkpoint
@@ -284,6 +837,12 @@ impl CheckpointStorage {
             } else {
                 anyhow::bail!("Parent checkpoint not found: {}", parent_id);
             }
+        } else {
+            // A second root would be dropped from the tree
+            anyhow::bail!(
+                "Checkpoint {} has no parent but the timeline already has a root",
+                checkpoint.id
+            );
         }
 
         timeline.total_checkpoints += 1;
@@ -313,27 +872,35 @@ impl CheckpointStorage {
     }
 
     /// Calculate hash of file content
-    pub fn calculate_file_hash(content: &str) -> String {
+    pub fn calculate_file_hash(content: &[u8]) -> String {
         let mut hasher = Sha256::new();
-        hasher.update(content.as_bytes());
+        hasher.update(content);
         format!("{:x}", hasher.finalize())
     }
 
+    /// Calculate the hash of content read from a stream, without holding it in memory
+    pub fn calculate_reader_hash(mut reader: impl Read) -> std::io::Result<String> {
+        let mut hasher = Sha256::new();
+        let mut buffer = [0u8; 64 * 1024];
+        loop {
+            let read = reader.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+        }
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+
     /// Generate a new checkpoint ID
     pub fn generate_checkpoint_id() -> String {
         Uuid::new_v4().to_string()
     }
 
-    /// Estimate storage size for a checkpoint
-    pub fn estimate_checkpoint_size(messages: &str, file_snapshots: &[FileSnapshot]) -> u64 {
-        let messages_size = messages.len() as u64;
-        let files_size: u64 = file_snapshots.iter().map(|s| s.content.len() as u64).sum();
-
-        // Estimate compressed size (typically 20-30% of original for text)
-        (messages_size + files_size) / 4
-    }
-
-    /// Clean up old checkpoints based on retention policy
+    /// Clean up old checkpoints, keeping the newest `keep_count`
+    ///
+    /// Children of pruned checkpoints are re-parented. The current checkpoint
+    /// and tagged checkpoints are always kept.
     pub fn cleanup_old_checkpoints(
         &self,
         project_id: &str,
@@ -341,7 +908,7 @@ impl CheckpointStorage {
         keep_count: usize,
     ) -> Result<usize> {
         let paths = CheckpointPaths::new(&self.claude_dir, project_id, session_id);
-        let timeline = self.load_timeline(&paths.timeline_file)?;
+        let mut timeline = self.load_timeline(&paths.timeline_file)?;
 
         // Collect all checkpoint IDs in chronological order
         let mut all_checkpoints = Vec::new();
@@ -357,13 +924,21 @@ impl CheckpointStorage {
         let mut removed_count = 0;
 
         for checkpoint in all_checkpoints.into_iter().take(to_remove) {
-            if self.remove_checkpoint(&paths, &checkpoint.id).is_ok() {
-                removed_count += 1;
+            if timeline.current_checkpoint_id.as_deref() == Some(checkpoint.id.as_str())
+                || !checkpoint.tags.is_empty()
+            {
+                continue;
+            }
+            match self.prune_checkpoint(&paths, &mut timeline, &checkpoint.id) {
+                Ok(()) => removed_count += 1,
+                Err(e) => log::warn!("Failed to remove checkpoint {}: {}", checkpoint.id, e),
             }
         }
 
         // Run garbage collection to clean up orphaned content
         if removed_count > 0 {
+            self.save_timeline(&paths.timeline_file, &timeline)?;
+
             match self.garbage_collect_content(project_id, session_id) {
                 Ok(gc_count) => {
                     log::info!("Garbage collected {} orphaned content files", gc_count);
@@ -386,18 +961,18 @@ impl CheckpointStorage {
     }
 
     /// Remove a checkpoint and its associated files
-    fn remove_checkpoint(&self, paths: &CheckpointPaths, checkpoint_id: &str) -> Result<()> {
+    pub fn remove_checkpoint(&self, paths: &CheckpointPaths, checkpoint_id: &str) -> Result<()> {
         // Remove checkpoint metadata directory
         let checkpoint_dir = paths.checkpoint_dir(checkpoint_id);
         if checkpoint_dir.exists() {
             fs::remove_dir_all(&checkpoint_dir).context("Failed to remove checkpoint directory")?;
         }
 
-        // Remove file references for this checkpoint
-        let refs_dir = paths.files_dir.join("refs").join(checkpoint_id);
-        if refs_dir.exists() {
-            fs::remove_dir_all(&refs_dir).context("Failed to remove file references")?;
-        }
+        // Remove file references for this checkpoint and release their content
+        let hashes = Self::read_ref_hashes(paths, checkpoint_id);
+        let _pool_guard = lock_pool();
+        Self::remove_checkpoint_refs(paths, checkpoint_id)?;
+        self.adjust_refcounts(paths, &hashes, -1)?;
 
         // Note: We don't remove content from the pool here as it might be
         // referenced by other checkpoints. Use garbage_collect_content() for that.
@@ -405,56 +980,703 @@ impl CheckpointStorage {
         Ok(())
     }
 
-    /// Garbage collect unreferenced content from the content pool
-    pub fn garbage_collect_content(&self, project_id: &str, session_id: &str) -> Result<usize> {
+    /// Rewrite the metadata of an existing checkpoint
+    pub fn update_checkpoint_metadata(
+        &self,
+        paths: &CheckpointPaths,
+        checkpoint: &Checkpoint,
+    ) -> Result<()> {
+        let metadata_json = serde_json::to_string_pretty(checkpoint)
+            .context("Failed to serialize checkpoint metadata")?;
+        self.write_data(
+            &paths.checkpoint_metadata_file(&checkpoint.id),
+            metadata_json.as_bytes(),
+        )
+        .context("Failed to update checkpoint metadata")
+    }
+
+    /// Resolve a checkpoint ID, tag or unique ID prefix using the saved timeline
+    pub fn resolve_checkpoint(
+        &self,
+        project_id: &str,
+        session_id: &str,
+        reference: &str,
+    ) -> Result<String> {
         let paths = CheckpointPaths::new(&self.claude_dir, project_id, session_id);
-        let content_pool_dir = paths.files_dir.join("content_pool");
-        let refs_dir = paths.files_dir.join("refs");
+        self.load_timeline(&paths.timeline_file)?
+            .resolve_checkpoint(reference)
+    }
 
-        if !content_pool_dir.exists() {
-            return Ok(0);
+    /// Remove a checkpoint from the timeline and from disk
+    ///
+    /// Children of the checkpoint are attached to its parent and their
+    /// metadata is rewritten to match. Children that only record their
+    /// changes are first given a manifest of their whole tree, since the
+    /// changes they build on are removed. The caller saves the timeline.
+    pub fn prune_checkpoint(
+        &self,
+        paths: &CheckpointPaths,
+        timeline: &mut SessionTimeline,
+        checkpoint_id: &str,
+    ) -> Result<()> {
+        let changes_only_children: Vec<String> = timeline
+            .find_checkpoint(checkpoint_id)
+            .ok_or_else(|| anyhow::anyhow!("Checkpoint not found: {}", checkpoint_id))?
+            .children
+            .iter()
+            .filter(|child| !child.checkpoint.full_tree)
+            .map(|child| child.checkpoint.id.clone())
+            .collect();
+        for child_id in &changes_only_children {
+            self.write_full_tree_manifest(paths, timeline, child_id)?;
+            if let Some(child) = timeline.find_checkpoint_mut(child_id) {
+                child.checkpoint.full_tree = true;
+            }
         }
 
-        // Collect all referenced hashes
-        let mut referenced_hashes = std::collections::HashSet::new();
+        let reparented = timeline
+            .remove_checkpoint(checkpoint_id)
+            .ok_or_else(|| anyhow::anyhow!("Checkpoint not found: {}", checkpoint_id))?;
 
+        for child in &reparented {
+            if paths.checkpoint_metadata_file(&child.id).exists() {
+                self.update_checkpoint_metadata(paths, child)?;
+            }
+        }
+
+        self.remove_checkpoint(paths, checkpoint_id)
+    }
+
+    /// Replace the references of a checkpoint that only records its changes
+    /// with a manifest of its whole tree
+    fn write_full_tree_manifest(
+        &self,
+        paths: &CheckpointPaths,
+        timeline: &SessionTimeline,
+        checkpoint_id: &str,
+    ) -> Result<()> {
+        let file_refs: Vec<FileRef> = Self::read_tree_refs(paths, timeline, checkpoint_id)?
+            .into_values()
+            .collect();
+        let old_hashes = Self::read_ref_hashes(paths, checkpoint_id);
+
+        let _pool_guard = lock_pool();
+        self.write_checkpoint_manifest(paths, checkpoint_id, &file_refs)?;
+        let refs_dir = paths.checkpoint_refs_dir(checkpoint_id);
         if refs_dir.exists() {
-            for checkpoint_entry in fs::read_dir(&refs_dir)? {
-                let checkpoint_dir = checkpoint_entry?.path();
-                if checkpoint_dir.is_dir() {
-                    for ref_entry in fs::read_dir(&checkpoint_dir)? {
-                        let ref_path = ref_entry?.path();
-                        if ref_path.extension().and_then(|e| e.to_str()) == Some("json") {
-                            if let Ok(ref_json) = fs::read_to_string(&ref_path) {
-                                if let Ok(ref_metadata) =
-                                    serde_json::from_str::<serde_json::Value>(&ref_json)
-                                {
-                                    if let Some(hash) = ref_metadata["hash"].as_str() {
-                                        referenced_hashes.insert(hash.to_string());
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
+            fs::remove_dir_all(&refs_dir).context("Failed to remove file references")?;
+        }
+        self.adjust_refcounts(paths, &Self::content_hashes(&file_refs), 1)?;
+        self.adjust_refcounts(paths, &old_hashes, -1)
+    }
+
+    /// Reference counts of every pool object used by any session of the project
+    pub fn project_reference_counts(&self, project_id: &str) -> Result<BTreeMap<String, u64>> {
+        let _pool_guard = lock_pool();
+        self.count_project_references(project_id)
+    }
+
+    /// Delete a pool object regardless of its references
+    ///
+    /// Used for content that failed verification; checkpoints that still
+    /// reference it become unrecoverable.
+    pub fn discard_content_object(&self, paths: &CheckpointPaths, hash: &str) -> Result<()> {
+        let _pool_guard = lock_pool();
+        let content_file = paths.content_object_path(hash);
+        if content_file.exists() {
+            let chunked = !self
+                .content_chunks(paths, hash)
+                .unwrap_or_default()
+                .is_empty();
+            fs::remove_file(&content_file).context("Failed to remove pool content")?;
+            if chunked {
+                self.remove_unused_chunks(paths)?;
             }
         }
+        Ok(())
+    }
+
+    /// Garbage collect unreferenced content from the project's shared content pool
+    ///
+    /// Reference counts are rebuilt from the refs of every session in the
+    /// project before anything is deleted, so a stale count can never remove
+    /// content that another session still uses.
+    pub fn garbage_collect_content(&self, project_id: &str, session_id: &str) -> Result<usize> {
+        let paths = CheckpointPaths::new(&self.claude_dir, project_id, session_id);
+        let objects_dir = paths.content_pool_dir.join("objects");
+
+        if !objects_dir.exists() {
+            return Ok(0);
+        }
+
+        let _pool_guard = lock_pool();
+        let refcounts = self.count_project_references(project_id)?;
+        self.save_refcounts(&paths, &refcounts)?;
 
         // Remove unreferenced content
         let mut removed_count = 0;
-        for entry in fs::read_dir(&content_pool_dir)? {
+        for entry in fs::read_dir(&objects_dir)? {
             let content_file = entry?.path();
-            if content_file.is_file() {
-                if let Some(hash) = content_file.file_name().and_then(|n| n.to_str()) {
-                    if !referenced_hashes.contains(hash) {
-                        if fs::remove_file(&content_file).is_ok() {
-                            removed_count += 1;
-                        }
+            if !content_file.is_file() {
+                continue;
+            }
+            let Some(hash) = content_file.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            if !refcounts.contains_key(hash) && fs::remove_file(&content_file).is_ok() {
+                removed_count += 1;
+            }
+        }
+        self.remove_unused_chunks(&paths)?;
+
+        Ok(removed_count)
+    }
+
+    /// Delete chunks that no chunk list in the pool mentions
+    ///
+    /// Must be called with the pool lock held. Returns the number removed.
+    fn remove_unused_chunks(&self, paths: &CheckpointPaths) -> Result<usize> {
+        let chunks_dir = paths.chunks_dir();
+        if !chunks_dir.is_dir() {
+            return Ok(0);
+        }
+
+        let mut used = HashSet::new();
+        let objects_dir = paths.content_pool_dir.join("objects");
+        if objects_dir.is_dir() {
+            for entry in fs::read_dir(&objects_dir)? {
+                let Some(hash) = entry?.file_name().to_str().map(str::to_string) else {
+                    continue;
+                };
+                if hash.starts_with('.') {
+                    continue;
+                }
+                match self.content_chunks(paths, &hash) {
+                    Ok(entries) => used.extend(entries.into_iter().map(|e| e.hash)),
+                    // Keep everything rather than guess what an unreadable list held
+                    Err(e) => {
+                        log::warn!("Not removing chunks, {} is unreadable: {}", hash, e);
+                        return Ok(0);
                     }
                 }
             }
         }
 
-        Ok(removed_count)
+        let mut removed = 0;
+        for entry in fs::read_dir(&chunks_dir)? {
+            let chunk_file = entry?.path();
+            let unused = chunk_file
+                .file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|hash| !hash.starts_with('.') && !used.contains(hash));
+            if unused && fs::remove_file(&chunk_file).is_ok() {
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// List the IDs of all sessions in a project that have a timeline
+    ///
+    /// Hidden directories, such as an import still being staged, are skipped.
+    pub fn list_session_ids(&self, project_id: &str) -> Result<Vec<String>> {
+        let timelines_dir = CheckpointPaths::timelines_dir(&self.claude_dir, project_id);
+        if !timelines_dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut session_ids = Vec::new();
+        for entry in fs::read_dir(&timelines_dir)? {
+            let path = entry?.path();
+            let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            if !name.starts_with('.') && path.join("timeline.json").is_file() {
+                session_ids.push(name.to_string());
+            }
+        }
+        session_ids.sort();
+
+        Ok(session_ids)
+    }
+
+    /// Count references to each pool object across every session of a project
+    ///
+    /// Callers must hold the pool lock.
+    fn count_project_references(&self, project_id: &str) -> Result<BTreeMap<String, u64>> {
+        let mut refcounts: BTreeMap<String, u64> = BTreeMap::new();
+
+        for session_id in self.list_session_ids(project_id)? {
+            let paths = CheckpointPaths::new(&self.claude_dir, project_id, &session_id);
+            for checkpoint_id in Self::list_manifest_ids(&paths)? {
+                for hash in Self::read_ref_hashes(&paths, &checkpoint_id) {
+                    *refcounts.entry(hash).or_insert(0) += 1;
+                }
+            }
+        }
+
+        Ok(refcounts)
+    }
+
+    /// Recount references to pool objects from the refs of every session
+    ///
+    /// Callers must hold the pool lock.
+    pub fn rebuild_refcounts(&self, project_id: &str, paths: &CheckpointPaths) -> Result<()> {
+        let refcounts = self.count_project_references(project_id)?;
+        self.save_refcounts(paths, &refcounts)
+    }
+
+    /// Read the content hashes referenced by a checkpoint, or none if its
+    /// references are unreadable
+    fn read_ref_hashes(paths: &CheckpointPaths, checkpoint_id: &str) -> Vec<String> {
+        Self::read_checkpoint_refs(paths, checkpoint_id)
+            .map(|file_refs| Self::content_hashes(&file_refs))
+            .unwrap_or_default()
+    }
+
+    /// Apply a reference count change to pool objects
+    ///
+    /// Callers must hold the pool lock.
+    fn adjust_refcounts(
+        &self,
+        paths: &CheckpointPaths,
+        hashes: &[String],
+        delta: i64,
+    ) -> Result<()> {
+        if hashes.is_empty() {
+            return Ok(());
+        }
+
+        let mut refcounts = self.load_refcounts(paths);
+        for hash in hashes.iter().filter(|h| !h.is_empty()) {
+            let count = refcounts.entry(hash.clone()).or_insert(0);
+            *count = count.saturating_add_signed(delta);
+            if *count == 0 {
+                refcounts.remove(hash);
+            }
+        }
+
+        self.save_refcounts(paths, &refcounts)
+    }
+
+    fn load_refcounts(&self, paths: &CheckpointPaths) -> BTreeMap<String, u64> {
+        fs::read_to_string(paths.refcounts_file())
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    fn save_refcounts(
+        &self,
+        paths: &CheckpointPaths,
+        refcounts: &BTreeMap<String, u64>,
+    ) -> Result<()> {
+        let json = serde_json::to_string_pretty(refcounts)
+            .context("Failed to serialize pool reference counts")?;
+        self.write_data(&paths.refcounts_file(), json.as_bytes())
+            .context("Failed to write pool reference counts")
+    }
+
+    /// Move a session's per-session content pool into the shared project pool
+    fn migrate_legacy_pool(&self, project_id: &str, paths: &CheckpointPaths) -> Result<()> {
+        let legacy_dir = paths.legacy_content_pool_dir();
+        if !legacy_dir.is_dir() {
+            return Ok(());
+        }
+
+        let _pool_guard = lock_pool();
+        let mut migrated = 0;
+        for entry in fs::read_dir(&legacy_dir)? {
+            let source = entry?.path();
+            let Some(hash) = source.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            if !source.is_file() {
+                continue;
+            }
+
+            let target = paths.content_object_path(hash);
+            if target.exists() {
+                // Identical content is already in the shared pool
+                fs::remove_file(&source).context("Failed to remove migrated content")?;
+            } else if fs::rename(&source, &target).is_err() {
+                // Fall back to copying when the pool is on another filesystem
+                fs::copy(&source, &target).context("Failed to migrate content to shared pool")?;
+                fs::remove_file(&source).context("Failed to remove migrated content")?;
+            }
+            migrated += 1;
+        }
+        fs::remove_dir_all(&legacy_dir).context("Failed to remove legacy content pool")?;
+
+        // The session's refs now point into the shared pool
+        self.rebuild_refcounts(project_id, paths)?;
+
+        log::info!(
+            "Migrated {} content files from {} into the shared pool",
+            migrated,
+            legacy_dir.display()
+        );
+
+        Ok(())
+    }
+
+    /// Record that a checkpoint write is in progress
+    fn begin_journal(&self, paths: &CheckpointPaths, checkpoint_id: &str) -> Result<()> {
+        let journal_dir = paths.journal_dir();
+        fs::create_dir_all(&journal_dir).context("Failed to create journal directory")?;
+
+        let entry = JournalEntry {
+            checkpoint_id: checkpoint_id.to_string(),
+            started_at: Utc::now(),
+        };
+        let json = serde_json::to_string_pretty(&entry).context("Failed to serialize journal")?;
+        self.write_commit(&paths.journal_entry_file(checkpoint_id), json.as_bytes())
+            .context("Failed to write journal")
+    }
+
+    /// Resolve journal entries left behind by interrupted checkpoint writes
+    ///
+    /// Checkpoints that reached the timeline are kept; all others are rolled
+    /// back. Returns the IDs of rolled back checkpoints.
+    fn recover_incomplete_checkpoints(
+        &self,
+        project_id: &str,
+        paths: &CheckpointPaths,
+    ) -> Result<Vec<String>> {
+        let journal_dir = paths.journal_dir();
+        if !journal_dir.is_dir() {
+            return Ok(Vec::new());
+        }
+
+        let timeline = if paths.timeline_file.exists() {
+            Some(self.load_timeline(&paths.timeline_file)?)
+        } else {
+            None
+        };
+
+        let mut rolled_back = Vec::new();
+        for entry in fs::read_dir(&journal_dir)? {
+            let entry_path = entry?.path();
+            if entry_path.extension().and_then(|e| e.to_str()) != Some("json") {
+                // Temporary file from an interrupted journal write
+                let _ = fs::remove_file(&entry_path);
+                continue;
+            }
+
+            let journal: JournalEntry = match fs::read_to_string(&entry_path)
+                .ok()
+                .and_then(|json| serde_json::from_str(&json).ok())
+            {
+                Some(journal) => journal,
+                None => {
+                    log::warn!("Discarding unreadable journal entry {:?}", entry_path);
+                    let _ = fs::remove_file(&entry_path);
+                    continue;
+                }
+            };
+
+            let committed = timeline
+                .as_ref()
+                .is_some_and(|t| t.find_checkpoint(&journal.checkpoint_id).is_some());
+            if committed {
+                fs::remove_file(&entry_path).context("Failed to clear journal entry")?;
+            } else {
+                log::warn!(
+                    "Rolling back incomplete checkpoint {} started at {}",
+                    journal.checkpoint_id,
+                    journal.started_at
+                );
+                self.rollback_checkpoint(project_id, paths, &journal.checkpoint_id)?;
+                rolled_back.push(journal.checkpoint_id);
+            }
+        }
+
+        Ok(rolled_back)
+    }
+
+    /// Remove everything written for an uncommitted checkpoint
+    fn rollback_checkpoint(
+        &self,
+        project_id: &str,
+        paths: &CheckpointPaths,
+        checkpoint_id: &str,
+    ) -> Result<()> {
+        let checkpoint_dir = paths.checkpoint_dir(checkpoint_id);
+        if checkpoint_dir.exists() {
+            fs::remove_dir_all(&checkpoint_dir).context("Failed to remove checkpoint directory")?;
+        }
+
+        {
+            let _pool_guard = lock_pool();
+            Self::remove_checkpoint_refs(paths, checkpoint_id)?;
+
+            // The counts may or may not include this checkpoint, so rebuild
+            // them. Pool objects it added are left for garbage collection.
+            self.rebuild_refcounts(project_id, paths)?;
+        }
+
+        let journal_file = paths.journal_entry_file(checkpoint_id);
+        if journal_file.exists() {
+            fs::remove_file(&journal_file).context("Failed to clear journal entry")?;
+        }
+
+        Ok(())
+    }
+
+    /// Atomically write a data file, syncing it only under `FsyncPolicy::Always`
+    pub fn write_data(&self, path: &Path, contents: &[u8]) -> Result<()> {
+        write_atomic(path, contents, self.fsync_policy() == FsyncPolicy::Always)
+    }
+
+    /// Atomically write a file that commits state, syncing it unless fsync is disabled
+    fn write_commit(&self, path: &Path, contents: &[u8]) -> Result<()> {
+        write_atomic(path, contents, self.fsync_policy() != FsyncPolicy::Never)
+    }
+}
+
+/// Write a file through a temporary sibling and rename it into place
+///
+/// Readers see either the old or the new content, never a partial write.
+fn write_atomic(path: &Path, contents: &[u8], sync: bool) -> Result<()> {
+    let dir = path
+        .parent()
+        .ok_or_else(|| anyhow::anyhow!("No parent directory for {}", path.display()))?;
+
+    let mut temp = tempfile::Builder::new()
+        .prefix(".tmp")
+        .tempfile_in(dir)
+        .context("Failed to create temporary file")?;
+    temp.write_all(contents)
+        .context("Failed to write temporary file")?;
+    if sync {
+        temp.as_file().sync_all().context("Failed to sync file")?;
+    }
+    temp.persist(path)
+        .map_err(|e| e.error)
+        .context("Failed to move file into place")?;
+
+    if sync {
+        sync_dir(dir)?;
+    }
+
+    Ok(())
+}
+
+/// Flush a directory entry so a rename survives a crash
+fn sync_dir(dir: &Path) -> Result<()> {
+    #[cfg(unix)]
+    fs::File::open(dir)
+        .and_then(|d| d.sync_all())
+        .context("Failed to sync directory")?;
+    #[cfg(not(unix))]
+    let _ = dir;
+    Ok(())
+}
+
+/// Total size of the files below a directory, zero if it does not exist
+pub fn dir_size(dir: &Path) -> u64 {
+    WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter_map(|e| e.metadata().ok())
+        .filter(|m| m.is_file())
+        .map(|m| m.len())
+        .sum()
+}
+
+/// Bytes a checkpoint's metadata, messages and manifest or refs take on disk
+pub fn checkpoint_metadata_bytes(paths: &CheckpointPaths, checkpoint_id: &str) -> u64 {
+    let manifest_bytes = fs::metadata(paths.checkpoint_manifest_file(checkpoint_id))
+        .map(|m| m.len())
+        .unwrap_or(0);
+    dir_size(&paths.checkpoint_dir(checkpoint_id))
+        + manifest_bytes
+        + dir_size(&paths.checkpoint_refs_dir(checkpoint_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checkpoint::manager::CheckpointManager;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_interrupted_checkpoint_is_rolled_back() {
+        let temp_dir = TempDir::new().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        let project_path = temp_dir.path().join("project");
+        fs::create_dir_all(&project_path).unwrap();
+        fs::write(project_path.join("a.txt"), b"one").unwrap();
+
+        let manager = CheckpointManager::new(
+            "proj".to_string(),
+            "sess".to_string(),
+            project_path,
+            claude_dir.clone(),
+        )
+        .await
+        .unwrap();
+        let committed = manager
+            .create_checkpoint(None, None)
+            .await
+            .unwrap()
+            .checkpoint;
+
+        let storage = CheckpointStorage::new(claude_dir.clone());
+        let paths = CheckpointPaths::new(&claude_dir, "proj", "sess");
+        let refcounts = storage.load_refcounts(&paths);
+
+        // A write that stopped after its files but before the timeline commit
+        let partial = Checkpoint {
+            id: "partial".to_string(),
+            parent_checkpoint_id: Some(committed.id.clone()),
+            ..committed.clone()
+        };
+        storage.begin_journal(&paths, &partial.id).unwrap();
+        fs::create_dir_all(paths.checkpoint_dir(&partial.id)).unwrap();
+        storage
+            .write_data(
+                &paths.checkpoint_metadata_file(&partial.id),
+                &serde_json::to_vec(&partial).unwrap(),
+            )
+            .unwrap();
+        let file_refs = CheckpointStorage::read_checkpoint_refs(&paths, &committed.id).unwrap();
+        storage
+            .write_checkpoint_manifest(&paths, &partial.id, &file_refs)
+            .unwrap();
+        storage
+            .adjust_refcounts(&paths, &CheckpointStorage::content_hashes(&file_refs), 1)
+            .unwrap();
+        // A committed checkpoint whose journal entry was never cleared, and a
+        // journal write that never finished
+        storage.begin_journal(&paths, &committed.id).unwrap();
+        fs::write(paths.journal_dir().join(".entry.tmp"), b"{").unwrap();
+
+        storage.init_storage("proj", "sess").unwrap();
+
+        assert!(!paths.checkpoint_dir(&partial.id).exists());
+        assert!(!paths.checkpoint_manifest_file(&partial.id).exists());
+        assert!(paths.checkpoint_dir(&committed.id).exists());
+        assert_eq!(fs::read_dir(paths.journal_dir()).unwrap().count(), 0);
+        assert_eq!(storage.load_refcounts(&paths), refcounts);
+        let timeline = storage.load_timeline(&paths.timeline_file).unwrap();
+        assert_eq!(timeline.checkpoint_ids(), vec![committed.id]);
+    }
+
+    #[tokio::test]
+    async fn test_full_tree_manifests_and_changes_only_overlay() {
+        let temp_dir = TempDir::new().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        let project_path = temp_dir.path().join("project");
+        fs::create_dir_all(&project_path).unwrap();
+        let manager = CheckpointManager::new(
+            "proj".to_string(),
+            "sess".to_string(),
+            project_path.clone(),
+            claude_dir.clone(),
+        )
+        .await
+        .unwrap();
+
+        let mut ids = Vec::new();
+        fs::write(project_path.join("a.txt"), b"one").unwrap();
+        fs::write(project_path.join("b.txt"), b"kept").unwrap();
+        ids.push(manager.create_checkpoint(None, None).await.unwrap());
+        fs::write(project_path.join("a.txt"), b"two").unwrap();
+        ids.push(manager.create_checkpoint(None, None).await.unwrap());
+        fs::remove_file(project_path.join("b.txt")).unwrap();
+        fs::write(project_path.join("c.txt"), b"new").unwrap();
+        ids.push(manager.create_checkpoint(None, None).await.unwrap());
+        let ids: Vec<String> = ids.into_iter().map(|r| r.checkpoint.id).collect();
+
+        let storage = CheckpointStorage::new(claude_dir.clone());
+        let paths = CheckpointPaths::new(&claude_dir, "proj", "sess");
+        let mut timeline = storage.load_timeline(&paths.timeline_file).unwrap();
+        let hashes = |tree: BTreeMap<PathBuf, FileRef>| -> Vec<(PathBuf, String)> {
+            tree.into_iter().map(|(path, r)| (path, r.hash)).collect()
+        };
+
+        // Each manifest lists every file, including the unchanged ones
+        let manifest_paths = |id: &str| -> Vec<PathBuf> {
+            let mut paths: Vec<PathBuf> = CheckpointStorage::read_checkpoint_refs(&paths, id)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.path)
+                .collect();
+            paths.sort();
+            paths
+        };
+        assert_eq!(
+            manifest_paths(&ids[1]),
+            vec![PathBuf::from("a.txt"), PathBuf::from("b.txt")]
+        );
+        assert_eq!(
+            manifest_paths(&ids[2]),
+            vec![PathBuf::from("a.txt"), PathBuf::from("c.txt")]
+        );
+        let trees: Vec<_> = ids
+            .iter()
+            .map(|id| {
+                hashes(
+                    storage
+                        .load_checkpoint_tree_refs("proj", "sess", &timeline, id)
+                        .unwrap(),
+                )
+            })
+            .collect();
+
+        // Rewrite the later checkpoints as older versions stored them, with
+        // only the files that changed since their parent
+        let refs = |id: &str| CheckpointStorage::read_checkpoint_refs(&paths, id).unwrap();
+        let first = refs(&ids[0]);
+        let second: Vec<FileRef> = refs(&ids[1])
+            .into_iter()
+            .filter(|r| r.path == Path::new("a.txt"))
+            .collect();
+        let mut third: Vec<FileRef> = refs(&ids[2])
+            .into_iter()
+            .filter(|r| r.path == Path::new("c.txt"))
+            .collect();
+        third.extend(
+            first
+                .iter()
+                .filter(|r| r.path == Path::new("b.txt"))
+                .map(|r| FileRef {
+                    is_deleted: true,
+                    ..r.clone()
+                }),
+        );
+        for (id, changes) in [(&ids[1], second), (&ids[2], third)] {
+            storage
+                .write_checkpoint_manifest(&paths, id, &changes)
+                .unwrap();
+            timeline
+                .find_checkpoint_mut(id)
+                .unwrap()
+                .checkpoint
+                .full_tree = false;
+        }
+
+        // Changes are overlaid from the nearest full tree ancestor
+        for (id, tree) in ids.iter().zip(&trees) {
+            let overlaid = storage
+                .load_checkpoint_tree_refs("proj", "sess", &timeline, id)
+                .unwrap();
+            assert_eq!(&hashes(overlaid), tree);
+        }
+
+        // A full tree checkpoint is read on its own, without its ancestors
+        timeline
+            .find_checkpoint_mut(&ids[1])
+            .unwrap()
+            .checkpoint
+            .full_tree = true;
+        let tree = storage
+            .load_checkpoint_tree_refs("proj", "sess", &timeline, &ids[1])
+            .unwrap();
+        assert_eq!(
+            tree.keys().cloned().collect::<Vec<_>>(),
+            vec![PathBuf::from("a.txt")]
+        );
     }
 }
@@ -0,0 +1,58 @@
+use serde_json::Value;
+
+/// Turn checkpoint messages into a session transcript Claude can resume
+///
+/// Every message ends up on its own newline-terminated line, so messages
+/// Claude appends later start on a fresh line. With `session_id` set, the
+/// `sessionId` of every message is rewritten to it.
+pub fn session_transcript(messages: &str, session_id: Option<&str>) -> String {
+    let mut transcript = String::with_capacity(messages.len() + 1);
+    for line in messages.lines() {
+        match session_id {
+            Some(session_id) => transcript.push_str(&with_session_id(line, session_id)),
+            None => transcript.push_str(line),
+        }
+        transcript.push('\n');
+    }
+    transcript
+}
+
+/// Point a single JSONL message at another session
+///
+/// Lines that are not JSON objects with a `sessionId` are returned unchanged.
+fn with_session_id(line: &str, session_id: &str) -> String {
+    let Ok(Value::Object(mut message)) = serde_json::from_str::<Value>(line) else {
+        return line.to_string();
+    };
+    match message.get_mut("sessionId") {
+        Some(id) => {
+            *id = Value::String(session_id.to_string());
+            serde_json::to_string(&message).unwrap_or_else(|_| line.to_string())
+        }
+        None => line.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_transcript() {
+        let messages =
+            "{\"type\":\"user\",\"sessionId\":\"old\"}\nnot json\n{\"type\":\"summary\"}";
+
+        assert_eq!(
+            session_transcript(messages, None),
+            format!("{}\n", messages)
+        );
+        let forked = session_transcript(messages, Some("new"));
+        let lines: Vec<&str> = forked.lines().collect();
+        let first: Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(first["sessionId"], "new");
+        assert_eq!(first["type"], "user");
+        assert_eq!(&lines[1..], ["not json", "{\"type\":\"summary\"}"]);
+        assert!(forked.ends_with('\n'));
+        assert_eq!(session_transcript("", Some("new")), "");
+    }
+}
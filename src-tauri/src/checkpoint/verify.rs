@@ -0,0 +1,364 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::PathBuf;
+use zstd::stream::decode_all;
+
+use super::{storage::CheckpointStorage, CheckpointPaths};
+
+/// Result of checking a session's checkpoints against the content pool
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifyReport {
+    /// Session that was verified
+    pub session_id: String,
+    /// Number of checkpoints in the timeline
+    pub checkpoints_checked: usize,
+    /// Number of distinct pool objects that were re-hashed
+    pub blobs_checked: usize,
+    /// Problems that make a checkpoint impossible to restore
+    pub issues: Vec<IntegrityIssue>,
+    /// Checkpoint data on disk that the timeline does not reference
+    pub orphaned_checkpoints: Vec<String>,
+    /// Pool objects that no checkpoint in the project references
+    pub orphaned_blobs: Vec<String>,
+    /// Whether repair mode was used
+    pub repaired: bool,
+    /// Checkpoints removed from the timeline by repair
+    pub pruned_checkpoints: Vec<String>,
+    /// Pool objects deleted by repair
+    pub removed_blobs: usize,
+}
+
+/// A single problem found while verifying a checkpoint
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IntegrityIssue {
+    /// Checkpoint the problem belongs to
+    pub checkpoint_id: String,
+    /// What is wrong
+    pub kind: IssueKind,
+    /// Affected file, for problems with file content
+    pub file_path: Option<PathBuf>,
+    /// Affected pool object, for problems with file content
+    pub hash: Option<String>,
+    /// Human-readable details
+    pub message: String,
+}
+
+/// Kind of integrity problem
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    /// Checkpoint metadata or messages are missing or unreadable
+    MissingCheckpointData,
+    /// The checkpoint's file references cannot be read
+    InvalidReferences,
+    /// A referenced pool object does not exist
+    MissingBlob,
+    /// A pool object cannot be decompressed or does not match its hash
+    CorruptBlob,
+}
+
+/// Outcome of re-hashing one pool object
+#[derive(Clone)]
+enum BlobStatus {
+    Ok,
+    Missing,
+    Corrupt(String),
+}
+
+impl CheckpointStorage {
+    /// Verify every checkpoint of a session and optionally repair the damage
+    ///
+    /// Each checkpoint's metadata, messages and file references are read and
+    /// every referenced pool object is decompressed and re-hashed. In repair
+    /// mode, corrupt objects are deleted, checkpoints with any problem are
+    /// pruned from the timeline, orphaned checkpoint data is removed and the
+    /// pool is garbage collected.
+    pub fn verify_session(
+        &self,
+        project_id: &str,
+        session_id: &str,
+        repair: bool,
+    ) -> Result<VerifyReport> {
+        let paths = CheckpointPaths::new(&self.claude_dir, project_id, session_id);
+        let mut report = VerifyReport {
+            session_id: session_id.to_string(),
+            repaired: repair,
+            ..Default::default()
+        };
+
+        if !paths.timeline_file.exists() {
+            return Ok(report);
+        }
+        let mut timeline = self.load_timeline(&paths.timeline_file)?;
+
+        let checkpoint_ids = timeline.checkpoint_ids();
+        report.checkpoints_checked = checkpoint_ids.len();
+
+        let mut blob_status: HashMap<String, BlobStatus> = HashMap::new();
+        for checkpoint_id in &checkpoint_ids {
+            report
+                .issues
+                .extend(self.verify_checkpoint(&paths, checkpoint_id, &mut blob_status));
+        }
+        report.blobs_checked = blob_status.len();
+
+        report.orphaned_checkpoints = find_orphaned_checkpoints(&paths, &checkpoint_ids)?;
+
+        let referenced = self.project_reference_counts(project_id)?;
+        let objects_dir = paths.content_pool_dir.join("objects");
+        if objects_dir.is_dir() {
+            for entry in fs::read_dir(&objects_dir)? {
+                let name = entry?.file_name().to_string_lossy().to_string();
+                if !name.starts_with('.') && !referenced.contains_key(&name) {
+                    report.orphaned_blobs.push(name);
+                }
+            }
+            report.orphaned_blobs.sort();
+        }
+
+        if !repair {
+            return Ok(report);
+        }
+
+        for (hash, status) in &blob_status {
+            if matches!(status, BlobStatus::Corrupt(_)) {
+                self.discard_content_object(&paths, hash)?;
+                report.removed_blobs += 1;
+            }
+        }
+
+        let unrecoverable: BTreeSet<&str> = report
+            .issues
+            .iter()
+            .map(|issue| issue.checkpoint_id.as_str())
+            .collect();
+        for checkpoint_id in unrecoverable {
+            self.prune_checkpoint(&paths, &mut timeline, checkpoint_id)
+                .with_context(|| format!("Failed to prune checkpoint {}", checkpoint_id))?;
+            report.pruned_checkpoints.push(checkpoint_id.to_string());
+        }
+        self.save_timeline(&paths.timeline_file, &timeline)?;
+
+        for checkpoint_id in &report.orphaned_checkpoints {
+            self.remove_checkpoint(&paths, checkpoint_id)?;
+        }
+
+        report.removed_blobs += self.garbage_collect_content(project_id, session_id)?;
+
+        log::info!(
+            "Repaired session {}: pruned {} checkpoints, removed {} orphaned checkpoints and {} pool objects",
+            session_id,
+            report.pruned_checkpoints.len(),
+            report.orphaned_checkpoints.len(),
+            report.removed_blobs
+        );
+
+        Ok(report)
+    }
+
+    /// Check one checkpoint, re-hashing pool objects not seen before
+    fn verify_checkpoint(
+        &self,
+        paths: &CheckpointPaths,
+        checkpoint_id: &str,
+        blob_status: &mut HashMap<String, BlobStatus>,
+    ) -> Vec<IntegrityIssue> {
+        let mut issues = Vec::new();
+        let issue = |kind, file_path, hash, message: String| IntegrityIssue {
+            checkpoint_id: checkpoint_id.to_string(),
+            kind,
+            file_path,
+            hash,
+            message,
+        };
+
+        let metadata_ok = fs::read_to_string(paths.checkpoint_metadata_file(checkpoint_id))
+            .ok()
+            .and_then(|json| serde_json::from_str::<super::Checkpoint>(&json).ok())
+            .is_some();
+        if !metadata_ok {
+            issues.push(issue(
+                IssueKind::MissingCheckpointData,
+                None,
+                None,
+                "Checkpoint metadata is missing or unreadable".to_string(),
+            ));
+        }
+
+        let messages_ok = fs::read(paths.checkpoint_messages_file(checkpoint_id))
+            .ok()
+            .and_then(|compressed| decode_all(&compressed[..]).ok())
+            .is_some_and(|messages| String::from_utf8(messages).is_ok());
+        if !messages_ok {
+            issues.push(issue(
+                IssueKind::MissingCheckpointData,
+                None,
+                None,
+                "Checkpoint messages are missing or unreadable".to_string(),
+            ));
+        }
+
+        let file_refs = match Self::read_checkpoint_refs(paths, checkpoint_id) {
+            Ok(file_refs) => file_refs,
+            Err(e) => {
+                issues.push(issue(
+                    IssueKind::InvalidReferences,
+                    None,
+                    None,
+                    format!("{:#}", e),
+                ));
+                return issues;
+            }
+        };
+
+        for file_ref in file_refs.into_iter().filter(|r| !r.is_deleted) {
+            let status = blob_status
+                .entry(file_ref.hash.clone())
+                .or_insert_with(|| self.check_blob(paths, &file_ref.hash))
+                .clone();
+            match status {
+                BlobStatus::Ok => {}
+                BlobStatus::Missing => issues.push(issue(
+                    IssueKind::MissingBlob,
+                    Some(file_ref.path),
+                    Some(file_ref.hash),
+                    "Content is missing from the pool".to_string(),
+                )),
+                BlobStatus::Corrupt(reason) => issues.push(issue(
+                    IssueKind::CorruptBlob,
+                    Some(file_ref.path),
+                    Some(file_ref.hash),
+                    reason,
+                )),
+            }
+        }
+
+        issues
+    }
+
+    /// Decompress a pool object and compare its hash with its name
+    ///
+    /// A chunked object is corrupt if any of its chunks is missing or damaged.
+    fn check_blob(&self, paths: &CheckpointPaths, hash: &str) -> BlobStatus {
+        if !paths.content_object_path(hash).exists() {
+            return BlobStatus::Missing;
+        }
+
+        let mut hasher = Sha256::new();
+        match self.copy_content_object(paths, hash, &mut hasher) {
+            Ok(()) => {
+                let actual = format!("{:x}", hasher.finalize());
+                if actual == hash {
+                    BlobStatus::Ok
+                } else {
+                    BlobStatus::Corrupt(format!("Content hash mismatch: found {}", actual))
+                }
+            }
+            Err(e) => BlobStatus::Corrupt(format!("{:#}", e)),
+        }
+    }
+}
+
+/// Checkpoint directories, manifests and refs that are neither in the timeline nor being written
+fn find_orphaned_checkpoints(
+    paths: &CheckpointPaths,
+    checkpoint_ids: &[String],
+) -> Result<Vec<String>> {
+    let mut stored_ids = CheckpointStorage::list_manifest_ids(paths)?;
+    if paths.checkpoints_dir.is_dir() {
+        for entry in
+            fs::read_dir(&paths.checkpoints_dir).context("Failed to list checkpoint data")?
+        {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                stored_ids.insert(entry.file_name().to_string_lossy().to_string());
+            }
+        }
+    }
+
+    Ok(stored_ids
+        .into_iter()
+        .filter(|id| !checkpoint_ids.contains(id) && !paths.journal_entry_file(id).exists())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checkpoint::manager::CheckpointManager;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_verify_and_repair_damaged_pool() {
+        let temp_dir = TempDir::new().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        let project_path = temp_dir.path().join("project");
+        fs::create_dir_all(&project_path).unwrap();
+
+        let manager = CheckpointManager::new(
+            "proj".to_string(),
+            "sess".to_string(),
+            project_path.clone(),
+            claude_dir.clone(),
+        )
+        .await
+        .unwrap();
+        let mut ids = Vec::new();
+        for content in ["one", "two", "three"] {
+            fs::write(project_path.join("a.txt"), content).unwrap();
+            let result = manager.create_checkpoint(None, None).await.unwrap();
+            ids.push(result.checkpoint.id);
+        }
+
+        let storage = CheckpointStorage::new(claude_dir.clone());
+        let paths = CheckpointPaths::new(&claude_dir, "proj", "sess");
+        assert!(storage
+            .verify_session("proj", "sess", false)
+            .unwrap()
+            .issues
+            .is_empty());
+
+        let missing = CheckpointStorage::calculate_file_hash(b"two");
+        let corrupt = CheckpointStorage::calculate_file_hash(b"three");
+        fs::remove_file(paths.content_object_path(&missing)).unwrap();
+        fs::write(paths.content_object_path(&corrupt), b"garbage").unwrap();
+        fs::create_dir_all(paths.checkpoint_dir("stray")).unwrap();
+
+        let report = storage.verify_session("proj", "sess", false).unwrap();
+        let found: Vec<(&str, IssueKind)> = report
+            .issues
+            .iter()
+            .map(|issue| (issue.checkpoint_id.as_str(), issue.kind))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (ids[1].as_str(), IssueKind::MissingBlob),
+                (ids[2].as_str(), IssueKind::CorruptBlob),
+            ]
+        );
+        assert_eq!(report.issues[1].hash.as_deref(), Some(corrupt.as_str()));
+        assert_eq!(report.orphaned_checkpoints, vec!["stray".to_string()]);
+        // Checking alone changes nothing
+        assert!(paths.content_object_path(&corrupt).exists());
+
+        let mut report = storage.verify_session("proj", "sess", true).unwrap();
+        report.pruned_checkpoints.sort();
+        let mut damaged = vec![ids[1].clone(), ids[2].clone()];
+        damaged.sort();
+        assert_eq!(report.pruned_checkpoints, damaged);
+        assert!(!paths.content_object_path(&corrupt).exists());
+        assert!(!paths.checkpoint_dir("stray").exists());
+        let timeline = storage.load_timeline(&paths.timeline_file).unwrap();
+        assert_eq!(timeline.checkpoint_ids(), vec![ids[0].clone()]);
+
+        let report = storage.verify_session("proj", "sess", false).unwrap();
+        assert!(report.issues.is_empty());
+        assert!(report.orphaned_checkpoints.is_empty());
+    }
+}
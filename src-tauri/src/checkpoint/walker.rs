@@ -0,0 +1,369 @@
+use anyhow::Result;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::WalkBuilder;
+use std::collections::{BTreeSet, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::EntryKind;
+
+/// Project-local ignore file with gitignore syntax, relative to the project root
+pub const CHECKPOINT_IGNORE_FILE: &str = ".claude/checkpointignore";
+
+/// Walks a project directory using the same ignore rules for snapshot and restore
+///
+/// Files are skipped when they match `.gitignore` (at any level),
+/// `.git/info/exclude`, the user's global git excludes file or the project's
+/// `.claude/checkpointignore`. Hidden directories such as `.git` are never
+/// entered. The rules apply even when the project is not a git repository.
+pub struct ProjectWalker {
+    root: PathBuf,
+    checkpoint_ignore: Arc<Gitignore>,
+}
+
+impl ProjectWalker {
+    /// Create a walker rooted at the project directory
+    pub fn new(root: &Path) -> Self {
+        let mut builder = GitignoreBuilder::new(root);
+        let ignore_file = root.join(CHECKPOINT_IGNORE_FILE);
+        if ignore_file.is_file() {
+            if let Some(e) = builder.add(&ignore_file) {
+                log::warn!("Failed to parse {}: {}", ignore_file.display(), e);
+            }
+        }
+        let checkpoint_ignore = builder.build().unwrap_or_else(|e| {
+            log::warn!("Failed to build checkpoint ignore rules: {}", e);
+            Gitignore::empty()
+        });
+
+        Self {
+            root: root.to_path_buf(),
+            checkpoint_ignore: Arc::new(checkpoint_ignore),
+        }
+    }
+
+    /// Collect all non-ignored files, symlinks and empty directories as paths
+    /// relative to the project root
+    ///
+    /// Symlinks are not followed. Directories holding only ignored files are
+    /// not empty and are left out.
+    pub fn collect_entries(&self) -> Result<Vec<(PathBuf, EntryKind)>> {
+        self.collect_entries_under(Path::new(""))
+    }
+
+    /// Like `collect_entries`, but only below `dir`, a directory relative to
+    /// the project root
+    pub fn collect_entries_under(&self, dir: &Path) -> Result<Vec<(PathBuf, EntryKind)>> {
+        let mut entries = Vec::new();
+        let mut dirs = BTreeSet::new();
+        let mut parents = HashSet::new();
+
+        for entry in self.build_walk(dir).build() {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    log::warn!("Skipping unreadable entry while walking project: {}", e);
+                    continue;
+                }
+            };
+            if entry.depth() == 0 {
+                continue;
+            }
+            let Some(file_type) = entry.file_type() else {
+                continue;
+            };
+            let Ok(rel) = entry.path().strip_prefix(&self.root) else {
+                continue;
+            };
+            if let Some(parent) = rel.parent() {
+                parents.insert(parent.to_path_buf());
+            }
+
+            if file_type.is_dir() {
+                dirs.insert(rel.to_path_buf());
+            } else if file_type.is_symlink() {
+                entries.push((rel.to_path_buf(), EntryKind::Symlink));
+            } else if file_type.is_file() {
+                entries.push((rel.to_path_buf(), EntryKind::File));
+            }
+        }
+
+        entries.extend(
+            dirs.into_iter()
+                .filter(|dir| !parents.contains(dir) && is_empty_dir(&self.root.join(dir)))
+                .map(|dir| (dir, EntryKind::Directory)),
+        );
+        Ok(entries)
+    }
+
+    /// Collect `dir` and every non-ignored directory below it, relative to
+    /// the project root
+    pub fn collect_dirs_under(&self, dir: &Path) -> Vec<PathBuf> {
+        self.build_walk(dir)
+            .build()
+            .flatten()
+            .filter(|entry| entry.file_type().is_some_and(|t| t.is_dir()))
+            .filter_map(|entry| {
+                let rel = entry.path().strip_prefix(&self.root).ok()?;
+                Some(rel.to_path_buf())
+            })
+            .collect()
+    }
+
+    /// Build a matcher that applies this walker's rules to individual paths
+    ///
+    /// The `.gitignore` files are collected once, so the matcher must be
+    /// rebuilt when one of them changes.
+    pub fn ignore_matcher(&self) -> IgnoreMatcher {
+        let mut gitignores = Vec::new();
+
+        for entry in self.build_walk(Path::new("")).build().flatten() {
+            if entry.file_name() != ".gitignore" || !entry.file_type().is_some_and(|t| t.is_file())
+            {
+                continue;
+            }
+            let Some(dir) = entry.path().parent() else {
+                continue;
+            };
+            let mut builder = GitignoreBuilder::new(dir);
+            if builder.add(entry.path()).is_none() {
+                if let Ok(gitignore) = builder.build() {
+                    gitignores.push(gitignore);
+                }
+            }
+        }
+
+        let mut builder = GitignoreBuilder::new(&self.root);
+        builder.add(self.root.join(".git").join("info").join("exclude"));
+        if let Ok(exclude) = builder.build() {
+            gitignores.push(exclude);
+        }
+        let (global, _) = GitignoreBuilder::new(&self.root).build_global();
+        gitignores.push(global);
+
+        // Rules in deeper directories take precedence
+        gitignores.sort_by_key(|g| std::cmp::Reverse(g.path().components().count()));
+
+        IgnoreMatcher {
+            root: self.root.clone(),
+            gitignores,
+            checkpoint_ignore: Arc::clone(&self.checkpoint_ignore),
+        }
+    }
+
+    /// Walk from `dir`, relative to the project root; parent directories'
+    /// `.gitignore` files still apply
+    fn build_walk(&self, dir: &Path) -> WalkBuilder {
+        let mut builder = WalkBuilder::new(self.root.join(dir));
+        builder
+            .hidden(false)
+            .ignore(false)
+            .git_ignore(true)
+            .git_exclude(true)
+            .git_global(true)
+            .require_git(false)
+            .follow_links(false);
+
+        let root = self.root.clone();
+        let checkpoint_ignore = Arc::clone(&self.checkpoint_ignore);
+        builder.filter_entry(move |entry| {
+            if entry.depth() == 0 {
+                return true;
+            }
+
+            let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+
+            // Skip hidden directories like .git
+            if is_dir && entry.file_name().to_string_lossy().starts_with('.') {
+                return false;
+            }
+
+            match entry.path().strip_prefix(&root) {
+                Ok(rel) => !checkpoint_ignore
+                    .matched_path_or_any_parents(rel, is_dir)
+                    .is_ignore(),
+                Err(_) => true,
+            }
+        });
+
+        builder
+    }
+}
+
+/// Whether a path is a directory with nothing in it
+pub fn is_empty_dir(path: &Path) -> bool {
+    fs::read_dir(path).is_ok_and(|mut entries| entries.next().is_none())
+}
+
+/// Applies a project's ignore rules to single paths, such as watcher events
+pub struct IgnoreMatcher {
+    root: PathBuf,
+    gitignores: Vec<Gitignore>,
+    checkpoint_ignore: Arc<Gitignore>,
+}
+
+impl IgnoreMatcher {
+    /// Whether a path would be skipped by `ProjectWalker`
+    ///
+    /// Paths outside the project are always ignored.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let Ok(rel) = path.strip_prefix(&self.root) else {
+            return true;
+        };
+
+        // Hidden directories like .git are never entered
+        let mut dirs: Vec<_> = rel.components().collect();
+        if !is_dir {
+            dirs.pop();
+        }
+        if dirs
+            .iter()
+            .any(|c| c.as_os_str().to_string_lossy().starts_with('.'))
+        {
+            return true;
+        }
+
+        if self
+            .checkpoint_ignore
+            .matched_path_or_any_parents(rel, is_dir)
+            .is_ignore()
+        {
+            return true;
+        }
+
+        for gitignore in &self.gitignores {
+            let scope = gitignore.path();
+            if !scope.as_os_str().is_empty() && !path.starts_with(scope) {
+                continue;
+            }
+            let matched = gitignore.matched_path_or_any_parents(path, is_dir);
+            if matched.is_ignore() {
+                return true;
+            }
+            if matched.is_whitelist() {
+                return false;
+            }
+        }
+
+        false
+    }
+
+    /// Whether a path is a file that defines ignore rules
+    pub fn is_rules_file(&self, path: &Path) -> bool {
+        path.file_name().is_some_and(|n| n == ".gitignore")
+            || path.ends_with(CHECKPOINT_IGNORE_FILE)
+            || path.ends_with(".git/info/exclude")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn project(files: &[&str]) -> TempDir {
+        let root = TempDir::new().unwrap();
+        for file in files {
+            let path = root.path().join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, file.as_bytes()).unwrap();
+        }
+        root
+    }
+
+    fn sorted(mut paths: Vec<PathBuf>) -> Vec<PathBuf> {
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn test_gitignore_and_checkpoint_ignore() {
+        let root = project(&[
+            ".gitignore",
+            ".claude/checkpointignore",
+            "src/main.rs",
+            "src/.gitignore",
+            "src/generated.rs",
+            "build.log",
+            "docs/draft.md",
+            "docs/readme.md",
+        ]);
+        fs::write(root.path().join(".gitignore"), "*.log\n").unwrap();
+        fs::write(root.path().join("src/.gitignore"), "generated.rs\n").unwrap();
+        fs::write(root.path().join(CHECKPOINT_IGNORE_FILE), "docs/draft.md\n").unwrap();
+
+        let files = sorted(
+            ProjectWalker::new(root.path())
+                .collect_entries()
+                .unwrap()
+                .into_iter()
+                .map(|(path, _)| path)
+                .collect(),
+        );
+        assert_eq!(
+            files,
+            vec![
+                PathBuf::from(".gitignore"),
+                PathBuf::from("docs/readme.md"),
+                PathBuf::from("src/.gitignore"),
+                PathBuf::from("src/main.rs"),
+            ]
+        );
+
+        let matcher = ProjectWalker::new(root.path()).ignore_matcher();
+        for (path, ignored) in [
+            ("build.log", true),
+            ("src/generated.rs", true),
+            ("docs/draft.md", true),
+            ("docs/readme.md", false),
+            ("src/main.rs", false),
+        ] {
+            assert_eq!(
+                matcher.is_ignored(&root.path().join(path), false),
+                ignored,
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn test_hidden_directories_and_empty_directories() {
+        let root = project(&[".git/HEAD", ".cache/data", ".env", "src/lib.rs"]);
+        fs::create_dir_all(root.path().join("empty")).unwrap();
+        fs::create_dir_all(root.path().join("ignored_only")).unwrap();
+        fs::write(root.path().join("ignored_only/a.tmp"), b"").unwrap();
+        fs::write(root.path().join(".gitignore"), "*.tmp\n").unwrap();
+
+        let entries = ProjectWalker::new(root.path()).collect_entries().unwrap();
+        let mut files: Vec<PathBuf> = entries
+            .iter()
+            .filter(|(_, kind)| *kind == EntryKind::File)
+            .map(|(path, _)| path.clone())
+            .collect();
+        files.sort();
+        assert_eq!(
+            files,
+            vec![
+                PathBuf::from(".env"),
+                PathBuf::from(".gitignore"),
+                PathBuf::from("src/lib.rs"),
+            ]
+        );
+
+        // Only truly empty directories are recorded
+        let dirs: Vec<&PathBuf> = entries
+            .iter()
+            .filter(|(_, kind)| *kind == EntryKind::Directory)
+            .map(|(path, _)| path)
+            .collect();
+        assert_eq!(dirs, vec![&PathBuf::from("empty")]);
+
+        let matcher = ProjectWalker::new(root.path()).ignore_matcher();
+        assert!(matcher.is_ignored(&root.path().join(".git/HEAD"), false));
+        assert!(matcher.is_ignored(&root.path().join(".cache"), true));
+        assert!(!matcher.is_ignored(&root.path().join(".env"), false));
+        assert!(matcher.is_ignored(Path::new("/elsewhere/file"), false));
+    }
+}
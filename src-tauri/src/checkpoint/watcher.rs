@@ -0,0 +1,251 @@
+use anyhow::{Context, Result};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use super::{manager::CheckpointManager, walker::ProjectWalker};
+
+/// How long to collect events before updating the file tracker
+const DEBOUNCE: Duration = Duration::from_millis(50);
+
+/// Live tracking state shared between a manager and its watcher
+#[derive(Debug, Default)]
+pub struct LiveTracking {
+    /// Whether the file tracker is being kept up to date by a watcher
+    active: AtomicBool,
+    /// Events received but not yet applied to the file tracker
+    pending_events: AtomicUsize,
+}
+
+impl LiveTracking {
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::Acquire)
+    }
+
+    pub fn set_active(&self, active: bool) {
+        self.active.store(active, Ordering::Release);
+    }
+
+    pub fn has_pending_events(&self) -> bool {
+        self.pending_events.load(Ordering::Acquire) > 0
+    }
+}
+
+/// Watches a project directory and feeds changes into a manager's file tracker
+///
+/// The watcher first brings the tracker in sync with a full scan, then only
+/// re-reads files that the operating system reports as changed. Dropping it
+/// stops watching and returns the manager to full scans.
+///
+/// Each non-ignored directory gets its own watch, so ignored trees such as
+/// `node_modules` or `target` don't count against the system's watch limit.
+pub struct ProjectWatcher {
+    _watcher: Arc<Mutex<RecommendedWatcher>>,
+    task: JoinHandle<()>,
+    live: Arc<LiveTracking>,
+}
+
+impl ProjectWatcher {
+    /// Start watching the manager's project directory
+    pub fn start(manager: Arc<CheckpointManager>) -> Result<Self> {
+        let root = manager.project_path().to_path_buf();
+        let live = manager.live_tracking();
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let callback_live = Arc::clone(&live);
+        let mut watcher = notify::recommended_watcher(move |event| {
+            callback_live.pending_events.fetch_add(1, Ordering::AcqRel);
+            if tx.send(event).is_err() {
+                callback_live.pending_events.fetch_sub(1, Ordering::AcqRel);
+            }
+        })
+        .context("Failed to create file watcher")?;
+        watch_tree(&mut watcher, &root, Path::new(""))?;
+        let watcher = Arc::new(Mutex::new(watcher));
+
+        let task_live = Arc::clone(&live);
+        let task_watcher = Arc::clone(&watcher);
+        let task = tokio::spawn(async move {
+            let mut matcher = ProjectWalker::new(&root).ignore_matcher();
+            manager.sync_file_tracker().await;
+
+            while let Some(first) = rx.recv().await {
+                tokio::time::sleep(DEBOUNCE).await;
+                let mut batch = vec![first];
+                while let Ok(event) = rx.try_recv() {
+                    batch.push(event);
+                }
+                let batch_len = batch.len();
+
+                let mut rescan = false;
+                let mut paths = BTreeSet::new();
+                for event in batch {
+                    match event {
+                        Ok(event) => {
+                            rescan |= event.need_rescan();
+                            if !matches!(event.kind, EventKind::Access(_)) {
+                                paths.extend(event.paths);
+                            }
+                        }
+                        Err(e) => {
+                            log::warn!("File watcher error for {}: {}", root.display(), e);
+                            rescan = true;
+                        }
+                    }
+                }
+
+                if paths.iter().any(|p| matcher.is_rules_file(p)) {
+                    matcher = ProjectWalker::new(&root).ignore_matcher();
+                    rescan = true;
+                }
+
+                let changed: Vec<PathBuf> = paths
+                    .into_iter()
+                    .filter(|p| !matcher.is_ignored(p, p.is_dir()))
+                    .filter_map(|p| p.strip_prefix(&root).ok().map(PathBuf::from))
+                    .collect();
+
+                // Watch new directories before reading them, so nothing
+                // created in between is missed
+                let new_dirs: Vec<&Path> = if rescan {
+                    vec![Path::new("")]
+                } else {
+                    changed
+                        .iter()
+                        .filter(|rel| root.join(rel).is_dir())
+                        .map(PathBuf::as_path)
+                        .collect()
+                };
+                let watched = {
+                    let mut watcher = task_watcher.lock().unwrap();
+                    new_dirs
+                        .into_iter()
+                        .try_for_each(|dir| watch_tree(&mut watcher, &root, dir))
+                };
+                if let Err(e) = watched {
+                    log::warn!("Stopped watching {}: {:#}", root.display(), e);
+                    break;
+                }
+
+                if rescan {
+                    manager.sync_file_tracker().await;
+                } else {
+                    manager.track_watched_paths(changed).await;
+                }
+
+                task_live
+                    .pending_events
+                    .fetch_sub(batch_len, Ordering::AcqRel);
+            }
+
+            task_live.set_active(false);
+        });
+
+        Ok(Self {
+            _watcher: watcher,
+            task,
+            live,
+        })
+    }
+}
+
+/// Watch `dir`, relative to the project root, and every non-ignored
+/// directory below it
+///
+/// Running out of watches is an error, since changes in the unwatched
+/// directories would go unnoticed; a directory that disappears first is not.
+fn watch_tree(watcher: &mut RecommendedWatcher, root: &Path, dir: &Path) -> Result<()> {
+    for rel in ProjectWalker::new(root).collect_dirs_under(dir) {
+        let path = root.join(&rel);
+        match watcher.watch(&path, RecursiveMode::NonRecursive) {
+            Ok(()) => {}
+            Err(e) if matches!(e.kind, notify::ErrorKind::MaxFilesWatch) => {
+                return Err(e).with_context(|| {
+                    format!(
+                        "Reached the system's file watch limit at {}; on Linux it can be raised \
+                         with fs.inotify.max_user_watches",
+                        path.display()
+                    )
+                });
+            }
+            Err(e) if path.exists() => {
+                return Err(e).with_context(|| format!("Failed to watch {}", path.display()));
+            }
+            Err(_) => {}
+        }
+    }
+    Ok(())
+}
+
+impl Drop for ProjectWatcher {
+    fn drop(&mut self) {
+        self.task.abort();
+        self.live.set_active(false);
+        self.live.pending_events.store(0, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checkpoint::state::CheckpointState;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_directory_moved_into_project_is_tracked() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_path = temp_dir.path().join("project");
+        fs::create_dir_all(project_path.join("target")).unwrap();
+        fs::write(project_path.join(".gitignore"), "target/\n").unwrap();
+
+        let state = CheckpointState::new();
+        state.set_claude_dir(temp_dir.path().join("claude")).await;
+        let manager = state
+            .get_or_create_manager("sess".to_string(), "proj".to_string(), project_path.clone())
+            .await
+            .unwrap();
+        for _ in 0..100 {
+            if manager.live_tracking().is_active() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(manager.live_tracking().is_active());
+        manager.create_checkpoint(None, None).await.unwrap();
+
+        // A whole tree appears at once, with a single event for its top directory
+        let outside = temp_dir.path().join("vendor");
+        fs::create_dir_all(outside.join("lib/nested")).unwrap();
+        fs::write(outside.join("lib/a.rs"), b"a").unwrap();
+        fs::write(outside.join("lib/nested/b.rs"), b"b").unwrap();
+        fs::rename(&outside, project_path.join("vendor")).unwrap();
+        fs::write(project_path.join("target/out.o"), b"o").unwrap();
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        let since = chrono::Utc::now() - chrono::Duration::days(1);
+        let mut changed: Vec<String> = manager
+            .get_files_modified_since(since)
+            .await
+            .iter()
+            .map(|path| path.to_string_lossy().into_owned())
+            .collect();
+        changed.sort();
+        assert_eq!(changed, vec!["vendor/lib/a.rs", "vendor/lib/nested/b.rs"]);
+
+        // Files created later in the moved tree are seen through its new watches
+        fs::write(project_path.join("vendor/lib/nested/c.rs"), b"c").unwrap();
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert!(manager
+            .get_files_modified_since(since)
+            .await
+            .contains(&PathBuf::from("vendor/lib/nested/c.rs")));
+
+        state.remove_manager("sess").await;
+    }
+}
@@ -1541,42 +1541,62 @@ pub async fn create_checkpoint(
         .await
         .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
 
-    // Always load current session messages from the JSONL file
+    // Track session messages from the JSONL file that the manager has not seen yet
+    let messages = read_untracked_session_messages(
+        &project_id,
+        &session_id,
+        manager.message_count().await,
+        message_index,
+    )?;
+    for message in messages {
+        manager
+            .track_message(message)
+            .await
+            .map_err(|e| format!("Failed to track message: {}", e))?;
+    }
+
+    manager
+        .create_checkpoint(description, None)
+        .await
+        .map_err(|e| format!("Failed to create checkpoint: {}", e))
+}
+
+/// Reads the lines of a session's JSONL file after the first `tracked`,
+/// up to and including `message_index` if given
+fn read_untracked_session_messages(
+    project_id: &str,
+    session_id: &str,
+    tracked: usize,
+    message_index: Option<usize>,
+) -> Result<Vec<String>, String> {
     let session_path = get_claude_dir()
         .map_err(|e| e.to_string())?
         .join("projects")
-        .join(&project_id)
+        .join(project_id)
         .join(format!("{}.jsonl", session_id));
+    if !session_path.exists() {
+        return Ok(Vec::new());
+    }
 
-    if session_path.exists() {
-        let file = fs::File::open(&session_path)
-            .map_err(|e| format!("Failed to open session file: {}", e))?;
-        let reader = BufReader::new(file);
+    let file = fs::File::open(&session_path)
+        .map_err(|e| format!("Failed to open session file: {}", e))?;
+    let reader = BufReader::new(file);
 
-        let mut line_count = 0;
-        for line in reader.lines() {
-            if let Some(index) = message_index {
-                if line_count > index {
-                    break;
-                }
-            }
-            if let Ok(line) = line {
-                manager
-                    .track_message(line)
-                    .await
-                    .map_err(|e| format!("Failed to track message: {}", e))?;
-            }
-            line_count += 1;
+    let mut messages = Vec::new();
+    for (line_count, line) in reader.lines().enumerate().skip(tracked) {
+        if message_index.is_some_and(|index| line_count > index) {
+            break;
+        }
+        if let Ok(line) = line {
+            messages.push(line);
         }
     }
-
-    manager
-        .create_checkpoint(description, None)
-        .await
-        .map_err(|e| format!("Failed to create checkpoint: {}", e))
+    Ok(messages)
 }
 
 /// Restores a session to a specific checkpoint
+///
+/// `checkpoint_id` may also be a tag or a unique checkpoint ID prefix.
 #[tauri::command]
 pub async fn restore_checkpoint(
     app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
@@ -1600,31 +1620,95 @@ pub async fn restore_checkpoint(
         .await
         .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
 
+    let checkpoint_id = manager
+        .resolve_checkpoint(&checkpoint_id)
+        .await
+        .map_err(|e| e.to_string())?;
+
     let result = manager
         .restore_checkpoint(&checkpoint_id)
         .await
         .map_err(|e| format!("Failed to restore checkpoint: {}", e))?;
 
-    // Update the session JSONL file with restored messages
-    let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
-    let session_path = claude_dir
-        .join("projects")
-        .join(&result.checkpoint.project_id)
-        .join(format!("{}.jsonl", session_id));
-
-    // The manager has already restored the messages internally,
-    // but we need to update the actual session file
-    let (_, _, messages) = manager
-        .storage
-        .load_checkpoint(&result.checkpoint.project_id, &session_id, &checkpoint_id)
-        .map_err(|e| format!("Failed to load checkpoint data: {}", e))?;
-
-    fs::write(&session_path, messages)
+    // The manager has already restored the messages internally, but the
+    // session file has to be rewound too for a resume to see them
+    manager
+        .write_session_transcript(&checkpoint_id, &session_id)
         .map_err(|e| format!("Failed to update session file: {}", e))?;
 
     Ok(result)
 }
 
+/// Restores only the given paths from a checkpoint
+///
+/// `paths` may contain globs. The rest of the project, the session messages
+/// and the current checkpoint are left unchanged. `checkpoint_id` may also be
+/// a tag or a unique checkpoint ID prefix.
+#[tauri::command]
+pub async fn restore_checkpoint_files(
+    app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
+    checkpoint_id: String,
+    paths: Vec<String>,
+    session_id: String,
+    project_id: String,
+    project_path: String,
+) -> Result<crate::checkpoint::CheckpointResult, String> {
+    log::info!(
+        "Restoring {:?} from checkpoint: {} for session: {}",
+        paths,
+        checkpoint_id,
+        session_id
+    );
+
+    let manager = app
+        .get_or_create_manager(session_id, project_id, PathBuf::from(&project_path))
+        .await
+        .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
+
+    let checkpoint_id = manager
+        .resolve_checkpoint(&checkpoint_id)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    manager
+        .restore_files(&checkpoint_id, &paths)
+        .await
+        .map_err(|e| format!("Failed to restore files: {}", e))
+}
+
+/// Previews the changes restoring a checkpoint would make, without touching disk
+///
+/// `checkpoint_id` may also be a tag or a unique checkpoint ID prefix.
+#[tauri::command]
+pub async fn preview_restore_checkpoint(
+    app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
+    checkpoint_id: String,
+    session_id: String,
+    project_id: String,
+    project_path: String,
+) -> Result<crate::checkpoint::RestorePlan, String> {
+    log::info!(
+        "Previewing restore of checkpoint: {} for session: {}",
+        checkpoint_id,
+        session_id
+    );
+
+    let manager = app
+        .get_or_create_manager(session_id, project_id, PathBuf::from(&project_path))
+        .await
+        .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
+
+    let checkpoint_id = manager
+        .resolve_checkpoint(&checkpoint_id)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    manager
+        .plan_restore(&checkpoint_id)
+        .await
+        .map_err(|e| format!("Failed to plan restore: {}", e))
+}
+
 /// Lists all checkpoints for a session
 #[tauri::command]
 pub async fn list_checkpoints(
@@ -1647,7 +1731,13 @@ pub async fn list_checkpoints(
     Ok(manager.list_checkpoints().await)
 }
 
-/// Forks a new timeline branch from a checkpoint
+/// Forks a checkpoint into a new Claude session
+///
+/// `checkpoint_id` may also be a tag or a unique checkpoint ID prefix in the
+/// source session. The new session's transcript holds exactly the
+/// conversation up to the checkpoint, so resuming `new_session_id` continues
+/// from there, and its timeline starts with a checkpoint of the restored
+/// files. The source session can still be resumed where it was.
 #[tauri::command]
 pub async fn fork_from_checkpoint(
     app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
@@ -1664,37 +1754,65 @@ pub async fn fork_from_checkpoint(
         new_session_id
     );
 
-    let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
+    let source = app
+        .get_or_create_manager(
+            session_id.clone(),
+            project_id.clone(),
+            PathBuf::from(&project_path),
+        )
+        .await
+        .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
 
-    // First, copy the session file to the new session
-    let source_session_path = claude_dir
-        .join("projects")
-        .join(&project_id)
-        .join(format!("{}.jsonl", session_id));
-    let new_session_path = claude_dir
-        .join("projects")
-        .join(&project_id)
-        .join(format!("{}.jsonl", new_session_id));
+    let checkpoint_id = source
+        .resolve_checkpoint(&checkpoint_id)
+        .await
+        .map_err(|e| e.to_string())?;
 
-    if source_session_path.exists() {
-        fs::copy(&source_session_path, &new_session_path)
-            .map_err(|e| format!("Failed to copy session file: {}", e))?;
-    }
+    let restored = source
+        .fork_session(&checkpoint_id, &new_session_id)
+        .await
+        .map_err(|e| format!("Failed to fork checkpoint: {}", e))?;
 
-    // Create manager for the new session
+    // Create manager for the new session and record where it started
     let manager = app
         .get_or_create_manager(
             new_session_id.clone(),
-            project_id,
+            project_id.clone(),
             PathBuf::from(&project_path),
         )
         .await
         .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
 
-    manager
-        .fork_from_checkpoint(&checkpoint_id, description)
+    let new_session_path = get_claude_dir()
+        .map_err(|e| e.to_string())?
+        .join("projects")
+        .join(&project_id)
+        .join(format!("{}.jsonl", new_session_id));
+    let transcript = fs::read_to_string(&new_session_path)
+        .map_err(|e| format!("Failed to read session file: {}", e))?;
+    let tracked = manager.message_count().await;
+    for line in transcript.lines().skip(tracked) {
+        manager
+            .track_message(line.to_string())
+            .await
+            .map_err(|e| format!("Failed to track message: {}", e))?;
+    }
+
+    let fork_description = description.unwrap_or_else(|| {
+        format!(
+            "Fork from checkpoint {} of session {}",
+            crate::checkpoint::manager::short_id(&checkpoint_id),
+            crate::checkpoint::manager::short_id(&session_id)
+        )
+    });
+    let mut result = manager
+        .create_checkpoint(Some(fork_description), None)
         .await
-        .map_err(|e| format!("Failed to fork checkpoint: {}", e))
+        .map_err(|e| format!("Failed to create fork checkpoint: {}", e))?;
+    result.warnings.extend(restored.warnings);
+    result.safety_checkpoint_id = restored.safety_checkpoint_id;
+
+    Ok(result)
 }
 
 /// Gets the timeline for a session
@@ -1758,13 +1876,18 @@ pub async fn update_checkpoint_settings(
 }
 
 /// Gets diff between two checkpoints
+///
+/// Either checkpoint may also be given as a tag or a unique ID prefix.
 #[tauri::command]
 pub async fn get_checkpoint_diff(
     from_checkpoint_id: String,
     to_checkpoint_id: String,
     session_id: String,
     project_id: String,
+    context_lines: Option<usize>,
+    ignore_whitespace: Option<bool>,
 ) -> Result<crate::checkpoint::CheckpointDiff, String> {
+    use crate::checkpoint::diff::{diff_snapshots, DiffOptions};
     use crate::checkpoint::storage::CheckpointStorage;
 
     log::info!(
@@ -1774,28 +1897,40 @@ pub async fn get_checkpoint_diff(
     );
 
     let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
-    let storage = CheckpointStorage::new(claude_dir);
+    let storage = CheckpointStorage::new(claude_dir.clone());
+
+    let from_checkpoint_id = storage
+        .resolve_checkpoint(&project_id, &session_id, &from_checkpoint_id)
+        .map_err(|e| e.to_string())?;
+    let to_checkpoint_id = storage
+        .resolve_checkpoint(&project_id, &session_id, &to_checkpoint_id)
+        .map_err(|e| e.to_string())?;
 
     // Load both checkpoints
-    let (from_checkpoint, from_files, _) = storage
+    let (from_checkpoint, _, _) = storage
         .load_checkpoint(&project_id, &session_id, &from_checkpoint_id)
         .map_err(|e| format!("Failed to load source checkpoint: {}", e))?;
-    let (to_checkpoint, to_files, _) = storage
+    let (to_checkpoint, _, _) = storage
         .load_checkpoint(&project_id, &session_id, &to_checkpoint_id)
         .map_err(|e| format!("Failed to load target checkpoint: {}", e))?;
 
-    // Build file maps
-    let mut from_map: std::collections::HashMap<PathBuf, &crate::checkpoint::FileSnapshot> =
-        std::collections::HashMap::new();
-    for file in &from_files {
-        from_map.insert(file.file_path.clone(), file);
-    }
-
-    let mut to_map: std::collections::HashMap<PathBuf, &crate::checkpoint::FileSnapshot> =
-        std::collections::HashMap::new();
-    for file in &to_files {
-        to_map.insert(file.file_path.clone(), file);
-    }
+    // Compare the whole trees, not just the files each checkpoint changed
+    let paths = crate::checkpoint::CheckpointPaths::new(&claude_dir, &project_id, &session_id);
+    let timeline = storage
+        .load_timeline(&paths.timeline_file)
+        .map_err(|e| format!("Failed to load timeline: {}", e))?;
+    let from_map = storage
+        .load_checkpoint_tree(&project_id, &session_id, &timeline, &from_checkpoint_id)
+        .map_err(|e| format!("Failed to load source checkpoint files: {}", e))?;
+    let to_map = storage
+        .load_checkpoint_tree(&project_id, &session_id, &timeline, &to_checkpoint_id)
+        .map_err(|e| format!("Failed to load target checkpoint files: {}", e))?;
+
+    let defaults = DiffOptions::default();
+    let diff_options = DiffOptions {
+        context_lines: context_lines.unwrap_or(defaults.context_lines),
+        ignore_whitespace: ignore_whitespace.unwrap_or(defaults.ignore_whitespace),
+    };
 
     // Calculate differences
     let mut modified_files = Vec::new();
@@ -1807,15 +1942,10 @@ pub async fn get_checkpoint_diff(
         if let Some(to_file) = to_map.get(path) {
             if from_file.hash != to_file.hash {
                 // File was modified
-                let additions = to_file.content.lines().count();
-                let deletions = from_file.content.lines().count();
-
-                modified_files.push(crate::checkpoint::FileDiff {
-                    path: path.clone(),
-                    additions,
-                    deletions,
-                    diff_content: None, // TODO: Generate actual diff
-                });
+                let file_diff =
+                    diff_snapshots(&storage, &paths, from_file, to_file, &diff_options)
+                        .map_err(|e| format!("Failed to diff {}: {}", path.display(), e))?;
+                modified_files.push(file_diff);
             }
         } else {
             // File was deleted
@@ -1845,6 +1975,8 @@ pub async fn get_checkpoint_diff(
 }
 
 /// Tracks a message for checkpointing
+///
+/// Takes an automatic checkpoint if the message completes a scheduled one.
 #[tauri::command]
 pub async fn track_checkpoint_message(
     app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
@@ -1861,12 +1993,40 @@ pub async fn track_checkpoint_message(
         .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
 
     manager
-        .track_message(message)
+        .track_message_with_auto_checkpoint(message)
         .await
+        .map(|_| ())
         .map_err(|e| format!("Failed to track message: {}", e))
 }
 
-/// Checks if auto-checkpoint should be triggered
+/// Observes a message from the live output stream for checkpointing
+///
+/// Lets Bash commands be diffed against the working tree from before they
+/// ran; the message itself is tracked when the session file is replayed.
+#[tauri::command]
+pub async fn track_live_checkpoint_message(
+    app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
+    session_id: String,
+    project_id: String,
+    project_path: String,
+    message: String,
+) -> Result<(), String> {
+    let manager = app
+        .get_or_create_manager(session_id, project_id, PathBuf::from(project_path))
+        .await
+        .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
+
+    manager
+        .track_live_message(&message)
+        .await
+        .map_err(|e| format!("Failed to track live message: {}", e))
+}
+
+/// Runs the auto-checkpoint scheduler at the end of an assistant turn
+///
+/// The turn's messages are tracked from the session file first, so the
+/// tool uses in them can request a checkpoint. Returns whether an
+/// automatic checkpoint was created.
 #[tauri::command]
 pub async fn check_auto_checkpoint(
     app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
@@ -1878,11 +2038,27 @@ pub async fn check_auto_checkpoint(
     log::info!("Checking auto-checkpoint for session: {}", session_id);
 
     let manager = app
-        .get_or_create_manager(session_id.clone(), project_id, PathBuf::from(project_path))
+        .get_or_create_manager(
+            session_id.clone(),
+            project_id.clone(),
+            PathBuf::from(project_path),
+        )
         .await
         .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
 
-    Ok(manager.should_auto_checkpoint(&message).await)
+    // The turn's tool uses are only in the session file, not the prompt
+    let turn_messages = read_untracked_session_messages(
+        &project_id,
+        &session_id,
+        manager.message_count().await,
+        None,
+    )?;
+
+    manager
+        .check_auto_checkpoint(turn_messages, &message)
+        .await
+        .map(|result| result.is_some())
+        .map_err(|e| format!("Failed to run auto-checkpoint: {}", e))
 }
 
 /// Triggers cleanup of old checkpoints
@@ -1900,6 +2076,209 @@ pub async fn cleanup_old_checkpoints(
         keep_count
     );
 
+    let manager = app
+        .get_or_create_manager(session_id, project_id, PathBuf::from(project_path))
+        .await
+        .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
+
+    manager
+        .cleanup_old_checkpoints(keep_count)
+        .await
+        .map_err(|e| format!("Failed to cleanup checkpoints: {}", e))
+}
+
+/// Sets when automatic checkpoints are taken for a session
+#[tauri::command]
+pub async fn update_auto_checkpoint_schedule(
+    app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
+    session_id: String,
+    project_id: String,
+    project_path: String,
+    schedule: crate::checkpoint::AutoCheckpointSchedule,
+) -> Result<(), String> {
+    log::info!("Updating auto-checkpoint schedule for session: {}", session_id);
+
+    let manager = app
+        .get_or_create_manager(session_id, project_id, PathBuf::from(project_path))
+        .await
+        .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
+
+    manager
+        .update_auto_checkpoint_schedule(schedule)
+        .await
+        .map_err(|e| format!("Failed to update auto-checkpoint schedule: {}", e))
+}
+
+/// Sets the size above which files are left out of a session's checkpoints
+#[tauri::command]
+pub async fn update_checkpoint_max_file_size(
+    app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
+    session_id: String,
+    project_id: String,
+    project_path: String,
+    max_file_size: u64,
+) -> Result<(), String> {
+    log::info!(
+        "Setting checkpoint max file size for session {} to {} bytes",
+        session_id,
+        max_file_size
+    );
+
+    let manager = app
+        .get_or_create_manager(session_id, project_id, PathBuf::from(project_path))
+        .await
+        .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
+
+    manager
+        .update_max_file_size(max_file_size)
+        .await
+        .map_err(|e| format!("Failed to update max file size: {}", e))
+}
+
+/// Sets when a session's checkpoint writes are flushed to stable storage
+#[tauri::command]
+pub async fn update_checkpoint_fsync_policy(
+    app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
+    session_id: String,
+    project_id: String,
+    project_path: String,
+    fsync_policy: crate::checkpoint::storage::FsyncPolicy,
+) -> Result<(), String> {
+    log::info!(
+        "Setting checkpoint fsync policy for session {} to {:?}",
+        session_id,
+        fsync_policy
+    );
+
+    let manager = app
+        .get_or_create_manager(session_id, project_id, PathBuf::from(project_path))
+        .await
+        .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
+
+    manager
+        .update_fsync_policy(fsync_policy)
+        .await
+        .map_err(|e| format!("Failed to update fsync policy: {}", e))
+}
+
+/// Gets the rules the `Smart` checkpoint strategy applies in a project
+#[tauri::command]
+pub async fn get_smart_checkpoint_rules(
+    project_id: String,
+) -> Result<crate::checkpoint::SmartRules, String> {
+    log::info!("Getting smart checkpoint rules for project: {}", project_id);
+
+    let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
+    crate::checkpoint::storage::CheckpointStorage::new(claude_dir)
+        .load_smart_rules(&project_id)
+        .map_err(|e| format!("Failed to load smart checkpoint rules: {}", e))
+}
+
+/// Replaces the rules the `Smart` checkpoint strategy applies in a project
+///
+/// The rules are shared by every session of the project and take effect
+/// with the next tracked message.
+#[tauri::command]
+pub async fn update_smart_checkpoint_rules(
+    project_id: String,
+    rules: crate::checkpoint::SmartRules,
+) -> Result<(), String> {
+    log::info!("Updating smart checkpoint rules for project: {}", project_id);
+
+    let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
+    crate::checkpoint::storage::CheckpointStorage::new(claude_dir)
+        .save_smart_rules(&project_id, &rules)
+        .map_err(|e| format!("Failed to save smart checkpoint rules: {:#}", e))
+}
+
+/// Sets the retention policy for a session and prunes checkpoints it does not keep
+#[tauri::command]
+pub async fn update_retention_policy(
+    app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
+    session_id: String,
+    project_id: String,
+    project_path: String,
+    policy: crate::checkpoint::RetentionPolicy,
+) -> Result<crate::checkpoint::retention::RetentionReport, String> {
+    log::info!("Updating retention policy for session: {}", session_id);
+
+    let manager = app
+        .get_or_create_manager(session_id, project_id, PathBuf::from(project_path))
+        .await
+        .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
+
+    manager
+        .update_retention_policy(policy)
+        .await
+        .map_err(|e| format!("Failed to update retention policy: {}", e))
+}
+
+/// Prunes checkpoints according to the session's retention policy
+#[tauri::command]
+pub async fn apply_retention_policy(
+    app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
+    session_id: String,
+    project_id: String,
+    project_path: String,
+) -> Result<crate::checkpoint::retention::RetentionReport, String> {
+    log::info!("Applying retention policy for session: {}", session_id);
+
+    let manager = app
+        .get_or_create_manager(session_id, project_id, PathBuf::from(project_path))
+        .await
+        .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
+
+    manager
+        .apply_retention_policy()
+        .await
+        .map_err(|e| format!("Failed to apply retention policy: {}", e))
+}
+
+/// Verifies a session's checkpoints against the content pool
+///
+/// With `repair`, unrecoverable checkpoints are pruned from the timeline and
+/// orphaned data is removed.
+#[tauri::command]
+pub async fn verify_checkpoints(
+    app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
+    session_id: String,
+    project_id: String,
+    project_path: String,
+    repair: Option<bool>,
+) -> Result<crate::checkpoint::verify::VerifyReport, String> {
+    let repair = repair.unwrap_or(false);
+    log::info!(
+        "Verifying checkpoints for session: {} (repair: {})",
+        session_id,
+        repair
+    );
+
+    let manager = app
+        .get_or_create_manager(session_id, project_id, PathBuf::from(project_path))
+        .await
+        .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
+
+    manager
+        .verify_integrity(repair)
+        .await
+        .map_err(|e| format!("Failed to verify checkpoints: {}", e))
+}
+
+/// Exports a session's checkpoint timeline to a tar+zstd archive
+#[tauri::command]
+pub async fn export_checkpoint_timeline(
+    app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
+    session_id: String,
+    project_id: String,
+    project_path: String,
+    archive_path: String,
+) -> Result<crate::checkpoint::archive::ArchiveSummary, String> {
+    log::info!(
+        "Exporting checkpoint timeline for session: {} to {}",
+        session_id,
+        archive_path
+    );
+
     let manager = app
         .get_or_create_manager(
             session_id.clone(),
@@ -1911,8 +2290,41 @@ pub async fn cleanup_old_checkpoints(
 
     manager
         .storage
-        .cleanup_old_checkpoints(&project_id, &session_id, keep_count)
-        .map_err(|e| format!("Failed to cleanup checkpoints: {}", e))
+        .export_session(&project_id, &session_id, &PathBuf::from(archive_path))
+        .map_err(|e| format!("Failed to export checkpoint timeline: {}", e))
+}
+
+/// Imports a checkpoint timeline archive into a project
+///
+/// The session keeps the ID it was exported with unless `session_id` is given.
+#[tauri::command]
+pub async fn import_checkpoint_timeline(
+    app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
+    archive_path: String,
+    project_id: String,
+    session_id: Option<String>,
+) -> Result<crate::checkpoint::archive::ArchiveSummary, String> {
+    log::info!(
+        "Importing checkpoint timeline from {} into project: {}",
+        archive_path,
+        project_id
+    );
+
+    let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
+    let storage = crate::checkpoint::storage::CheckpointStorage::new(claude_dir);
+
+    let summary = storage
+        .import_session(
+            &PathBuf::from(archive_path),
+            &project_id,
+            session_id.as_deref(),
+        )
+        .map_err(|e| format!("Failed to import checkpoint timeline: {}", e))?;
+
+    // A cached manager would still hold the empty timeline
+    app.remove_manager(&summary.session_id).await;
+
+    Ok(summary)
 }
 
 /// Gets checkpoint settings for a session
@@ -1937,6 +2349,10 @@ pub async fn get_checkpoint_settings(
         "checkpoint_strategy": timeline.checkpoint_strategy,
         "total_checkpoints": timeline.total_checkpoints,
         "current_checkpoint_id": timeline.current_checkpoint_id,
+        "retention_policy": timeline.retention_policy,
+        "auto_checkpoint_schedule": timeline.auto_checkpoint_schedule,
+        "max_file_size": timeline.max_file_size,
+        "fsync_policy": timeline.fsync_policy,
     }))
 }
 
@@ -1966,6 +2382,56 @@ pub async fn get_checkpoint_state_stats(
     }))
 }
 
+/// Reports the checkpoint storage used by a session, or by the whole project
+/// when no session is given
+#[tauri::command]
+pub async fn get_checkpoint_storage_report(
+    project_id: String,
+    session_id: Option<String>,
+) -> Result<crate::checkpoint::stats::StorageReport, String> {
+    log::info!("Getting checkpoint storage report for project: {}", project_id);
+
+    let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
+    let storage = crate::checkpoint::storage::CheckpointStorage::new(claude_dir);
+    match session_id {
+        Some(session_id) => storage.session_storage_report(&project_id, &session_id),
+        None => storage.project_storage_report(&project_id),
+    }
+    .map_err(|e| format!("Failed to build storage report: {}", e))
+}
+
+/// Searches the checkpoints of a project, or of one session, by prompt,
+/// description, changed file path and optionally the content they added
+#[tauri::command]
+pub async fn search_checkpoints(
+    project_id: String,
+    query: String,
+    session_id: Option<String>,
+    include_contents: Option<bool>,
+    limit: Option<usize>,
+) -> Result<Vec<crate::checkpoint::search::SearchResult>, String> {
+    use crate::checkpoint::search::SearchOptions;
+
+    log::info!(
+        "Searching checkpoints in project {} for: '{}'",
+        project_id,
+        query
+    );
+
+    let defaults = SearchOptions::default();
+    let options = SearchOptions {
+        session_id,
+        include_contents: include_contents.unwrap_or(defaults.include_contents),
+        limit: limit.unwrap_or(defaults.limit),
+    };
+
+    let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
+    let storage = crate::checkpoint::storage::CheckpointStorage::new(claude_dir);
+    storage
+        .search_checkpoints(&project_id, &query, &options)
+        .map_err(|e| format!("Failed to search checkpoints: {}", e))
+}
+
 /// Gets files modified in the last N minutes for a session
 #[tauri::command]
 pub async fn get_recently_modified_files(
@@ -2028,7 +2494,7 @@ pub async fn track_session_messages(
 
     for message in messages {
         manager
-            .track_message(message)
+            .track_message_with_auto_checkpoint(message)
             .await
             .map_err(|e| format!("Failed to track message: {}", e))?;
     }
@@ -2036,6 +2502,183 @@ pub async fn track_session_messages(
     Ok(())
 }
 
+/// Merges one checkpoint's branch into another's
+///
+/// Either checkpoint may also be given as a tag or a unique ID prefix. The
+/// merged tree is written to the project and recorded as a new checkpoint
+/// with both checkpoints as parents.
+#[tauri::command]
+pub async fn merge_checkpoints(
+    app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
+    ours_checkpoint_id: String,
+    theirs_checkpoint_id: String,
+    session_id: String,
+    project_id: String,
+    project_path: String,
+    description: Option<String>,
+) -> Result<crate::checkpoint::merge::MergeResult, String> {
+    log::info!(
+        "Merging checkpoint {} into {} for session: {}",
+        theirs_checkpoint_id,
+        ours_checkpoint_id,
+        session_id
+    );
+
+    let manager = app
+        .get_or_create_manager(
+            session_id.clone(),
+            project_id.clone(),
+            PathBuf::from(&project_path),
+        )
+        .await
+        .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
+
+    let ours_checkpoint_id = manager
+        .resolve_checkpoint(&ours_checkpoint_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let theirs_checkpoint_id = manager
+        .resolve_checkpoint(&theirs_checkpoint_id)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let result = manager
+        .merge_checkpoints(&ours_checkpoint_id, &theirs_checkpoint_id, description)
+        .await
+        .map_err(|e| format!("Failed to merge checkpoints: {}", e))?;
+
+    // The conversation continues from the first branch
+    manager
+        .write_session_transcript(&result.checkpoint.id, &session_id)
+        .map_err(|e| format!("Failed to update session file: {}", e))?;
+
+    Ok(result)
+}
+
+/// Exports a checkpoint, or its whole branch, to git commits under `refs/claude/`
+///
+/// `checkpoint_id` may also be a tag or a unique checkpoint ID prefix.
+#[tauri::command]
+pub async fn export_checkpoint_to_git(
+    app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
+    checkpoint_id: String,
+    session_id: String,
+    project_id: String,
+    project_path: String,
+    include_ancestors: Option<bool>,
+) -> Result<Vec<crate::checkpoint::git::GitExport>, String> {
+    log::info!(
+        "Exporting checkpoint {} of session {} to git",
+        checkpoint_id,
+        session_id
+    );
+
+    let manager = app
+        .get_or_create_manager(session_id, project_id, PathBuf::from(&project_path))
+        .await
+        .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
+
+    let checkpoint_id = manager
+        .resolve_checkpoint(&checkpoint_id)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    manager
+        .export_to_git(&checkpoint_id, include_ancestors.unwrap_or(false))
+        .await
+        .map_err(|e| format!("Failed to export checkpoint to git: {}", e))
+}
+
+/// Creates a checkpoint from the files of a git commit in the project's repository
+///
+/// The current checkpoint only moves to the new one when `make_current` is set.
+#[tauri::command]
+pub async fn seed_checkpoint_from_git(
+    app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
+    rev: String,
+    session_id: String,
+    project_id: String,
+    project_path: String,
+    description: Option<String>,
+    make_current: Option<bool>,
+) -> Result<crate::checkpoint::CheckpointResult, String> {
+    log::info!("Seeding checkpoint for session {} from git {}", session_id, rev);
+
+    let manager = app
+        .get_or_create_manager(session_id, project_id, PathBuf::from(&project_path))
+        .await
+        .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
+
+    manager
+        .seed_from_git(&rev, description, make_current.unwrap_or(false))
+        .await
+        .map_err(|e| format!("Failed to seed checkpoint from git: {}", e))
+}
+
+/// Tags a checkpoint so it can be referred to by name
+///
+/// Tagged checkpoints are never removed by automatic cleanup.
+#[tauri::command]
+pub async fn tag_checkpoint(
+    app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
+    checkpoint_id: String,
+    tag: String,
+    session_id: String,
+    project_id: String,
+    project_path: String,
+) -> Result<crate::checkpoint::Checkpoint, String> {
+    log::info!("Tagging checkpoint {} as {}", checkpoint_id, tag);
+
+    let manager = app
+        .get_or_create_manager(session_id, project_id, PathBuf::from(&project_path))
+        .await
+        .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
+
+    manager
+        .tag_checkpoint(&checkpoint_id, &tag)
+        .await
+        .map_err(|e| format!("Failed to tag checkpoint: {}", e))
+}
+
+/// Removes a tag from a checkpoint
+#[tauri::command]
+pub async fn untag_checkpoint(
+    app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
+    tag: String,
+    session_id: String,
+    project_id: String,
+    project_path: String,
+) -> Result<crate::checkpoint::Checkpoint, String> {
+    log::info!("Removing checkpoint tag {}", tag);
+
+    let manager = app
+        .get_or_create_manager(session_id, project_id, PathBuf::from(&project_path))
+        .await
+        .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
+
+    manager
+        .untag_checkpoint(&tag)
+        .await
+        .map_err(|e| format!("Failed to remove tag: {}", e))
+}
+
+/// Lists checkpoint tags for a session, optionally filtered by a search query
+#[tauri::command]
+pub async fn list_checkpoint_tags(
+    app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
+    session_id: String,
+    project_id: String,
+    project_path: String,
+    query: Option<String>,
+) -> Result<Vec<crate::checkpoint::CheckpointTag>, String> {
+    let manager = app
+        .get_or_create_manager(session_id, project_id, PathBuf::from(&project_path))
+        .await
+        .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
+
+    Ok(manager.list_tags(query.as_deref()).await)
+}
+
 /// Gets hooks configuration from settings at specified scope
 #[tauri::command]
 pub async fn get_hooks_config(scope: String, project_path: Option<String>) -> Result<serde_json::Value, String> {
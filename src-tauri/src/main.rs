@@ -19,13 +19,17 @@ use commands::agents::{
 use commands::claude::{
     cancel_claude_execution, check_auto_checkpoint, check_claude_version, cleanup_old_checkpoints,
     clear_checkpoint_manager, continue_claude_code, create_checkpoint, create_project, execute_claude_code,
+    export_checkpoint_timeline, import_checkpoint_timeline, update_retention_policy,
+    update_auto_checkpoint_schedule, update_checkpoint_max_file_size, update_checkpoint_fsync_policy, get_smart_checkpoint_rules, update_smart_checkpoint_rules,
+    apply_retention_policy, merge_checkpoints, export_checkpoint_to_git, seed_checkpoint_from_git, tag_checkpoint, untag_checkpoint, list_checkpoint_tags,
     find_claude_md_files, fork_from_checkpoint, get_checkpoint_diff, get_checkpoint_settings,
-    get_checkpoint_state_stats, get_claude_session_output, get_claude_settings, get_home_directory, get_project_sessions,
+    get_checkpoint_state_stats, get_checkpoint_storage_report, search_checkpoints, get_claude_session_output, get_claude_settings, get_home_directory, get_project_sessions,
     get_recently_modified_files, get_session_timeline, get_system_prompt, list_checkpoints,
     list_directory_contents, list_projects, list_running_claude_sessions, load_session_history,
-    open_new_session, read_claude_md_file, restore_checkpoint, resume_claude_code,
-    save_claude_md_file, save_claude_settings, save_system_prompt, search_files,
-    track_checkpoint_message, track_session_messages, update_checkpoint_settings,
+    open_new_session, preview_restore_checkpoint, read_claude_md_file, restore_checkpoint,
+    restore_checkpoint_files,
+    resume_claude_code, save_claude_md_file, save_claude_settings, save_system_prompt, search_files,
+    track_checkpoint_message, track_live_checkpoint_message, track_session_messages, update_checkpoint_settings, verify_checkpoints,
     get_hooks_config, update_hooks_config, validate_hook_command,
     ClaudeProcessState,
 };
@@ -203,18 +207,39 @@ fn main() {
             // Checkpoint Management
             create_checkpoint,
             restore_checkpoint,
+            restore_checkpoint_files,
+            preview_restore_checkpoint,
             list_checkpoints,
             fork_from_checkpoint,
             get_session_timeline,
             update_checkpoint_settings,
             get_checkpoint_diff,
             track_checkpoint_message,
+            track_live_checkpoint_message,
             track_session_messages,
             check_auto_checkpoint,
             cleanup_old_checkpoints,
+            update_auto_checkpoint_schedule,
+            update_checkpoint_max_file_size,
+            update_checkpoint_fsync_policy,
+            get_smart_checkpoint_rules,
+            update_smart_checkpoint_rules,
+            update_retention_policy,
+            apply_retention_policy,
+            verify_checkpoints,
+            export_checkpoint_timeline,
+            import_checkpoint_timeline,
+            merge_checkpoints,
+            export_checkpoint_to_git,
+            seed_checkpoint_from_git,
+            tag_checkpoint,
+            untag_checkpoint,
+            list_checkpoint_tags,
             get_checkpoint_settings,
             clear_checkpoint_manager,
             get_checkpoint_state_stats,
+            get_checkpoint_storage_report,
+            search_checkpoints,
             
             // Agent Management
             list_agents,
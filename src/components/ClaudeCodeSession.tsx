@@ -544,6 +544,34 @@ export const ClaudeCodeSession: React.FC<ClaudeCodeSessionProps> = ({
           }
         });
 
+        // Bash commands are diffed against the tree captured when they start, so
+        // their tool uses and results go to the checkpoint manager as they stream.
+        // The calls are chained to keep each result after its capture.
+        const liveBashToolIds = new Set<string>();
+        let liveCheckpointTracking: Promise<void> = Promise.resolve();
+
+        function trackLiveCheckpointMessage(message: ClaudeStreamMessage, payload: string) {
+          const content = message.message?.content;
+          if (!currentSessionId || !Array.isArray(content)) return;
+
+          let isBashMessage = false;
+          for (const item of content) {
+            if (message.type === 'assistant' && item.type === 'tool_use' && item.name?.toLowerCase() === 'bash') {
+              liveBashToolIds.add(item.id);
+              isBashMessage = true;
+            } else if (message.type === 'user' && item.type === 'tool_result' && liveBashToolIds.delete(item.tool_use_id)) {
+              isBashMessage = true;
+            }
+          }
+          if (!isBashMessage) return;
+
+          const sessionId = currentSessionId;
+          const projectId = effectiveSession?.project_id || projectPath.replace(/[^a-zA-Z0-9]/g, '-');
+          liveCheckpointTracking = liveCheckpointTracking
+            .then(() => api.trackLiveCheckpointMessage(sessionId, projectId, projectPath, payload))
+            .catch((err) => console.error('Failed to track live checkpoint message:', err));
+        }
+
         // Helper to process any JSONL stream message string
         function handleStreamMessage(payload: string) {
           try {
@@ -554,6 +582,8 @@ export const ClaudeCodeSession: React.FC<ClaudeCodeSessionProps> = ({
             setRawJsonlOutput((prev) => [...prev, payload]);
 
             const message = JSON.parse(payload) as ClaudeStreamMessage;
+
+            trackLiveCheckpointMessage(message, payload);
             
             // Track enhanced tool execution
             if (message.type === 'assistant' && message.message?.content) {
@@ -694,6 +724,8 @@ export const ClaudeCodeSession: React.FC<ClaudeCodeSessionProps> = ({
 
           if (effectiveSession && success) {
             try {
+              // Let Bash commands finish being diffed before the checkpoint
+              await liveCheckpointTracking;
               const settings = await api.getCheckpointSettings(
                 effectiveSession.id,
                 effectiveSession.project_id,
@@ -1033,7 +1065,8 @@ export const ClaudeCodeSession: React.FC<ClaudeCodeSessionProps> = ({
       setIsLoading(true);
       setError(null);
       
-      const newSessionId = `${Date.now()}-${Math.random().toString(36).substr(2, 9)}`;
+      // Claude session IDs are UUIDs
+      const newSessionId = crypto.randomUUID();
       await api.forkFromCheckpoint(
         forkCheckpointId,
         effectiveSession.id,
@@ -150,27 +150,21 @@ export const TimelineNavigator: React.FC<TimelineNavigatorProps> = ({
   };
 
   const handleRestoreCheckpoint = async (checkpoint: Checkpoint) => {
-    if (!confirm(`Restore to checkpoint "${checkpoint.description || checkpoint.id.slice(0, 8)}"? Current state will be saved as a new checkpoint.`)) {
-      return;
-    }
-
     try {
       setIsLoading(true);
       setError(null);
+
+      // Preview the file changes so the user knows what the restore will do
+      const plan = await api.previewRestoreCheckpoint(checkpoint.id, sessionId, projectId, projectPath);
+      const changes = `${plan.filesToDelete.length} deleted, ${plan.filesToCreate.length} created, ${plan.filesToOverwrite.length} overwritten`;
+      if (!confirm(`Restore to checkpoint "${checkpoint.description || checkpoint.id.slice(0, 8)}"? Files: ${changes}. Current state will be saved as a new checkpoint.`)) {
+        return;
+      }
       
       const checkpointTime = new Date(checkpoint.timestamp).getTime();
       const timeSinceCheckpoint = Date.now() - checkpointTime;
       
-      // First create a checkpoint of current state
-      await api.createCheckpoint(
-        sessionId,
-        projectId,
-        projectPath,
-        currentMessageIndex,
-        "Auto-save before restore"
-      );
-      
-      // Then restore
+      // The backend saves the current state as a pre-restore checkpoint first
       await api.restoreCheckpoint(checkpoint.id, sessionId, projectId, projectPath);
       
       // Track checkpoint restoration
@@ -556,12 +550,34 @@ export const TimelineNavigator: React.FC<TimelineNavigatorProps> = ({
                   <h4 className="text-sm font-medium mb-2">Modified Files</h4>
                   <div className="space-y-1">
                     {diff.modifiedFiles.map((file) => (
-                      <div key={file.path} className="flex items-center justify-between text-xs">
-                        <span className="font-mono">{file.path}</span>
-                        <div className="flex items-center gap-2 text-xs">
-                          <span className="text-green-600">+{file.additions}</span>
-                          <span className="text-red-600">-{file.deletions}</span>
+                      <div key={file.path} className="space-y-1">
+                        <div className="flex items-center justify-between text-xs">
+                          <span className="font-mono">{file.path}</span>
+                          <div className="flex items-center gap-2 text-xs">
+                            <span className="text-green-600">+{file.additions}</span>
+                            <span className="text-red-600">-{file.deletions}</span>
+                          </div>
                         </div>
+                        {file.diffContent && (
+                          <pre className="text-xs font-mono bg-muted/50 rounded p-2 overflow-x-auto max-h-64">
+                            {file.diffContent.split("\n").map((line, i) => (
+                              <div
+                                key={i}
+                                className={
+                                  line.startsWith("+") && !line.startsWith("+++")
+                                    ? "text-green-600"
+                                    : line.startsWith("-") && !line.startsWith("---")
+                                    ? "text-red-600"
+                                    : line.startsWith("@@")
+                                    ? "text-muted-foreground"
+                                    : undefined
+                                }
+                              >
+                                {line || " "}
+                              </div>
+                            ))}
+                          </pre>
+                        )}
                       </div>
                     ))}
                   </div>
@@ -251,9 +251,50 @@ export interface Checkpoint {
   timestamp: string;
   description?: string;
   parentCheckpointId?: string;
+  mergeParentCheckpointId?: string;
+  tags: string[];
   metadata: CheckpointMetadata;
 }
 
+/**
+ * Result of merging one checkpoint into another
+ */
+export interface MergeResult {
+  checkpoint: Checkpoint;
+  baseCheckpointId?: string;
+  conflicts: MergeConflict[];
+  filesProcessed: number;
+  warnings: string[];
+  safetyCheckpointId?: string;
+}
+
+/**
+ * A file both branches changed in incompatible ways
+ */
+export interface MergeConflict {
+  path: string;
+  kind: 'content' | 'modify_delete' | 'binary' | 'too_large';
+}
+
+/**
+ * A checkpoint written to git as a commit
+ */
+export interface GitExport {
+  checkpointId: string;
+  commit: string;
+  refName: string;
+}
+
+/**
+ * A tag and the checkpoint it names
+ */
+export interface CheckpointTag {
+  tag: string;
+  checkpointId: string;
+  timestamp: string;
+  description?: string;
+}
+
 /**
  * Metadata associated with a checkpoint
  */
@@ -263,6 +304,17 @@ export interface CheckpointMetadata {
   userPrompt: string;
   fileChanges: number;
   snapshotSize: number;
+  /** Files present in the project that the checkpoint does not hold */
+  skippedFiles?: SkippedFile[];
+}
+
+/**
+ * A file left out of a checkpoint
+ */
+export interface SkippedFile {
+  path: string;
+  size: number;
+  reason: 'too_large';
 }
 
 /**
@@ -271,11 +323,13 @@ export interface CheckpointMetadata {
 export interface FileSnapshot {
   checkpointId: string;
   filePath: string;
-  content: string;
+  content: number[];
   hash: string;
   isDeleted: boolean;
   permissions?: number;
   size: number;
+  /** A symlink's content is its target; directories are only recorded while empty */
+  kind: 'file' | 'symlink' | 'directory';
 }
 
 /**
@@ -297,6 +351,138 @@ export interface SessionTimeline {
   autoCheckpointEnabled: boolean;
   checkpointStrategy: CheckpointStrategy;
   totalCheckpoints: number;
+  retentionPolicy: RetentionPolicy;
+  autoCheckpointSchedule: AutoCheckpointSchedule;
+}
+
+/**
+ * Limits on when automatic checkpoints are taken
+ */
+export interface AutoCheckpointSchedule {
+  debounceSecs: number;
+  minChangedBytes: number;
+  atEndOfTurn: boolean;
+}
+
+/**
+ * Ordered rules deciding which tool calls the smart strategy checkpoints
+ */
+export interface SmartRules {
+  rules: SmartRule[];
+}
+
+/**
+ * A smart strategy rule; every non-empty condition must match
+ */
+export interface SmartRule {
+  tools: string[];
+  bashCommands: string[];
+  paths: string[];
+  checkpoint: boolean;
+  description?: string;
+}
+
+/**
+ * Rules for which checkpoints are kept when pruning
+ */
+export interface RetentionPolicy {
+  keepLastPerBranch?: number;
+  keepDailyForDays?: number;
+  keepWeeklyForDays?: number;
+  /** Also keeps automatic safety, merge and fork checkpoints, which are described */
+  keepDescribed: boolean;
+  maxDiskBytes?: number;
+}
+
+/**
+ * Outcome of applying a retention policy
+ */
+export interface RetentionReport {
+  prunedCheckpoints: string[];
+  bytesBefore: number;
+  bytesAfter: number;
+}
+
+/**
+ * Checkpoint storage used by a session or a whole project
+ */
+export interface StorageReport {
+  sessionIds: string[];
+  checkpoints: CheckpointUsage[];
+  totalBytes: number;
+  logicalBytes: number;
+  uniqueBytes: number;
+  storedBytes: number;
+  dedupeRatio: number;
+  compressionRatio: number;
+  largestFiles: LargeFile[];
+}
+
+/**
+ * Disk usage of a single checkpoint
+ */
+export interface CheckpointUsage {
+  checkpointId: string;
+  sessionId: string;
+  timestamp: string;
+  description?: string;
+  fileCount: number;
+  metadataBytes: number;
+  contentBytes: number;
+  pruneSavingsBytes: number;
+}
+
+/**
+ * A file content in the checkpoint pool
+ */
+export interface LargeFile {
+  path: string;
+  hash: string;
+  size: number;
+  storedBytes: number;
+  checkpointCount: number;
+}
+
+/**
+ * Options for searching checkpoints
+ */
+export interface CheckpointSearchOptions {
+  /** Only search this session instead of the whole project */
+  sessionId?: string;
+  /** Also search the text checkpoints added to files (default false) */
+  includeContents?: boolean;
+  /** Maximum number of results (default 50) */
+  limit?: number;
+}
+
+/**
+ * Part of a checkpoint a search matched
+ */
+export type SearchField = 'description' | 'prompt' | 'path' | 'content';
+
+/**
+ * One place a checkpoint matched a search
+ */
+export interface SearchMatch {
+  field: SearchField;
+  /** File the match is in, for path and content matches */
+  path?: string;
+  /** 1-based line of a content match */
+  line?: number;
+  snippet: string;
+  score: number;
+}
+
+/**
+ * A checkpoint matching a search, with its matches best first
+ */
+export interface CheckpointSearchResult {
+  checkpointId: string;
+  sessionId: string;
+  timestamp: string;
+  description?: string;
+  score: number;
+  matches: SearchMatch[];
 }
 
 /**
@@ -304,6 +490,12 @@ export interface SessionTimeline {
  */
 export type CheckpointStrategy = 'manual' | 'per_prompt' | 'per_tool_use' | 'smart';
 
+/**
+ * When checkpoint writes are flushed to stable storage; `commit` only
+ * flushes the journal and timeline that commit a checkpoint
+ */
+export type FsyncPolicy = 'never' | 'commit' | 'always';
+
 /**
  * Result of a checkpoint operation
  */
@@ -311,6 +503,66 @@ export interface CheckpointResult {
   checkpoint: Checkpoint;
   filesProcessed: number;
   warnings: string[];
+  /** Checkpoint holding the working tree as it was before a restore */
+  safetyCheckpointId?: string;
+}
+
+/**
+ * Changes a restore would make, computed without touching disk
+ */
+export interface RestorePlan {
+  checkpointId: string;
+  filesToDelete: string[];
+  filesToCreate: string[];
+  filesToOverwrite: string[];
+  permissionChanges: PermissionChange[];
+  unchangedFiles: number;
+}
+
+/**
+ * A planned change to a file's Unix permissions
+ */
+export interface PermissionChange {
+  path: string;
+  from?: number;
+  to: number;
+}
+
+/**
+ * Result of verifying a session's checkpoints
+ */
+export interface VerifyReport {
+  sessionId: string;
+  checkpointsChecked: number;
+  blobsChecked: number;
+  issues: IntegrityIssue[];
+  orphanedCheckpoints: string[];
+  orphanedBlobs: string[];
+  repaired: boolean;
+  prunedCheckpoints: string[];
+  removedBlobs: number;
+}
+
+/**
+ * A problem that makes a checkpoint impossible to restore
+ */
+export interface IntegrityIssue {
+  checkpointId: string;
+  kind: 'missing_checkpoint_data' | 'invalid_references' | 'missing_blob' | 'corrupt_blob';
+  filePath?: string;
+  hash?: string;
+  message: string;
+}
+
+/**
+ * Summary of an exported or imported checkpoint timeline archive
+ */
+export interface ArchiveSummary {
+  sessionId: string;
+  projectId: string;
+  checkpointCount: number;
+  blobCount: number;
+  archivePath: string;
 }
 
 /**
@@ -335,6 +587,16 @@ export interface FileDiff {
   diffContent?: string;
 }
 
+/**
+ * Options for line-level checkpoint diffs
+ */
+export interface DiffOptions {
+  /** Number of unchanged lines shown around each hunk (default 3) */
+  contextLines?: number;
+  /** Treat lines that differ only in whitespace as equal */
+  ignoreWhitespace?: boolean;
+}
+
 /**
  * Represents an MCP server configuration
  */
@@ -1187,6 +1449,43 @@ export const api = {
     });
   },
 
+  /**
+   * Restores only the given paths (globs allowed) from a checkpoint, leaving
+   * the rest of the project and the session history untouched
+   */
+  async restoreCheckpointFiles(
+    checkpointId: string,
+    paths: string[],
+    sessionId: string,
+    projectId: string,
+    projectPath: string
+  ): Promise<CheckpointResult> {
+    return invoke("restore_checkpoint_files", {
+      checkpointId,
+      paths,
+      sessionId,
+      projectId,
+      projectPath
+    });
+  },
+
+  /**
+   * Previews the changes restoring a checkpoint would make, without touching disk
+   */
+  async previewRestoreCheckpoint(
+    checkpointId: string,
+    sessionId: string,
+    projectId: string,
+    projectPath: string
+  ): Promise<RestorePlan> {
+    return invoke("preview_restore_checkpoint", {
+      checkpointId,
+      sessionId,
+      projectId,
+      projectPath
+    });
+  },
+
   /**
    * Lists all checkpoints for a session
    */
@@ -1203,7 +1502,7 @@ export const api = {
   },
 
   /**
-   * Forks a new timeline branch from a checkpoint
+   * Forks a checkpoint into a new session that resumes from exactly the checkpointed conversation
    */
   async forkFromCheckpoint(
     checkpointId: string,
@@ -1264,14 +1563,17 @@ export const api = {
     fromCheckpointId: string,
     toCheckpointId: string,
     sessionId: string,
-    projectId: string
+    projectId: string,
+    options?: DiffOptions
   ): Promise<CheckpointDiff> {
     try {
       return await invoke<CheckpointDiff>("get_checkpoint_diff", {
         fromCheckpointId,
         toCheckpointId,
         sessionId,
-        projectId
+        projectId,
+        contextLines: options?.contextLines,
+        ignoreWhitespace: options?.ignoreWhitespace
       });
     } catch (error) {
       console.error("Failed to get checkpoint diff:", error);
@@ -1302,7 +1604,29 @@ export const api = {
   },
 
   /**
-   * Checks if auto-checkpoint should be triggered
+   * Observes a message from the live output stream, so Bash commands can be diffed as they run
+   */
+  async trackLiveCheckpointMessage(
+    sessionId: string,
+    projectId: string,
+    projectPath: string,
+    message: string
+  ): Promise<void> {
+    try {
+      await invoke("track_live_checkpoint_message", {
+        sessionId,
+        projectId,
+        projectPath,
+        message
+      });
+    } catch (error) {
+      console.error("Failed to track live checkpoint message:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Runs the auto-checkpoint scheduler at the end of a turn; resolves to whether a checkpoint was created
    */
   async checkAutoCheckpoint(
     sessionId: string,
@@ -1323,6 +1647,96 @@ export const api = {
     }
   },
 
+  /**
+   * Sets when automatic checkpoints are taken for a session
+   */
+  async updateAutoCheckpointSchedule(
+    sessionId: string,
+    projectId: string,
+    projectPath: string,
+    schedule: AutoCheckpointSchedule
+  ): Promise<void> {
+    try {
+      return await invoke<void>("update_auto_checkpoint_schedule", {
+        sessionId,
+        projectId,
+        projectPath,
+        schedule
+      });
+    } catch (error) {
+      console.error("Failed to update auto-checkpoint schedule:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Sets the size in bytes above which files are left out of a session's checkpoints
+   */
+  async updateCheckpointMaxFileSize(
+    sessionId: string,
+    projectId: string,
+    projectPath: string,
+    maxFileSize: number
+  ): Promise<void> {
+    try {
+      return await invoke<void>("update_checkpoint_max_file_size", {
+        sessionId,
+        projectId,
+        projectPath,
+        maxFileSize
+      });
+    } catch (error) {
+      console.error("Failed to update checkpoint max file size:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Sets when a session's checkpoint writes are flushed to stable storage
+   */
+  async updateCheckpointFsyncPolicy(
+    sessionId: string,
+    projectId: string,
+    projectPath: string,
+    fsyncPolicy: FsyncPolicy
+  ): Promise<void> {
+    try {
+      return await invoke<void>("update_checkpoint_fsync_policy", {
+        sessionId,
+        projectId,
+        projectPath,
+        fsyncPolicy
+      });
+    } catch (error) {
+      console.error("Failed to update checkpoint fsync policy:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Gets the rules the smart checkpoint strategy applies in a project
+   */
+  async getSmartCheckpointRules(projectId: string): Promise<SmartRules> {
+    try {
+      return await invoke<SmartRules>("get_smart_checkpoint_rules", { projectId });
+    } catch (error) {
+      console.error("Failed to get smart checkpoint rules:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Replaces the rules the smart checkpoint strategy applies in a project
+   */
+  async updateSmartCheckpointRules(projectId: string, rules: SmartRules): Promise<void> {
+    try {
+      return await invoke<void>("update_smart_checkpoint_rules", { projectId, rules });
+    } catch (error) {
+      console.error("Failed to update smart checkpoint rules:", error);
+      throw error;
+    }
+  },
+
   /**
    * Triggers cleanup of old checkpoints
    */
@@ -1345,6 +1759,298 @@ export const api = {
     }
   },
 
+  /**
+   * Sets the retention policy for a session and prunes checkpoints it does not keep
+   */
+  async updateRetentionPolicy(
+    sessionId: string,
+    projectId: string,
+    projectPath: string,
+    policy: RetentionPolicy
+  ): Promise<RetentionReport> {
+    try {
+      return await invoke<RetentionReport>("update_retention_policy", {
+        sessionId,
+        projectId,
+        projectPath,
+        policy
+      });
+    } catch (error) {
+      console.error("Failed to update retention policy:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Prunes checkpoints according to the session's retention policy
+   */
+  async applyRetentionPolicy(
+    sessionId: string,
+    projectId: string,
+    projectPath: string
+  ): Promise<RetentionReport> {
+    try {
+      return await invoke<RetentionReport>("apply_retention_policy", {
+        sessionId,
+        projectId,
+        projectPath
+      });
+    } catch (error) {
+      console.error("Failed to apply retention policy:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Reports checkpoint storage for a session, or for the whole project when no session is given
+   */
+  async getCheckpointStorageReport(
+    projectId: string,
+    sessionId?: string
+  ): Promise<StorageReport> {
+    try {
+      return await invoke<StorageReport>("get_checkpoint_storage_report", {
+        projectId,
+        sessionId
+      });
+    } catch (error) {
+      console.error("Failed to get checkpoint storage report:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Searches a project's checkpoints, or one session's, ranked best first
+   */
+  async searchCheckpoints(
+    projectId: string,
+    query: string,
+    options?: CheckpointSearchOptions
+  ): Promise<CheckpointSearchResult[]> {
+    try {
+      return await invoke<CheckpointSearchResult[]>("search_checkpoints", {
+        projectId,
+        query,
+        sessionId: options?.sessionId,
+        includeContents: options?.includeContents,
+        limit: options?.limit
+      });
+    } catch (error) {
+      console.error("Failed to search checkpoints:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Merges the branch of one checkpoint into another, recording a checkpoint with both parents
+   */
+  async mergeCheckpoints(
+    oursCheckpointId: string,
+    theirsCheckpointId: string,
+    sessionId: string,
+    projectId: string,
+    projectPath: string,
+    description?: string
+  ): Promise<MergeResult> {
+    try {
+      return await invoke<MergeResult>("merge_checkpoints", {
+        oursCheckpointId,
+        theirsCheckpointId,
+        sessionId,
+        projectId,
+        projectPath,
+        description
+      });
+    } catch (error) {
+      console.error("Failed to merge checkpoints:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Exports a checkpoint (or its whole branch) to git commits under refs/claude/<session>/<checkpoint>
+   */
+  async exportCheckpointToGit(
+    checkpointId: string,
+    sessionId: string,
+    projectId: string,
+    projectPath: string,
+    includeAncestors?: boolean
+  ): Promise<GitExport[]> {
+    try {
+      return await invoke<GitExport[]>("export_checkpoint_to_git", {
+        checkpointId,
+        sessionId,
+        projectId,
+        projectPath,
+        includeAncestors
+      });
+    } catch (error) {
+      console.error("Failed to export checkpoint to git:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Creates a checkpoint from the files of a git commit, without touching the project
+   *
+   * The new checkpoint only becomes the current one when `makeCurrent` is set.
+   */
+  async seedCheckpointFromGit(
+    rev: string,
+    sessionId: string,
+    projectId: string,
+    projectPath: string,
+    description?: string,
+    makeCurrent?: boolean
+  ): Promise<CheckpointResult> {
+    try {
+      return await invoke<CheckpointResult>("seed_checkpoint_from_git", {
+        rev,
+        sessionId,
+        projectId,
+        projectPath,
+        description,
+        makeCurrent
+      });
+    } catch (error) {
+      console.error("Failed to seed checkpoint from git:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Tags a checkpoint; tags can be used in place of checkpoint IDs
+   */
+  async tagCheckpoint(
+    checkpointId: string,
+    tag: string,
+    sessionId: string,
+    projectId: string,
+    projectPath: string
+  ): Promise<Checkpoint> {
+    try {
+      return await invoke<Checkpoint>("tag_checkpoint", {
+        checkpointId,
+        tag,
+        sessionId,
+        projectId,
+        projectPath
+      });
+    } catch (error) {
+      console.error("Failed to tag checkpoint:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Removes a tag from a checkpoint
+   */
+  async untagCheckpoint(
+    tag: string,
+    sessionId: string,
+    projectId: string,
+    projectPath: string
+  ): Promise<Checkpoint> {
+    try {
+      return await invoke<Checkpoint>("untag_checkpoint", {
+        tag,
+        sessionId,
+        projectId,
+        projectPath
+      });
+    } catch (error) {
+      console.error("Failed to remove checkpoint tag:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Lists checkpoint tags for a session, optionally filtered by a search query
+   */
+  async listCheckpointTags(
+    sessionId: string,
+    projectId: string,
+    projectPath: string,
+    query?: string
+  ): Promise<CheckpointTag[]> {
+    try {
+      return await invoke<CheckpointTag[]>("list_checkpoint_tags", {
+        sessionId,
+        projectId,
+        projectPath,
+        query
+      });
+    } catch (error) {
+      console.error("Failed to list checkpoint tags:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Verifies a session's checkpoints, optionally pruning unrecoverable ones
+   */
+  async verifyCheckpoints(
+    sessionId: string,
+    projectId: string,
+    projectPath: string,
+    repair?: boolean
+  ): Promise<VerifyReport> {
+    try {
+      return await invoke<VerifyReport>("verify_checkpoints", {
+        sessionId,
+        projectId,
+        projectPath,
+        repair
+      });
+    } catch (error) {
+      console.error("Failed to verify checkpoints:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Exports a session's checkpoint timeline to a tar+zstd archive
+   */
+  async exportCheckpointTimeline(
+    sessionId: string,
+    projectId: string,
+    projectPath: string,
+    archivePath: string
+  ): Promise<ArchiveSummary> {
+    try {
+      return await invoke<ArchiveSummary>("export_checkpoint_timeline", {
+        sessionId,
+        projectId,
+        projectPath,
+        archivePath
+      });
+    } catch (error) {
+      console.error("Failed to export checkpoint timeline:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Imports a checkpoint timeline archive into a project
+   */
+  async importCheckpointTimeline(
+    archivePath: string,
+    projectId: string,
+    sessionId?: string
+  ): Promise<ArchiveSummary> {
+    try {
+      return await invoke<ArchiveSummary>("import_checkpoint_timeline", {
+        archivePath,
+        projectId,
+        sessionId
+      });
+    } catch (error) {
+      console.error("Failed to import checkpoint timeline:", error);
+      throw error;
+    }
+  },
+
   /**
    * Gets checkpoint settings for a session
    */
@@ -1357,6 +2063,10 @@ export const api = {
     checkpoint_strategy: CheckpointStrategy;
     total_checkpoints: number;
     current_checkpoint_id?: string;
+    retention_policy: RetentionPolicy;
+    auto_checkpoint_schedule: AutoCheckpointSchedule;
+    max_file_size: number;
+    fsync_policy: FsyncPolicy;
   }> {
     try {
       return await invoke("get_checkpoint_settings", {